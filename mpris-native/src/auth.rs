@@ -0,0 +1,560 @@
+use crate::error::MprisError;
+use crate::state_file::spotify_tui_dir;
+use crate::types::{AuthProgress, AuthProgressKind, AuthenticateResult, SpotifydAuthState, SpotifydConfig};
+use futures::StreamExt;
+use inotify::{Inotify, WatchMask};
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+/// Where spotifyd 0.4's `authenticate` stores OAuth credentials, inside the cache dir
+const OAUTH_CREDENTIALS_FILE: &str = "oauth/credentials.json";
+/// Session credentials librespot caches after a username/password login
+const CREDENTIALS_FILE: &str = "credentials.json";
+/// Where a spawned spotifyd's stdout/stderr are captured
+const LOG_FILE: &str = "spotifyd.log";
+/// Only the end of the log is scanned; auth failures happen right after login
+const LOG_TAIL_BYTES: u64 = 64 * 1024;
+/// Log fragments (lowercased) that mean spotifyd couldn't log in
+const AUTH_FAILURE_PATTERNS: &[&str] = &[
+    "bad credentials",
+    "badcredentials",
+    "login failed",
+    "authentication failed",
+    "failed to authenticate",
+    "invalid credentials",
+];
+
+/// spotifyd's default cache dir ($XDG_CACHE_HOME/spotifyd or ~/.cache/spotifyd)
+fn default_cache_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(base.join("spotifyd"))
+}
+
+/// `cache_path` from a spotifyd config file
+fn cache_path_from_file(path: &Path) -> Option<PathBuf> {
+    let table: toml::Table = std::fs::read_to_string(path).ok()?.parse().ok()?;
+    ["global", "spotifyd"].iter().find_map(|section| {
+        table
+            .get(*section)?
+            .get("cache_path")?
+            .as_str()
+            .map(PathBuf::from)
+    })
+}
+
+/// Cache dir spotifyd will use with this config
+pub fn cache_dir(config: &SpotifydConfig) -> Option<PathBuf> {
+    if let Some(ref path) = config.cache_path {
+        return Some(PathBuf::from(path));
+    }
+    // The file spotifyd reads: the user-supplied one, else its default
+    let file = config
+        .config_path
+        .as_ref()
+        .map(PathBuf::from)
+        .or_else(crate::spotifyd_config::user_config_path);
+    if let Some(dir) = file.and_then(|path| cache_path_from_file(&path)) {
+        return Some(dir);
+    }
+    default_cache_dir()
+}
+
+/// File `spotifyd authenticate` writes OAuth credentials to
+pub fn credentials_path(config: &SpotifydConfig) -> Option<PathBuf> {
+    cache_dir(config).map(|dir| dir.join(OAUTH_CREDENTIALS_FILE))
+}
+
+/// Whether `path` holds complete credentials (username + auth_data)
+fn is_valid_credentials(path: &Path) -> bool {
+    let Ok(bytes) = std::fs::read(path) else {
+        return false;
+    };
+    let Ok(creds) = serde_json::from_slice::<serde_json::Value>(&bytes) else {
+        return false;
+    };
+    let non_empty = |key: &str| creds.get(key).is_some_and(|v| !v.is_null() && v != "");
+    non_empty("username") && non_empty("auth_data")
+}
+
+/// Whether usable cached credentials exist, from OAuth or an earlier session
+pub fn has_cached_credentials(config: &SpotifydConfig) -> bool {
+    let Some(dir) = cache_dir(config) else {
+        return false;
+    };
+    [OAUTH_CREDENTIALS_FILE, CREDENTIALS_FILE]
+        .iter()
+        .any(|file| is_valid_credentials(&dir.join(file)))
+}
+
+/// Whether spotifyd has anything to log in with
+pub fn has_credentials(config: &SpotifydConfig) -> bool {
+    (config.username.is_some() && config.password.is_some()) || has_cached_credentials(config)
+}
+
+/// Where we capture the output of spotifyd processes we spawn
+pub fn log_path() -> Option<PathBuf> {
+    spotify_tui_dir().map(|dir| dir.join(LOG_FILE))
+}
+
+/// Last log line reporting an authentication failure
+pub fn find_auth_failure(log: &str) -> Option<String> {
+    log.lines()
+        .rev()
+        .find(|line| {
+            let line = line.to_lowercase();
+            AUTH_FAILURE_PATTERNS.iter().any(|p| line.contains(p))
+        })
+        .map(|line| line.trim().to_string())
+}
+
+/// Scan the end of a captured spotifyd log for an authentication failure
+pub fn read_auth_failure(path: &Path) -> Option<String> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(LOG_TAIL_BYTES))).ok()?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).ok()?;
+    find_auth_failure(&String::from_utf8_lossy(&bytes))
+}
+
+/// Decide the auth state of a running spotifyd.
+///
+/// spotifyd only registers its D-Bus names once a session is established, so
+/// `registered` is conclusive. Otherwise a failure in its log wins, and we
+/// fall back to whether it has any credentials to log in with.
+pub fn assess(
+    config: &SpotifydConfig,
+    registered: bool,
+    failure: Option<String>,
+) -> (SpotifydAuthState, Option<String>) {
+    if registered {
+        return (SpotifydAuthState::Authenticated, None);
+    }
+    if let Some(line) = failure {
+        return (SpotifydAuthState::Failed, Some(line));
+    }
+    if has_credentials(config) {
+        (SpotifydAuthState::Pending, None)
+    } else {
+        (SpotifydAuthState::NoCredentials, None)
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// OAuth login (`spotifyd authenticate`)
+// ─────────────────────────────────────────────────────────────
+
+/// Default limit for finishing the browser login
+pub const DEFAULT_AUTH_TIMEOUT: Duration = Duration::from_secs(120);
+/// How long to wait for the credentials file once spotifyd exits successfully
+const CREDENTIALS_GRACE: Duration = Duration::from_secs(30);
+/// How long spotifyd gets to exit by itself after writing credentials
+const AUTH_EXIT_GRACE: Duration = Duration::from_secs(2);
+/// Output lines kept to explain a failed login
+const OUTPUT_TAIL_LINES: usize = 20;
+
+/// How to run `spotifyd authenticate`
+pub struct AuthCommand {
+    pub binary: String,
+    pub args: Vec<String>,
+    pub env: Vec<(OsString, OsString)>,
+    pub working_dir: Option<String>,
+    /// File spotifyd writes on success
+    pub credentials_path: PathBuf,
+    pub timeout: Duration,
+}
+
+/// The accounts.spotify.com login URL in a line of spotifyd output
+pub fn find_login_url(line: &str) -> Option<&str> {
+    let start = line.find("https://accounts.spotify.com")?;
+    line[start..].split_whitespace().next()
+}
+
+/// Next line from an output stream, or pending forever once it's closed
+async fn next_line(
+    lines: &mut Option<tokio::io::Lines<BufReader<impl tokio::io::AsyncRead + Unpin>>>,
+) -> Option<String> {
+    match lines {
+        Some(reader) => match reader.next_line().await {
+            Ok(Some(line)) => Some(line),
+            _ => {
+                *lines = None;
+                None
+            }
+        },
+        None => std::future::pending().await,
+    }
+}
+
+/// Run `spotifyd authenticate` until the credentials file is written.
+///
+/// Progress is reported through `on_progress`; opening the login URL is left
+/// to the caller. The credentials file is detected with inotify on its
+/// directory, which is created up front so it can be watched. Gives up on
+/// `cancel`, the timeout, or spotifyd failing.
+pub async fn run_authenticate(
+    command: AuthCommand,
+    cancel: &Notify,
+    on_progress: impl Fn(AuthProgress),
+) -> Result<AuthenticateResult, MprisError> {
+    let credentials_path = command.credentials_path;
+    let credentials_name = credentials_path.file_name().map(|n| n.to_os_string());
+    let credentials_dir = credentials_path
+        .parent()
+        .ok_or_else(|| MprisError::InvalidConfig("invalid credentials path".to_string()))?;
+    std::fs::create_dir_all(credentials_dir)?;
+
+    // Watch before spawning so an early write isn't missed
+    let inotify = Inotify::init()?;
+    inotify
+        .watches()
+        .add(credentials_dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO)?;
+    let mut fs_events = inotify.into_event_stream(vec![0u8; 4096])?;
+
+    let mut process = tokio::process::Command::new(&command.binary);
+    process
+        .args(&command.args)
+        .env_clear()
+        .envs(command.env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(ref dir) = command.working_dir {
+        process.current_dir(dir);
+    }
+    let mut child = process.spawn().map_err(|source| MprisError::ExecFailed {
+        path: command.binary.clone(),
+        source,
+    })?;
+
+    info!("Running spotifyd authenticate (PID {:?})", child.id());
+    on_progress(AuthProgress {
+        kind: AuthProgressKind::Started,
+        url: None,
+        path: None,
+    });
+
+    let mut stdout = child.stdout.take().map(|out| BufReader::new(out).lines());
+    let mut stderr = child.stderr.take().map(|err| BufReader::new(err).lines());
+    let mut output: VecDeque<String> = VecDeque::new();
+    let mut auth_url: Option<String> = None;
+    let mut exit_status: Option<std::process::ExitStatus> = None;
+
+    let deadline = tokio::time::sleep(command.timeout);
+    tokio::pin!(deadline);
+    let cancelled = cancel.notified();
+    tokio::pin!(cancelled);
+
+    let result = |success: bool, message: String, auth_url: &Option<String>| AuthenticateResult {
+        success,
+        message,
+        auth_url: auth_url.clone(),
+        credentials_path: success.then(|| credentials_path.to_string_lossy().into_owned()),
+        cancelled: false,
+    };
+
+    loop {
+        // A failed exit is final once its output has been drained
+        if let Some(status) = exit_status.filter(|s| !s.success()) {
+            if stdout.is_none() && stderr.is_none() {
+                let message = match output.is_empty() {
+                    true => format!("spotifyd authenticate exited with {}", status),
+                    false => Vec::from(output).join("\n"),
+                };
+                warn!("spotifyd authenticate failed: {}", message);
+                return Ok(result(false, message, &auth_url));
+            }
+        }
+
+        let line = tokio::select! {
+            _ = &mut cancelled => {
+                info!("spotifyd authenticate cancelled");
+                child.kill().await.ok();
+                return Ok(AuthenticateResult {
+                    cancelled: true,
+                    ..result(false, "Authentication cancelled".to_string(), &auth_url)
+                });
+            }
+            _ = &mut deadline => {
+                child.kill().await.ok();
+                let message = match exit_status {
+                    Some(_) => "Authentication completed but credentials were not written".to_string(),
+                    None => format!("Authentication timed out after {}s", command.timeout.as_secs()),
+                };
+                return Ok(result(false, message, &auth_url));
+            }
+            status = child.wait(), if exit_status.is_none() => {
+                let status = status?;
+                exit_status = Some(status);
+                if status.success() {
+                    // spotifyd may still be flushing the file; don't wait the full timeout
+                    let grace = tokio::time::Instant::now() + CREDENTIALS_GRACE;
+                    if grace < deadline.deadline() {
+                        deadline.as_mut().reset(grace);
+                    }
+                }
+                continue;
+            }
+            event = fs_events.next() => {
+                let Some(Ok(event)) = event else { continue };
+                if event.name != credentials_name || !is_valid_credentials(&credentials_path) {
+                    continue;
+                }
+                info!("spotifyd wrote credentials to {}", credentials_path.display());
+                on_progress(AuthProgress {
+                    kind: AuthProgressKind::CredentialsWritten,
+                    url: auth_url.clone(),
+                    path: Some(credentials_path.to_string_lossy().into_owned()),
+                });
+                if exit_status.is_none()
+                    && tokio::time::timeout(AUTH_EXIT_GRACE, child.wait()).await.is_err()
+                {
+                    child.kill().await.ok();
+                }
+                return Ok(result(true, "Authentication successful".to_string(), &auth_url));
+            }
+            line = next_line(&mut stdout) => line,
+            line = next_line(&mut stderr) => line,
+        };
+
+        let Some(line) = line else { continue };
+        if auth_url.is_none() {
+            if let Some(url) = find_login_url(&line) {
+                auth_url = Some(url.to_string());
+                on_progress(AuthProgress {
+                    kind: AuthProgressKind::UrlFound,
+                    url: auth_url.clone(),
+                    path: None,
+                });
+                on_progress(AuthProgress {
+                    kind: AuthProgressKind::Waiting,
+                    url: auth_url.clone(),
+                    path: None,
+                });
+            }
+        }
+        output.push_back(line);
+        if output.len() > OUTPUT_TAIL_LINES {
+            output.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{write_script, SPAWN_LOCK};
+    use std::sync::Mutex;
+
+    fn config_with_cache(dir: &Path) -> SpotifydConfig {
+        SpotifydConfig {
+            cache_path: Some(dir.to_string_lossy().to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn detects_auth_failures_in_log() {
+        let log = "\
+[INFO] Connecting to AP \"ap-gew4.spotify.com:4070\"
+[ERROR] Login failed with reason: Bad credentials
+[INFO] Shutting down";
+        assert_eq!(
+            find_auth_failure(log).as_deref(),
+            Some("[ERROR] Login failed with reason: Bad credentials")
+        );
+        assert_eq!(find_auth_failure("[INFO] Authenticated as \"user\" !"), None);
+    }
+
+    #[test]
+    fn cached_credentials_must_be_complete() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config_with_cache(dir.path());
+        assert!(!has_cached_credentials(&config));
+
+        let path = dir.path().join(CREDENTIALS_FILE);
+        std::fs::write(&path, "not json").unwrap();
+        assert!(!has_cached_credentials(&config));
+
+        std::fs::write(&path, r#"{"username":"user","auth_type":1,"auth_data":""}"#).unwrap();
+        assert!(!has_cached_credentials(&config));
+
+        std::fs::write(&path, r#"{"username":"user","auth_type":1,"auth_data":"AQD3"}"#).unwrap();
+        assert!(has_cached_credentials(&config));
+        std::fs::remove_file(&path).unwrap();
+
+        // spotifyd 0.4 OAuth login
+        let oauth = credentials_path(&config).unwrap();
+        std::fs::create_dir_all(oauth.parent().unwrap()).unwrap();
+        std::fs::write(&oauth, r#"{"username":"user","auth_type":3,"auth_data":"AQD3"}"#).unwrap();
+        assert!(has_cached_credentials(&config));
+    }
+
+    #[test]
+    fn cache_dir_comes_from_user_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let conf = dir.path().join("spotifyd.conf");
+        std::fs::write(&conf, "[global]\ncache_path = \"/srv/spotifyd-cache\"\n").unwrap();
+
+        let config = SpotifydConfig {
+            config_path: Some(conf.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        assert_eq!(cache_dir(&config), Some(PathBuf::from("/srv/spotifyd-cache")));
+    }
+
+    #[test]
+    fn assess_prefers_registration_then_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config_with_cache(dir.path());
+
+        assert_eq!(
+            assess(&config, true, Some("Login failed".to_string())).0,
+            SpotifydAuthState::Authenticated
+        );
+        assert_eq!(
+            assess(&config, false, Some("Login failed".to_string())),
+            (SpotifydAuthState::Failed, Some("Login failed".to_string()))
+        );
+        assert_eq!(assess(&config, false, None).0, SpotifydAuthState::NoCredentials);
+
+        let with_password = SpotifydConfig {
+            username: Some("user".to_string()),
+            password: Some("secret".to_string()),
+            ..config
+        };
+        assert_eq!(assess(&with_password, false, None).0, SpotifydAuthState::Pending);
+    }
+
+    const LOGIN_URL: &str = "https://accounts.spotify.com/authorize?client_id=abc&state=xyz";
+
+    /// Run a fake `spotifyd authenticate` script, collecting progress events
+    fn run_fake(
+        body: &str,
+        timeout: Duration,
+        cancel_after: Option<Duration>,
+    ) -> (AuthenticateResult, Vec<AuthProgressKind>, PathBuf) {
+        let _guard = SPAWN_LOCK.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let credentials_path = dir.path().join("cache").join(OAUTH_CREDENTIALS_FILE);
+        let body = body.replace("$CREDS", &credentials_path.to_string_lossy());
+        let binary = write_script(dir.path(), "spotifyd", &body, 0o755);
+
+        let events = Mutex::new(Vec::new());
+        let cancel = Notify::new();
+        let command = AuthCommand {
+            binary,
+            args: vec!["authenticate".to_string()],
+            env: std::env::vars_os().collect(),
+            working_dir: None,
+            credentials_path: credentials_path.clone(),
+            timeout,
+        };
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let result = runtime.block_on(async {
+            let run = run_authenticate(command, &cancel, |p| events.lock().unwrap().push(p.kind));
+            match cancel_after {
+                Some(delay) => {
+                    let cancel_later = async {
+                        tokio::time::sleep(delay).await;
+                        cancel.notify_one();
+                        std::future::pending::<()>().await
+                    };
+                    tokio::select! {
+                        result = run => result,
+                        _ = cancel_later => unreachable!(),
+                    }
+                }
+                None => run.await,
+            }
+        });
+
+        (result.unwrap(), events.into_inner().unwrap(), credentials_path)
+    }
+
+    #[test]
+    fn authenticate_reports_progress_until_credentials_written() {
+        let script = format!(
+            "echo 'OAuth server listening on 127.0.0.1:8888'\n\
+             echo 'Browse to: {}'\n\
+             sleep 0.2\n\
+             printf '{{\"username\":\"user\",\"auth_type\":1,\"auth_data\":\"AQD3\"}}' > $CREDS.tmp\n\
+             mv $CREDS.tmp $CREDS\n\
+             exit 0",
+            LOGIN_URL
+        );
+        let (result, events, path) = run_fake(&script, Duration::from_secs(10), None);
+
+        assert!(result.success, "{}", result.message);
+        assert_eq!(result.auth_url.as_deref(), Some(LOGIN_URL));
+        assert_eq!(result.credentials_path, Some(path.to_string_lossy().into_owned()));
+        assert_eq!(
+            events,
+            [
+                AuthProgressKind::Started,
+                AuthProgressKind::UrlFound,
+                AuthProgressKind::Waiting,
+                AuthProgressKind::CredentialsWritten,
+            ]
+        );
+    }
+
+    #[test]
+    fn authenticate_reports_spotifyd_errors() {
+        let (result, events, _) = run_fake(
+            "echo 'Error: failed to bind OAuth server: address in use' >&2\nexit 1",
+            Duration::from_secs(10),
+            None,
+        );
+
+        assert!(!result.success);
+        assert!(!result.cancelled);
+        assert!(result.message.contains("address in use"), "{}", result.message);
+        assert_eq!(events, [AuthProgressKind::Started]);
+    }
+
+    #[test]
+    fn authenticate_times_out() {
+        let (result, events, _) = run_fake(
+            &format!("echo 'Browse to: {}'\nexec sleep 30", LOGIN_URL),
+            Duration::from_millis(500),
+            None,
+        );
+
+        assert!(!result.success);
+        assert!(result.message.contains("timed out"), "{}", result.message);
+        assert_eq!(result.auth_url.as_deref(), Some(LOGIN_URL));
+        assert_eq!(events.last(), Some(&AuthProgressKind::Waiting));
+    }
+
+    #[test]
+    fn authenticate_can_be_cancelled() {
+        let (result, _, path) = run_fake(
+            &format!("echo 'Browse to: {}'\nexec sleep 30", LOGIN_URL),
+            Duration::from_secs(10),
+            Some(Duration::from_millis(300)),
+        );
+
+        assert!(result.cancelled);
+        assert!(!result.success);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn finds_login_url_in_output() {
+        assert_eq!(find_login_url(&format!("Browse to: {} ", LOGIN_URL)), Some(LOGIN_URL));
+        assert_eq!(find_login_url("OAuth server listening"), None);
+    }
+}
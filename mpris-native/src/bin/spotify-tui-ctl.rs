@@ -0,0 +1,458 @@
+//! Control spotifyd from the command line without starting the TUI, e.g.
+//! from i3/sway media key bindings or scripts.
+
+use std::process::ExitCode;
+use std::time::Duration;
+
+use mpris_native::controller::ControllerInner;
+use mpris_native::error::MprisError;
+use mpris_native::supervisor::SupervisorInner;
+use mpris_native::types::{PlaybackState, RepeatMode, SpotifydConfig};
+use tokio::sync::broadcast;
+
+const USAGE: &str = "\
+Usage: spotify-tui-ctl [--json] <command>
+
+Commands:
+  status                      Show what's playing
+  play | pause | toggle       Control playback
+  next | previous             Change track
+  seek <+/-offset>            Seek relative, e.g. +10s, -5s, +1m, +500ms
+  volume <level>              Set volume, e.g. 60%, +5%, -10%
+  shuffle <on|off|toggle>     Set shuffle
+  repeat <none|playlist|track>
+                              Set repeat mode
+  daemon <start|stop|status>  Manage the spotifyd daemon
+  watch                       Print the playback state as JSON lines on every change
+
+Options:
+  --json                      Print status as JSON
+  -h, --help                  Show this help";
+
+/// Same device name the TUI starts spotifyd with, so either adopts the other's daemon
+const DEVICE_NAME: &str = "spotify-tui";
+
+type CliResult = Result<(), Box<dyn std::error::Error>>;
+
+// ─────────────────────────────────────────────────────────────
+// Argument parsing
+// ─────────────────────────────────────────────────────────────
+
+#[derive(Debug, PartialEq)]
+enum Volume {
+    /// Absolute level, 0.0-1.0
+    Set(f64),
+    /// Change relative to the current level
+    Adjust(f64),
+}
+
+#[derive(Debug, PartialEq)]
+enum Switch {
+    On,
+    Off,
+    Toggle,
+}
+
+#[derive(Debug, PartialEq)]
+enum DaemonCommand {
+    Start,
+    Stop,
+    Status,
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Status,
+    Play,
+    Pause,
+    Toggle,
+    Next,
+    Previous,
+    Seek(i64),
+    Volume(Volume),
+    Shuffle(Switch),
+    Repeat(RepeatMode),
+    Daemon(DaemonCommand),
+    Watch,
+}
+
+#[derive(Debug, PartialEq)]
+struct Invocation {
+    command: Command,
+    json: bool,
+}
+
+/// Relative seek offset in ms: "+10s", "-5s", "+1m", "+500ms"
+fn parse_seek(arg: &str) -> Result<i64, String> {
+    let invalid = || format!("invalid seek offset {:?} (expected e.g. +10s or -5s)", arg);
+    let (sign, rest) = match arg.as_bytes().first() {
+        Some(b'+') => (1, &arg[1..]),
+        Some(b'-') => (-1, &arg[1..]),
+        _ => return Err(invalid()),
+    };
+    let (number, unit_ms) = if let Some(n) = rest.strip_suffix("ms") {
+        (n, 1.0)
+    } else if let Some(n) = rest.strip_suffix('s') {
+        (n, 1000.0)
+    } else if let Some(n) = rest.strip_suffix('m') {
+        (n, 60_000.0)
+    } else {
+        (rest, 1000.0)
+    };
+    let value: f64 = number.parse().map_err(|_| invalid())?;
+    if !value.is_finite() || value < 0.0 {
+        return Err(invalid());
+    }
+    Ok(sign * (value * unit_ms).round() as i64)
+}
+
+/// Volume as a percentage, absolute ("60%", "60") or relative ("+5%", "-10%")
+fn parse_volume(arg: &str) -> Result<Volume, String> {
+    let invalid = || format!("invalid volume {:?} (expected e.g. 60%, +5% or -10%)", arg);
+    let number = arg.strip_suffix('%').unwrap_or(arg);
+    let percent: f64 = number.parse().map_err(|_| invalid())?;
+    if !percent.is_finite() {
+        return Err(invalid());
+    }
+    if number.starts_with(['+', '-']) {
+        return Ok(Volume::Adjust(percent / 100.0));
+    }
+    if !(0.0..=100.0).contains(&percent) {
+        return Err(format!("volume must be 0-100%, got {}", arg));
+    }
+    Ok(Volume::Set(percent / 100.0))
+}
+
+fn parse_switch(arg: &str) -> Result<Switch, String> {
+    match arg {
+        "on" | "true" => Ok(Switch::On),
+        "off" | "false" => Ok(Switch::Off),
+        "toggle" => Ok(Switch::Toggle),
+        _ => Err(format!("expected on, off or toggle, got {:?}", arg)),
+    }
+}
+
+fn parse_repeat(arg: &str) -> Result<RepeatMode, String> {
+    match arg {
+        "none" | "off" => Ok(RepeatMode::None),
+        "playlist" | "context" => Ok(RepeatMode::Playlist),
+        "track" => Ok(RepeatMode::Track),
+        _ => Err(format!("expected none, playlist or track, got {:?}", arg)),
+    }
+}
+
+fn parse_daemon(arg: &str) -> Result<DaemonCommand, String> {
+    match arg {
+        "start" => Ok(DaemonCommand::Start),
+        "stop" => Ok(DaemonCommand::Stop),
+        "status" => Ok(DaemonCommand::Status),
+        _ => Err(format!("expected start, stop or status, got {:?}", arg)),
+    }
+}
+
+/// Parse the arguments after the program name. `Ok(None)` means help was asked for.
+fn parse_args(args: &[String]) -> Result<Option<Invocation>, String> {
+    let mut json = false;
+    let mut words = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "-h" | "--help" | "help" => return Ok(None),
+            "--json" => json = true,
+            _ => words.push(arg.as_str()),
+        }
+    }
+
+    let (name, rest) = words.split_first().ok_or("missing command")?;
+    let argument = |what: &str| match rest {
+        [value] => Ok(*value),
+        [] => Err(format!("{} needs {}", name, what)),
+        _ => Err(format!("{} takes a single argument", name)),
+    };
+    let no_argument = |command: Command| match rest {
+        [] => Ok(command),
+        _ => Err(format!("{} takes no arguments", name)),
+    };
+
+    let command = match *name {
+        "status" => no_argument(Command::Status)?,
+        "play" => no_argument(Command::Play)?,
+        "pause" => no_argument(Command::Pause)?,
+        "toggle" | "play-pause" => no_argument(Command::Toggle)?,
+        "next" => no_argument(Command::Next)?,
+        "previous" | "prev" => no_argument(Command::Previous)?,
+        "watch" => no_argument(Command::Watch)?,
+        "seek" => Command::Seek(parse_seek(argument("an offset")?)?),
+        "volume" => Command::Volume(parse_volume(argument("a level")?)?),
+        "shuffle" => Command::Shuffle(parse_switch(argument("on, off or toggle")?)?),
+        "repeat" => Command::Repeat(parse_repeat(argument("a mode")?)?),
+        "daemon" => Command::Daemon(parse_daemon(argument("start, stop or status")?)?),
+        other => return Err(format!("unknown command {:?}", other)),
+    };
+    Ok(Some(Invocation { command, json }))
+}
+
+// ─────────────────────────────────────────────────────────────
+// Output
+// ─────────────────────────────────────────────────────────────
+
+fn format_time(ms: i64) -> String {
+    let secs = ms.max(0) / 1000;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// One-line summary, e.g. "Playing: Artist - Title [1:23/3:45] vol 60% shuffle off repeat None"
+fn describe(state: &PlaybackState) -> String {
+    let track = match state.track {
+        Some(ref track) => format!(
+            "{}: {} - {} [{}/{}]",
+            if state.is_playing { "Playing" } else { "Paused" },
+            track.artist,
+            track.title,
+            format_time(state.position_ms),
+            format_time(state.duration_ms)
+        ),
+        None => "Nothing playing".to_string(),
+    };
+    format!(
+        "{} vol {}% shuffle {} repeat {:?}",
+        track,
+        (state.volume * 100.0).round(),
+        if state.shuffle { "on" } else { "off" },
+        state.repeat
+    )
+}
+
+fn print_json(state: &PlaybackState) -> CliResult {
+    println!("{}", serde_json::to_string(state)?);
+    Ok(())
+}
+
+// ─────────────────────────────────────────────────────────────
+// Commands
+// ─────────────────────────────────────────────────────────────
+
+async fn controller() -> Result<ControllerInner, MprisError> {
+    let controller = ControllerInner::new().await?;
+    controller.connect().await?;
+    Ok(controller)
+}
+
+async fn run_player(command: Command, json: bool) -> CliResult {
+    let controller = controller().await?;
+    let state = controller.state().await;
+
+    match command {
+        Command::Status if json => print_json(&state)?,
+        Command::Status => println!("{}", describe(&state)),
+        Command::Play => controller.play().await?,
+        Command::Pause => controller.pause().await?,
+        Command::Toggle => {
+            controller.play_pause().await?;
+        }
+        Command::Next => controller.next().await?,
+        Command::Previous => controller.previous().await?,
+        Command::Seek(offset_ms) => controller.seek(offset_ms).await?,
+        Command::Volume(Volume::Set(level)) => controller.set_volume(level).await?,
+        Command::Volume(Volume::Adjust(delta)) => {
+            controller.adjust_volume(delta).await?;
+        }
+        Command::Shuffle(switch) => {
+            let shuffle = match switch {
+                Switch::On => true,
+                Switch::Off => false,
+                Switch::Toggle => !state.shuffle,
+            };
+            controller.set_shuffle(shuffle).await?
+        }
+        Command::Repeat(mode) => controller.set_repeat(mode).await?,
+        Command::Watch => watch(&controller, state).await?,
+        Command::Daemon(_) => unreachable!("handled by run_daemon"),
+    }
+    Ok(())
+}
+
+/// Print `initial` and then every state change, until the player goes away
+async fn watch(controller: &ControllerInner, initial: PlaybackState) -> CliResult {
+    let mut changes = controller.subscribe_state_changes();
+    let gone = controller.player_gone();
+    tokio::pin!(gone);
+    print_json(&initial)?;
+    loop {
+        let change = tokio::select! {
+            change = changes.recv() => change,
+            _ = &mut gone => return Ok(()),
+        };
+        match change {
+            Ok(state) => print_json(&state)?,
+            // Only the latest state matters
+            Err(broadcast::error::RecvError::Lagged(_)) => print_json(&controller.state().await)?,
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
+async fn run_daemon(command: DaemonCommand) -> CliResult {
+    let supervisor = SupervisorInner::new(SpotifydConfig {
+        device_name: Some(DEVICE_NAME.to_string()),
+        ..Default::default()
+    });
+
+    match command {
+        DaemonCommand::Start => {
+            let result = supervisor.start_or_adopt().await?;
+            if !result.success {
+                return Err(result.message.into());
+            }
+            println!("{} (PID {})", result.message, result.pid.unwrap_or_default());
+        }
+        DaemonCommand::Stop => {
+            let result = supervisor.stop(true, None).await?;
+            if !result.stopped {
+                return Err(result.message.into());
+            }
+            println!("{}", result.message);
+        }
+        DaemonCommand::Status => {
+            let processes = supervisor.list_spotifyd_processes();
+            if processes.is_empty() {
+                println!("spotifyd is not running");
+            }
+            let responsive = supervisor.check_dbus_responsive().await;
+            for process in processes {
+                println!(
+                    "spotifyd running (PID {}{}){}",
+                    process.pid,
+                    process
+                        .binary_path
+                        .map(|path| format!(", {}", path))
+                        .unwrap_or_default(),
+                    if responsive { "" } else { ", MPRIS not registered" }
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let invocation = match parse_args(&args) {
+        Ok(Some(invocation)) => invocation,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("spotify-tui-ctl: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("spotify-tui-ctl: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let result = runtime.block_on(async {
+        match invocation.command {
+            Command::Daemon(command) => run_daemon(command).await,
+            command => run_player(command, invocation.json).await,
+        }
+    });
+    // Don't wait on background D-Bus tasks
+    runtime.shutdown_timeout(Duration::from_millis(100));
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("spotify-tui-ctl: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Invocation>, String> {
+        parse_args(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+    }
+
+    fn command(args: &[&str]) -> Command {
+        parse(args).unwrap().unwrap().command
+    }
+
+    #[test]
+    fn parses_seek_offsets() {
+        assert_eq!(parse_seek("+10s"), Ok(10_000));
+        assert_eq!(parse_seek("-5s"), Ok(-5_000));
+        assert_eq!(parse_seek("+1m"), Ok(60_000));
+        assert_eq!(parse_seek("+500ms"), Ok(500));
+        assert_eq!(parse_seek("+2.5"), Ok(2_500));
+        assert!(parse_seek("10s").is_err());
+        assert!(parse_seek("+s").is_err());
+        assert!(parse_seek("+10h").is_err());
+    }
+
+    #[test]
+    fn parses_volume_levels() {
+        assert_eq!(parse_volume("60%"), Ok(Volume::Set(0.6)));
+        assert_eq!(parse_volume("100"), Ok(Volume::Set(1.0)));
+        assert_eq!(parse_volume("+5%"), Ok(Volume::Adjust(0.05)));
+        assert_eq!(parse_volume("-10%"), Ok(Volume::Adjust(-0.1)));
+        assert!(parse_volume("150%").is_err());
+        assert!(parse_volume("loud").is_err());
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(command(&["seek", "+10s"]), Command::Seek(10_000));
+        assert_eq!(command(&["shuffle", "on"]), Command::Shuffle(Switch::On));
+        assert_eq!(command(&["repeat", "track"]), Command::Repeat(RepeatMode::Track));
+        assert_eq!(command(&["daemon", "stop"]), Command::Daemon(DaemonCommand::Stop));
+        // A leading "-" is an offset, not an option
+        assert_eq!(command(&["seek", "-5s"]), Command::Seek(-5_000));
+        assert_eq!(
+            parse(&["--json", "status"]).unwrap(),
+            Some(Invocation {
+                command: Command::Status,
+                json: true
+            })
+        );
+        assert_eq!(parse(&["--help"]).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_bad_usage() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["rewind"]).is_err());
+        assert!(parse(&["play", "now"]).is_err());
+        assert!(parse(&["seek"]).is_err());
+        assert!(parse(&["daemon", "restart"]).is_err());
+    }
+
+    #[test]
+    fn describes_state() {
+        let state = PlaybackState {
+            is_playing: true,
+            position_ms: 83_000,
+            duration_ms: 225_000,
+            volume: 0.6,
+            track: Some(mpris_native::types::TrackInfo {
+                title: "Song".to_string(),
+                artist: "Artist".to_string(),
+                album: "Album".to_string(),
+                art_url: None,
+                uri: String::new(),
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            describe(&state),
+            "Playing: Artist - Song [1:23/3:45] vol 60% shuffle off repeat None"
+        );
+    }
+}
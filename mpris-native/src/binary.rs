@@ -0,0 +1,337 @@
+use crate::error::MprisError;
+use crate::state_file::spotify_tui_dir;
+use crate::types::{
+    SpotifydBinarySource, SpotifydCandidate, SpotifydConfig, SpotifydDetection, SpotifydFeatures,
+};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Binary name looked up in ~/.spotify-tui/bin and PATH
+const SPOTIFYD_BINARY: &str = "spotifyd";
+/// Overrides the binary for every spotify-tui session
+pub const SPOTIFYD_PATH_ENV: &str = "SPOTIFY_TUI_SPOTIFYD_PATH";
+/// Limit for `--version` / `--help`, in case the binary hangs
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+/// First release with `spotifyd authenticate` and the rs.spotifyd.Controls interface
+const OAUTH_VERSION: (u32, u32, u32) = (0, 4, 0);
+
+/// Every place a spotifyd binary may come from, in priority order
+fn candidate_paths(config: &SpotifydConfig) -> Vec<(PathBuf, SpotifydBinarySource)> {
+    let mut candidates = Vec::new();
+
+    if let Some(ref path) = config.binary_path {
+        candidates.push((PathBuf::from(path), SpotifydBinarySource::Config));
+    }
+    if let Some(path) = std::env::var_os(SPOTIFYD_PATH_ENV).filter(|p| !p.is_empty()) {
+        candidates.push((PathBuf::from(path), SpotifydBinarySource::Environment));
+    }
+    if let Some(dir) = spotify_tui_dir() {
+        candidates.push((dir.join("bin").join(SPOTIFYD_BINARY), SpotifydBinarySource::Downloaded));
+    }
+    if let Some(path) = std::env::var_os("PATH") {
+        if let Some(found) = std::env::split_paths(&path)
+            .map(|dir| dir.join(SPOTIFYD_BINARY))
+            .find(|p| p.is_file())
+        {
+            candidates.push((found, SpotifydBinarySource::Path));
+        }
+    }
+
+    candidates
+}
+
+/// Whether we may execute `path`
+fn is_executable(path: &Path) -> bool {
+    if !path.is_file() {
+        return false;
+    }
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe { libc::access(c_path.as_ptr(), libc::X_OK) == 0 }
+}
+
+/// Parse `major.minor.patch` out of `spotifyd --version` output
+pub fn parse_version(output: &str) -> Option<(u32, u32, u32)> {
+    output.split_whitespace().find_map(|word| {
+        let word = word.trim_start_matches('v');
+        let mut parts = word.split(|c: char| !c.is_ascii_digit());
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.parse().ok()?;
+        let patch = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+        Some((major, minor, patch))
+    })
+}
+
+/// What a spotifyd build supports, from its version and `--help` output.
+///
+/// MPRIS (and with it the controls interface) is a compile-time feature;
+/// builds with it have a `--use-mpris` flag.
+pub fn features(version: Option<(u32, u32, u32)>, help: &str) -> SpotifydFeatures {
+    let modern = version.is_some_and(|v| v >= OAUTH_VERSION);
+    let mpris = help.contains("--use-mpris");
+    SpotifydFeatures {
+        authenticate: modern,
+        mpris,
+        controls: modern && mpris,
+    }
+}
+
+/// Run the binary with one argument and capture stdout (stderr as fallback)
+async fn run_probe(path: &Path, arg: &str) -> Result<String, String> {
+    let output = tokio::process::Command::new(path)
+        .arg(arg)
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    match tokio::time::timeout(PROBE_TIMEOUT, output).await {
+        Err(_) => Err(format!("`{}` timed out", arg)),
+        Ok(Err(e)) => Err(format!("failed to run: {}", e)),
+        Ok(Ok(out)) if !out.status.success() => Err(format!("`{}` exited with {}", arg, out.status)),
+        Ok(Ok(out)) => {
+            let text = match out.stdout.is_empty() {
+                true => out.stderr,
+                false => out.stdout,
+            };
+            Ok(String::from_utf8_lossy(&text).trim().to_string())
+        }
+    }
+}
+
+/// Version reported by a binary's `--version`, as major.minor.patch
+pub async fn version(path: &Path) -> Result<String, String> {
+    let output = run_probe(path, "--version").await?;
+    parse_version(&output)
+        .map(|(a, b, c)| format!("{}.{}.{}", a, b, c))
+        .ok_or_else(|| format!("unrecognised version output {:?}", output))
+}
+
+/// Check one candidate: existence, executability, version and features
+async fn probe(path: PathBuf, source: SpotifydBinarySource) -> SpotifydCandidate {
+    let mut candidate = SpotifydCandidate {
+        path: path.to_string_lossy().into_owned(),
+        source,
+        exists: path.exists(),
+        executable: false,
+        version: None,
+        features: None,
+        error: None,
+    };
+
+    if !candidate.exists {
+        candidate.error = Some("not found".to_string());
+        return candidate;
+    }
+    candidate.executable = is_executable(&path);
+    if !candidate.executable {
+        candidate.error = Some("not executable".to_string());
+        return candidate;
+    }
+
+    match run_probe(&path, "--version").await {
+        Ok(output) => {
+            let version = parse_version(&output);
+            if version.is_none() {
+                candidate.error = Some(format!("unrecognised version output {:?}", output));
+            }
+            let help = run_probe(&path, "--help").await.unwrap_or_default();
+            candidate.features = Some(features(version, &help));
+            candidate.version = version.map(|(a, b, c)| format!("{}.{}.{}", a, b, c));
+        }
+        Err(e) => candidate.error = Some(e),
+    }
+
+    debug!("spotifyd candidate {:?}", candidate);
+    candidate
+}
+
+/// A candidate we would launch: it runs and reports a version
+fn is_usable(candidate: &SpotifydCandidate) -> bool {
+    candidate.executable && candidate.version.is_some()
+}
+
+/// A configured binary is what the user asked for; when it's broken we
+/// don't quietly launch another one instead
+fn is_binding(candidate: &SpotifydCandidate) -> bool {
+    candidate.source == SpotifydBinarySource::Config
+}
+
+/// Probe every candidate and pick the first usable one, or none if the
+/// configured binary is unusable
+pub async fn detect(config: &SpotifydConfig) -> SpotifydDetection {
+    let mut candidates = Vec::new();
+    for (path, source) in candidate_paths(config) {
+        candidates.push(probe(path, source).await);
+    }
+
+    let selected = match candidates.first() {
+        Some(first) if is_binding(first) && !is_usable(first) => None,
+        _ => candidates.iter().find(|c| is_usable(c)).cloned(),
+    };
+    if selected.is_none() {
+        warn!("No usable spotifyd binary found");
+    }
+
+    SpotifydDetection {
+        selected,
+        candidates,
+    }
+}
+
+/// Binary to launch: the candidate `detect` would select. Probes stop at
+/// the first usable one; fails with every rejected candidate listed, or
+/// with just the configured binary's problem if that one is unusable.
+pub async fn resolve(config: &SpotifydConfig) -> Result<SpotifydCandidate, MprisError> {
+    resolve_from(candidate_paths(config)).await
+}
+
+async fn resolve_from(
+    candidates: Vec<(PathBuf, SpotifydBinarySource)>,
+) -> Result<SpotifydCandidate, MprisError> {
+    let mut tried = Vec::new();
+    for (path, source) in candidates {
+        let candidate = probe(path, source).await;
+        if is_usable(&candidate) {
+            return Ok(candidate);
+        }
+        tried.push(format!(
+            "{} ({:?}): {}",
+            candidate.path,
+            candidate.source,
+            candidate.error.as_deref().unwrap_or("unusable")
+        ));
+        if is_binding(&candidate) {
+            break;
+        }
+    }
+    Err(MprisError::SpotifydNotFound(match tried.is_empty() {
+        true => "no candidates".to_string(),
+        false => tried.join("; "),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{write_script, SPAWN_LOCK};
+
+    #[test]
+    fn parses_version_output() {
+        assert_eq!(parse_version("spotifyd 0.4.1"), Some((0, 4, 1)));
+        assert_eq!(parse_version("spotifyd v0.3.5-beta"), Some((0, 3, 5)));
+        assert_eq!(parse_version("spotifyd 1.0"), Some((1, 0, 0)));
+        assert_eq!(parse_version("spotifyd"), None);
+    }
+
+    #[test]
+    fn features_depend_on_version_and_build() {
+        let help = "Options:\n      --use-mpris <USE_MPRIS>  Enable MPRIS";
+        let f = features(Some((0, 4, 1)), help);
+        assert!(f.authenticate && f.mpris && f.controls);
+
+        let f = features(Some((0, 3, 5)), help);
+        assert!(!f.authenticate && f.mpris && !f.controls);
+
+        let f = features(Some((0, 4, 0)), "Options:\n  --no-daemon");
+        assert!(f.authenticate && !f.mpris && !f.controls);
+    }
+
+    #[test]
+    fn detect_probes_configured_binary() {
+        let _guard = SPAWN_LOCK.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let good = write_script(
+            dir.path(),
+            "spotifyd",
+            "case \"$1\" in\n  --version) echo 'spotifyd 0.4.1' ;;\n  --help) echo '  --use-mpris <USE_MPRIS>' ;;\nesac",
+            0o755,
+        );
+        let not_executable = write_script(dir.path(), "spotifyd-noexec", "exit 0", 0o644);
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let detection = runtime.block_on(detect(&SpotifydConfig {
+            binary_path: Some(good.clone()),
+            ..Default::default()
+        }));
+        let selected = detection.selected.unwrap();
+        assert_eq!(selected.path, good);
+        assert_eq!(selected.source, SpotifydBinarySource::Config);
+        assert_eq!(selected.version.as_deref(), Some("0.4.1"));
+        assert!(selected.features.unwrap().controls);
+
+        let detection = runtime.block_on(detect(&SpotifydConfig {
+            binary_path: Some(not_executable.clone()),
+            ..Default::default()
+        }));
+        let rejected = &detection.candidates[0];
+        assert_eq!(rejected.path, not_executable);
+        assert!(rejected.exists && !rejected.executable);
+        assert!(detection.selected.is_none());
+    }
+
+    #[test]
+    fn resolve_skips_unusable_candidates() {
+        let _guard = SPAWN_LOCK.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        // Executable, but not a working spotifyd
+        let broken = write_script(dir.path(), "spotifyd-broken", "exit 1", 0o755);
+        let good = write_script(dir.path(), "spotifyd", "echo 'spotifyd 0.4.2'", 0o755);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let resolved = runtime
+            .block_on(resolve_from(vec![
+                (missing.clone(), SpotifydBinarySource::Environment),
+                (PathBuf::from(&broken), SpotifydBinarySource::Downloaded),
+                (PathBuf::from(&good), SpotifydBinarySource::Path),
+            ]))
+            .unwrap();
+        assert_eq!(resolved.path, good);
+        assert_eq!(resolved.version.as_deref(), Some("0.4.2"));
+
+        let err = runtime
+            .block_on(resolve_from(vec![
+                (missing.clone(), SpotifydBinarySource::Environment),
+                (PathBuf::from(&broken), SpotifydBinarySource::Path),
+            ]))
+            .unwrap_err();
+        let MprisError::SpotifydNotFound(tried) = err else {
+            panic!("unexpected error {:?}", err);
+        };
+        assert_eq!(
+            tried,
+            format!(
+                "{} (Environment): not found; {} (Path): `--version` exited with exit status: 1",
+                missing.display(),
+                broken
+            )
+        );
+    }
+
+    #[test]
+    fn resolve_fails_on_a_broken_configured_binary() {
+        let _guard = SPAWN_LOCK.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let broken = write_script(dir.path(), "spotifyd-broken", "exit 1", 0o755);
+        let good = write_script(dir.path(), "spotifyd", "echo 'spotifyd 0.4.2'", 0o755);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let err = runtime
+            .block_on(resolve_from(vec![
+                (PathBuf::from(&broken), SpotifydBinarySource::Config),
+                (PathBuf::from(&good), SpotifydBinarySource::Path),
+            ]))
+            .unwrap_err();
+        let MprisError::SpotifydNotFound(tried) = err else {
+            panic!("unexpected error {:?}", err);
+        };
+        assert_eq!(
+            tried,
+            format!("{} (Config): `--version` exited with exit status: 1", broken)
+        );
+    }
+}
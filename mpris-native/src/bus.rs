@@ -0,0 +1,10 @@
+use zbus::Connection;
+
+/// Connect to the bus at `address`, or the session bus. Every component
+/// takes an optional address so tests can run on a private dbus-daemon.
+pub async fn open_bus(address: Option<&str>) -> zbus::Result<Connection> {
+    match address {
+        Some(address) => zbus::connection::Builder::address(address)?.build().await,
+        None => Connection::session().await,
+    }
+}
@@ -1,7 +1,12 @@
+use crate::bus::open_bus;
 use crate::error::MprisError;
-use crate::types::{PlaybackState, RepeatMode, TrackInfo};
+use crate::sleep_timer::{self, SleepTimer};
+use crate::types::{
+    FadeCurve, PlaybackState, RepeatMode, SleepTimerOptions, SleepTimerState, TrackInfo,
+};
 use futures::StreamExt;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::{broadcast, RwLock};
@@ -9,6 +14,52 @@ use tracing::{error, info, instrument, warn};
 use zbus::zvariant::{Array, ObjectPath, OwnedValue, Str};
 use zbus::{names::BusName, proxy, Connection};
 
+/// Bus name of our own MPRIS player (see `mpris_server`), never to be controlled
+pub const OWN_PLAYER_NAME: &str = "org.mpris.MediaPlayer2.spotify_tui";
+
+/// Time between volume steps in a fade
+const FADE_TICK: Duration = Duration::from_millis(50);
+/// A live volume further than this from what a fade last set means someone
+/// else changed it (spotifyd rounds volumes, so allow a little)
+const FADE_INTERVENTION_TOLERANCE: f64 = 0.02;
+
+/// Fraction of the way through a fade at `t` (0.0 - 1.0) of its duration
+fn fade_progress(curve: FadeCurve, t: f64) -> f64 {
+    let t = t.clamp(0.0, 1.0);
+    match curve {
+        FadeCurve::Linear => t,
+        FadeCurve::EaseIn => t * t,
+        FadeCurve::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+        FadeCurve::SCurve => t * t * (3.0 - 2.0 * t),
+    }
+}
+
+fn check_volume(volume: f64) -> Result<f64, MprisError> {
+    if (0.0..=1.0).contains(&volume) {
+        Ok(volume)
+    } else {
+        Err(MprisError::InvalidArgument(format!("volume {} is outside 0.0-1.0", volume)))
+    }
+}
+
+/// Whether `name` is spotifyd's or the official client's MPRIS player
+fn is_spotify_player(name: &str) -> bool {
+    if name == OWN_PLAYER_NAME || name.starts_with(&format!("{}.", OWN_PLAYER_NAME)) {
+        return false;
+    }
+    name.starts_with("org.mpris.MediaPlayer2.spotifyd")
+        || name.starts_with("org.mpris.MediaPlayer2.spotify")
+}
+
+/// Whether `new` is a different track from `old`
+pub(crate) fn track_changed(old: Option<&TrackInfo>, new: Option<&TrackInfo>) -> bool {
+    match (old, new) {
+        (Some(old), Some(new)) => old.uri != new.uri || old.title != new.title,
+        (None, None) => false,
+        _ => true,
+    }
+}
+
 #[proxy(
     interface = "org.mpris.MediaPlayer2.Player",
     default_path = "/org/mpris/MediaPlayer2"
@@ -48,6 +99,9 @@ trait Player {
 
     #[zbus(property)]
     fn position(&self) -> zbus::Result<i64>;
+
+    #[zbus(signal)]
+    fn seeked(&self, position: i64) -> zbus::Result<()>;
 }
 
 pub struct ControllerInner {
@@ -55,25 +109,68 @@ pub struct ControllerInner {
     player: RwLock<Option<PlayerProxy<'static>>>,
     state: Arc<RwLock<PlaybackState>>,
     state_tx: broadcast::Sender<PlaybackState>,
+    /// New positions (ms) the player jumped to
+    seek_tx: broadcast::Sender<i64>,
+    /// Signal listener tasks for the current connection
+    listeners: std::sync::Mutex<Vec<tokio::task::AbortHandle>>,
+    /// Bus to connect to instead of the session bus
+    bus_address: Option<String>,
+    sleep_timer: std::sync::Mutex<Option<SleepTimer>>,
+    /// Held across each read-modify-write of the volume
+    volume_lock: tokio::sync::Mutex<()>,
+    /// Bumped to stop a running fade
+    fade_generation: AtomicU64,
+    /// Volume to go back to on unmute, while muted
+    muted_volume: std::sync::Mutex<Option<f64>>,
 }
 
 impl ControllerInner {
     pub async fn new() -> Result<Self, MprisError> {
         let (state_tx, _) = broadcast::channel(16);
+        let (seek_tx, _) = broadcast::channel(16);
 
         Ok(Self {
             connection: RwLock::new(None),
             player: RwLock::new(None),
             state: Arc::new(RwLock::new(PlaybackState::default())),
             state_tx,
+            seek_tx,
+            listeners: std::sync::Mutex::new(Vec::new()),
+            bus_address: None,
+            sleep_timer: std::sync::Mutex::new(None),
+            volume_lock: tokio::sync::Mutex::new(()),
+            fade_generation: AtomicU64::new(0),
+            muted_volume: std::sync::Mutex::new(None),
         })
     }
 
+    /// Use the D-Bus daemon at `address` instead of the session bus
+    pub fn with_bus_address(mut self, address: impl Into<String>) -> Self {
+        self.bus_address = Some(address.into());
+        self
+    }
+
     #[instrument(skip(self))]
     pub async fn connect(&self) -> Result<(), MprisError> {
         self.connect_with_retry(3, 1000).await
     }
 
+    /// Drop the current connection and stop its signal listeners
+    pub async fn disconnect(&self) {
+        for listener in self.listeners.lock().unwrap().drain(..) {
+            listener.abort();
+        }
+        *self.player.write().await = None;
+        *self.connection.write().await = None;
+    }
+
+    /// Connect afresh, e.g. after spotifyd was restarted
+    #[instrument(skip(self))]
+    pub async fn reconnect(&self) -> Result<(), MprisError> {
+        self.disconnect().await;
+        self.connect().await
+    }
+
     /// Connect with retry logic to handle spotifyd startup delays
     async fn connect_with_retry(&self, max_retries: u32, initial_delay_ms: u64) -> Result<(), MprisError> {
         let mut last_error = None;
@@ -101,7 +198,7 @@ impl ControllerInner {
     async fn try_connect(&self) -> Result<(), MprisError> {
         info!("Connecting to MPRIS D-Bus interface");
 
-        let conn = Connection::session().await?;
+        let conn = open_bus(self.bus_address.as_deref()).await?;
         info!("D-Bus session connection established");
 
         // Try to find MPRIS service, if not found, try to activate it via TransferPlayback
@@ -145,9 +242,7 @@ impl ControllerInner {
         // Only look for spotifyd - do NOT fall back to other players
         for name in names.iter() {
             let name_str = name.as_str();
-            if name_str.starts_with("org.mpris.MediaPlayer2.spotifyd")
-                || name_str.starts_with("org.mpris.MediaPlayer2.spotify")
-            {
+            if is_spotify_player(name_str) {
                 info!("Found Spotify/spotifyd player: {}", name_str);
                 return Ok(name.to_owned().into());
             }
@@ -177,8 +272,7 @@ impl ControllerInner {
                 info!("Found spotifyd control service: {}", service);
 
                 // Call TransferPlayback method to activate MPRIS using Message API
-                use zbus::message::Builder;
-                let msg = Builder::method_call("/rs/spotifyd/Controls", "TransferPlayback")?
+                let msg = zbus::Message::method("/rs/spotifyd/Controls", "TransferPlayback")?
                     .destination(service)?
                     .interface("rs.spotifyd.Controls")?
                     .build(&())?;
@@ -226,6 +320,35 @@ impl ControllerInner {
         Ok(is_playing)
     }
 
+    #[instrument(skip(self))]
+    pub async fn play(&self) -> Result<(), MprisError> {
+        self.set_playing(true).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn pause(&self) -> Result<(), MprisError> {
+        self.set_playing(false).await
+    }
+
+    async fn set_playing(&self, playing: bool) -> Result<(), MprisError> {
+        let player = self.player.read().await;
+        let player = player.as_ref().ok_or(MprisError::NotConnected)?;
+        match playing {
+            true => player.play().await?,
+            false => player.pause().await?,
+        }
+
+        // Update local state
+        {
+            let mut state = self.state.write().await;
+            state.is_playing = playing;
+            let _ = self.state_tx.send(state.clone());
+        }
+
+        info!("Playback {}", if playing { "started" } else { "paused" });
+        Ok(())
+    }
+
     #[instrument(skip(self))]
     pub async fn next(&self) -> Result<(), MprisError> {
         let player = self.player.read().await;
@@ -254,8 +377,154 @@ impl ControllerInner {
         Ok(())
     }
 
+    /// Jump to `position_ms` in the current track
+    #[instrument(skip(self), fields(position_ms = position_ms))]
+    pub async fn set_position(&self, position_ms: i64) -> Result<(), MprisError> {
+        let player = self.player.read().await;
+        let player = player.as_ref().ok_or(MprisError::NotConnected)?;
+        let uri = self.state.read().await.track.as_ref().map(|t| t.uri.clone());
+        let track_id = uri
+            .and_then(|uri| ObjectPath::try_from(uri).ok())
+            .ok_or_else(|| MprisError::MetadataParse("no current track id".to_string()))?;
+        player.set_position(&track_id, position_ms * 1000).await?;
+        info!("Position set to {} ms", position_ms);
+        Ok(())
+    }
+
+    /// Live playback position, which PropertiesChanged doesn't report
+    pub async fn position_ms(&self) -> Result<i64, MprisError> {
+        let player = self.player.read().await;
+        let player = player.as_ref().ok_or(MprisError::NotConnected)?;
+        Ok(player.position().await? / 1000)
+    }
+
     #[instrument(skip(self), fields(volume = volume))]
     pub async fn set_volume(&self, volume: f64) -> Result<(), MprisError> {
+        self.take_over_volume();
+        let _guard = self.volume_lock.lock().await;
+        self.write_volume(volume).await?;
+        info!("Volume set to {:.2}", volume);
+        Ok(())
+    }
+
+    /// Change the volume by `delta` (clamped to 0.0 - 1.0), relative to the
+    /// player's live volume. Returns the new volume.
+    #[instrument(skip(self), fields(delta = delta))]
+    pub async fn adjust_volume(&self, delta: f64) -> Result<f64, MprisError> {
+        if !delta.is_finite() {
+            let message = format!("volume delta {} is not finite", delta);
+            return Err(MprisError::InvalidArgument(message));
+        }
+        self.take_over_volume();
+        let _guard = self.volume_lock.lock().await;
+        let volume = (self.live_volume().await? + delta).clamp(0.0, 1.0);
+        self.write_volume(volume).await?;
+        info!("Volume adjusted by {:+.2} to {:.2}", delta, volume);
+        Ok(volume)
+    }
+
+    /// Move the volume to `target` over `duration_ms`. Resolves when done:
+    /// true if the fade finished, false if it was cut short by another
+    /// volume change (ours or another client's).
+    #[instrument(skip(self), fields(target = target, duration_ms = duration_ms))]
+    pub async fn fade_to(
+        &self,
+        target: f64,
+        duration_ms: u32,
+        curve: FadeCurve,
+    ) -> Result<bool, MprisError> {
+        let target = check_volume(target)?;
+        self.take_over_volume();
+        let generation = self.fade_generation.load(Ordering::SeqCst);
+        let start = {
+            let _guard = self.volume_lock.lock().await;
+            self.live_volume().await?
+        };
+
+        let duration = Duration::from_millis(duration_ms.into());
+        let steps = (duration.as_millis() / FADE_TICK.as_millis()).max(1) as u32;
+        let mut last = start;
+        for step in 1..=steps {
+            tokio::time::sleep(duration / steps).await;
+            let _guard = self.volume_lock.lock().await;
+            if self.fade_generation.load(Ordering::SeqCst) != generation {
+                info!("Fade cancelled by a volume change");
+                return Ok(false);
+            }
+            if (self.live_volume().await? - last).abs() > FADE_INTERVENTION_TOLERANCE {
+                info!("Fade cancelled: volume changed elsewhere");
+                return Ok(false);
+            }
+            let progress = fade_progress(curve, f64::from(step) / f64::from(steps));
+            last = start + (target - start) * progress;
+            self.write_volume(last).await?;
+        }
+        info!("Faded volume from {:.2} to {:.2}", start, target);
+        Ok(true)
+    }
+
+    /// Silence playback, remembering the volume for `unmute`.
+    /// Returns false if already muted.
+    #[instrument(skip(self))]
+    pub async fn mute(&self) -> Result<bool, MprisError> {
+        self.stop_fade();
+        let _guard = self.volume_lock.lock().await;
+        if self.muted_volume.lock().unwrap().is_some() {
+            return Ok(false);
+        }
+        let volume = self.live_volume().await?;
+        self.write_volume(0.0).await?;
+        *self.muted_volume.lock().unwrap() = Some(volume);
+        info!("Muted (was {:.2})", volume);
+        Ok(true)
+    }
+
+    /// Restore the volume from before `mute`. Returns false if not muted,
+    /// including when the volume was changed since, here or by another
+    /// client.
+    #[instrument(skip(self))]
+    pub async fn unmute(&self) -> Result<bool, MprisError> {
+        self.stop_fade();
+        let _guard = self.volume_lock.lock().await;
+        let Some(volume) = self.muted_volume.lock().unwrap().take() else {
+            return Ok(false);
+        };
+        if self.live_volume().await? != 0.0 {
+            info!("Not unmuting: volume was changed elsewhere while muted");
+            return Ok(false);
+        }
+        self.write_volume(volume).await?;
+        info!("Unmuted to {:.2}", volume);
+        Ok(true)
+    }
+
+    fn stop_fade(&self) {
+        self.fade_generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// An explicit volume change: stops any fade and forgets the mute
+    fn take_over_volume(&self) {
+        self.stop_fade();
+        *self.muted_volume.lock().unwrap() = None;
+    }
+
+    /// Volume as the player has it now, bypassing the property cache
+    async fn live_volume(&self) -> Result<f64, MprisError> {
+        let player = self.player.read().await;
+        let player = player.as_ref().ok_or(MprisError::NotConnected)?;
+        let proxy = player.inner();
+        let properties = zbus::fdo::PropertiesProxy::builder(proxy.connection())
+            .destination(proxy.destination().to_owned())?
+            .path(proxy.path().to_owned())?
+            .cache_properties(zbus::CacheProperties::No)
+            .build()
+            .await?;
+        let value = properties.get(proxy.interface().to_owned(), "Volume").await?;
+        f64::try_from(value).map_err(|e| MprisError::MetadataParse(e.to_string()))
+    }
+
+    /// Set the player's volume and mirror it in the state
+    async fn write_volume(&self, volume: f64) -> Result<(), MprisError> {
         let player = self.player.read().await;
         let player = player.as_ref().ok_or(MprisError::NotConnected)?;
         player.set_volume(volume).await?;
@@ -266,8 +535,6 @@ impl ControllerInner {
             state.volume = volume;
             let _ = self.state_tx.send(state.clone());
         }
-
-        info!("Volume set to {:.2}", volume);
         Ok(())
     }
 
@@ -344,10 +611,17 @@ impl ControllerInner {
             shuffle,
             repeat,
             track,
+            sleep_timer: None,
         };
 
-        *self.state.write().await = new_state.clone();
-        let _ = self.state_tx.send(new_state);
+        let mut state = self.state.write().await;
+        // Not something the player knows about
+        let sleep_timer = state.sleep_timer.take();
+        *state = PlaybackState {
+            sleep_timer,
+            ..new_state
+        };
+        let _ = self.state_tx.send(state.clone());
 
         Ok(())
     }
@@ -399,7 +673,6 @@ impl ControllerInner {
         metadata
             .get("mpris:length")
             .and_then(|v| v.downcast_ref::<i64>().ok())
-            .map(|val| val.clone())
     }
 
     async fn start_signal_listener(&self, player: PlayerProxy<'static>) {
@@ -409,7 +682,7 @@ impl ControllerInner {
         let state_tx = self.state_tx.clone();
         let player_clone1 = player.clone();
 
-        tokio::spawn(async move {
+        let status_listener = tokio::spawn(async move {
             // Get a stream of PropertiesChanged signals
             let mut property_stream = player_clone1.receive_playback_status_changed().await;
 
@@ -444,7 +717,7 @@ impl ControllerInner {
         let state_tx = self.state_tx.clone();
         let player_clone = player.clone();
 
-        tokio::spawn(async move {
+        let metadata_listener = tokio::spawn(async move {
             let mut metadata_stream = player_clone.receive_metadata_changed().await;
 
             info!("Metadata change listener active");
@@ -483,6 +756,29 @@ impl ControllerInner {
 
             warn!("Metadata change listener stopped");
         });
+
+        let seek_tx = self.seek_tx.clone();
+        let seek_listener = tokio::spawn(async move {
+            let mut seeks = match player.receive_seeked().await {
+                Ok(seeks) => seeks,
+                Err(e) => {
+                    warn!("Could not listen for seeks: {}", e);
+                    return;
+                }
+            };
+            while let Some(seek) = seeks.next().await {
+                if let Ok(args) = seek.args() {
+                    let _ = seek_tx.send(args.position / 1000);
+                }
+            }
+            warn!("Seek listener stopped");
+        });
+
+        self.listeners.lock().unwrap().extend([
+            status_listener.abort_handle(),
+            metadata_listener.abort_handle(),
+            seek_listener.abort_handle(),
+        ]);
     }
 
     fn parse_metadata_static(metadata: &HashMap<String, OwnedValue>) -> Option<TrackInfo> {
@@ -532,7 +828,6 @@ impl ControllerInner {
         metadata
             .get("mpris:length")
             .and_then(|v| v.downcast_ref::<i64>().ok())
-            .map(|val| val.clone())
     }
 
     pub fn get_state(&self) -> PlaybackState {
@@ -540,7 +835,454 @@ impl ControllerInner {
         self.state.blocking_read().clone()
     }
 
+    /// Current state, for callers already on the runtime
+    pub async fn state(&self) -> PlaybackState {
+        self.state.read().await.clone()
+    }
+
     pub fn subscribe_state_changes(&self) -> broadcast::Receiver<PlaybackState> {
         self.state_tx.subscribe()
     }
+
+    /// Positions (ms) the player jumps to, from its Seeked signal
+    pub fn subscribe_seeks(&self) -> broadcast::Receiver<i64> {
+        self.seek_tx.subscribe()
+    }
+
+    /// Resolves once the connected player has left the bus, or right away
+    /// if there is none. State changes stop then until `reconnect`.
+    pub async fn player_gone(&self) {
+        let Some(player) = self.player.read().await.clone() else {
+            return;
+        };
+        let proxy = player.inner();
+        // Subscribe before checking so a player leaving in between isn't missed
+        let Ok(mut owners) = proxy.receive_owner_changed().await else {
+            return;
+        };
+        let owned = match zbus::fdo::DBusProxy::new(proxy.connection()).await {
+            Ok(dbus) => dbus.name_has_owner(proxy.destination().clone()).await,
+            Err(e) => Err(e.into()),
+        };
+        if !owned.unwrap_or(false) {
+            return;
+        }
+        while let Some(owner) = owners.next().await {
+            if owner.is_none() {
+                return;
+            }
+        }
+    }
+
+    /// Pause later, as `options` says. Replaces any running timer.
+    pub async fn start_sleep_timer(
+        self: &Arc<Self>,
+        options: SleepTimerOptions,
+    ) -> Result<SleepTimerState, MprisError> {
+        let plan = sleep_timer::plan(&options)?;
+        self.cancel_sleep_timer().await;
+        let (timer, state) = SleepTimer::start(self.clone(), plan).await;
+        // A concurrent start may have stored its timer meanwhile; stop it, don't leak it
+        let replaced = self.sleep_timer.lock().unwrap().replace(timer);
+        if let Some(replaced) = replaced {
+            if let Some(volume) = replaced.cancel() {
+                if let Err(e) = self.set_volume(volume).await {
+                    warn!("Could not restore volume after replaced fade: {}", e);
+                }
+            }
+            // It may have shown its own state after ours
+            self.publish_sleep_timer(Some(state.clone())).await;
+        }
+        info!("Sleep timer started: {:?}", options.mode);
+        Ok(state)
+    }
+
+    /// Stop the sleep timer, putting the volume back if it was fading.
+    /// Returns false if none was running.
+    pub async fn cancel_sleep_timer(&self) -> bool {
+        let timer = self.sleep_timer.lock().unwrap().take();
+        if let Some(timer) = timer {
+            if let Some(volume) = timer.cancel() {
+                if let Err(e) = self.set_volume(volume).await {
+                    warn!("Could not restore volume after cancelled fade: {}", e);
+                }
+            }
+        }
+        let was_set = self.state.read().await.sleep_timer.is_some();
+        self.publish_sleep_timer(None).await;
+        was_set
+    }
+
+    /// Update the sleep timer shown in the state
+    pub(crate) async fn publish_sleep_timer(&self, timer: Option<SleepTimerState>) {
+        let mut state = self.state.write().await;
+        if state.sleep_timer != timer {
+            state.sleep_timer = timer;
+            let _ = self.state_tx.send(state.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_player::{
+        connected_controller, DbusDaemon, MockOptions, MockPlayer, MockState, MockTrack,
+    };
+    use crate::types::SleepTimerMode;
+    use std::time::Instant;
+
+    /// Private bus plus a runtime to drive it
+    fn bus() -> (DbusDaemon, tokio::runtime::Runtime) {
+        (DbusDaemon::start(), tokio::runtime::Runtime::new().unwrap())
+    }
+
+    /// Next state broadcast matching `f`, within a second
+    async fn next_state(
+        rx: &mut broadcast::Receiver<PlaybackState>,
+        f: impl Fn(&PlaybackState) -> bool,
+    ) -> PlaybackState {
+        tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                let state = rx.recv().await.unwrap();
+                if f(&state) {
+                    return state;
+                }
+            }
+        })
+        .await
+        .expect("no matching state change")
+    }
+
+    #[test]
+    fn connect_reads_initial_state() {
+        let (bus, runtime) = bus();
+        let (controller, _player) = runtime.block_on(async {
+            let mut track = MockTrack::new("42", "Song", "Artist");
+            track.art_url = Some("https://i.scdn.co/image/abc".to_string());
+            let player = MockPlayer::start(
+                &bus,
+                MockOptions {
+                    state: MockState {
+                        playback_status: "Playing".to_string(),
+                        track: Some(track),
+                        position_us: 5_000_000,
+                        volume: 0.8,
+                        shuffle: true,
+                        loop_status: "Track".to_string(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            )
+            .await;
+            (connected_controller(&bus).await, player)
+        });
+
+        let state = controller.get_state();
+        assert!(state.is_playing && state.shuffle);
+        assert_eq!(state.position_ms, 5_000);
+        assert_eq!(state.duration_ms, 180_000);
+        assert_eq!(state.volume, 0.8);
+        assert!(matches!(state.repeat, RepeatMode::Track));
+        let track = state.track.unwrap();
+        assert_eq!((track.title.as_str(), track.artist.as_str()), ("Song", "Artist"));
+        assert_eq!(track.uri, "/org/spotify/track/42");
+        assert_eq!(track.art_url.as_deref(), Some("https://i.scdn.co/image/abc"));
+    }
+
+    #[test]
+    fn commands_reach_the_player() {
+        let (bus, runtime) = bus();
+        runtime.block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let controller = connected_controller(&bus).await;
+
+            assert!(controller.play_pause().await.unwrap());
+            controller.next().await.unwrap();
+            controller.previous().await.unwrap();
+            controller.seek(10_000).await.unwrap();
+            controller.set_volume(0.6).await.unwrap();
+            controller.set_shuffle(true).await.unwrap();
+            controller.set_repeat(RepeatMode::Playlist).await.unwrap();
+
+            assert_eq!(
+                player.calls(),
+                [
+                    "PlayPause",
+                    "Next",
+                    "Previous",
+                    "Seek(10000000)",
+                    "Volume=0.6",
+                    "Shuffle=true",
+                    "LoopStatus=Playlist"
+                ]
+            );
+            let state = player.state();
+            assert_eq!(state.playback_status, "Playing");
+            assert_eq!(state.position_us, 10_000_000);
+            assert!(state.shuffle);
+        });
+    }
+
+    /// Volumes the player was set to, in order
+    fn volumes(player: &MockPlayer) -> Vec<f64> {
+        player
+            .calls()
+            .iter()
+            .filter_map(|call| call.strip_prefix("Volume=")?.parse().ok())
+            .collect()
+    }
+
+    #[test]
+    fn concurrent_adjustments_do_not_drift() {
+        let (bus, runtime) = bus();
+        runtime.block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let controller = connected_controller(&bus).await;
+
+            let ups = (0..4).map(|_| controller.adjust_volume(0.1));
+            for result in futures::future::join_all(ups).await {
+                result.unwrap();
+            }
+            assert!((player.state().volume - 0.9).abs() < 1e-9);
+
+            // Relative to the live volume, not our cached copy
+            player.set_volume(0.2);
+            assert!((controller.adjust_volume(-0.05).await.unwrap() - 0.15).abs() < 1e-9);
+            assert_eq!(controller.adjust_volume(-1.0).await.unwrap(), 0.0);
+
+            for delta in [f64::NAN, f64::INFINITY] {
+                let err = controller.adjust_volume(delta).await.unwrap_err();
+                assert!(matches!(err, MprisError::InvalidArgument(_)), "{}", err);
+            }
+            assert_eq!(player.state().volume, 0.0);
+        });
+    }
+
+    #[test]
+    fn fades_follow_the_curve_and_stop_on_intervention() {
+        let (bus, runtime) = bus();
+        runtime.block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let controller = connected_controller(&bus).await;
+
+            assert!(controller.fade_to(1.0, 200, FadeCurve::Linear).await.unwrap());
+            let steps = volumes(&player);
+            assert_eq!(steps.len(), 4);
+            assert!(steps.windows(2).all(|w| w[0] < w[1]), "{:?}", steps);
+            assert_eq!(steps.last(), Some(&1.0));
+            assert!(controller.fade_to(1.5, 200, FadeCurve::Linear).await.is_err());
+
+            // Someone else turns it down mid-fade
+            let fade = controller.fade_to(0.0, 1000, FadeCurve::EaseOut);
+            let meddle = async {
+                tokio::time::sleep(Duration::from_millis(180)).await;
+                player.set_volume(0.9);
+            };
+            let (faded, ()) = tokio::join!(fade, meddle);
+            assert!(!faded.unwrap());
+            assert_eq!(player.state().volume, 0.9);
+
+            // Or we do
+            let fade = controller.fade_to(0.0, 1000, FadeCurve::Linear);
+            let set = async {
+                tokio::time::sleep(Duration::from_millis(180)).await;
+                controller.set_volume(0.3).await.unwrap();
+            };
+            let (faded, ()) = tokio::join!(fade, set);
+            assert!(!faded.unwrap());
+            assert_eq!(player.state().volume, 0.3);
+        });
+    }
+
+    #[test]
+    fn unmute_restores_the_volume_before_mute() {
+        let (bus, runtime) = bus();
+        runtime.block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let controller = connected_controller(&bus).await;
+
+            assert!(controller.mute().await.unwrap());
+            assert!(!controller.mute().await.unwrap());
+            assert_eq!(player.state().volume, 0.0);
+            assert!(controller.unmute().await.unwrap());
+            assert!(!controller.unmute().await.unwrap());
+            assert_eq!(player.state().volume, 0.5);
+
+            // Choosing a volume while muted replaces the saved one
+            controller.mute().await.unwrap();
+            controller.set_volume(0.7).await.unwrap();
+            assert!(!controller.unmute().await.unwrap());
+            assert_eq!(player.state().volume, 0.7);
+
+            // So does another client turning it up
+            controller.mute().await.unwrap();
+            player.set_volume(0.4);
+            assert!(!controller.unmute().await.unwrap());
+            assert_eq!(player.state().volume, 0.4);
+        });
+    }
+
+    #[test]
+    fn fade_curves_run_from_zero_to_one() {
+        for curve in [FadeCurve::Linear, FadeCurve::EaseIn, FadeCurve::EaseOut, FadeCurve::SCurve] {
+            assert_eq!(fade_progress(curve, 0.0), 0.0);
+            assert_eq!(fade_progress(curve, 1.0), 1.0);
+        }
+        assert!(fade_progress(FadeCurve::EaseIn, 0.5) < 0.5);
+        assert!(fade_progress(FadeCurve::EaseOut, 0.5) > 0.5);
+        assert_eq!(fade_progress(FadeCurve::SCurve, 0.5), 0.5);
+    }
+
+    #[test]
+    fn own_player_is_not_mistaken_for_spotifyd() {
+        assert!(is_spotify_player("org.mpris.MediaPlayer2.spotifyd.instance42"));
+        assert!(is_spotify_player("org.mpris.MediaPlayer2.spotify"));
+        assert!(!is_spotify_player(OWN_PLAYER_NAME));
+        assert!(!is_spotify_player("org.mpris.MediaPlayer2.spotify_tui.instance7"));
+        assert!(!is_spotify_player("org.mpris.MediaPlayer2.vlc"));
+    }
+
+    #[test]
+    fn connect_activates_mpris_via_transfer_playback() {
+        let (bus, runtime) = bus();
+        runtime.block_on(async {
+            let player = MockPlayer::start(
+                &bus,
+                MockOptions {
+                    mpris_at_start: false,
+                    ..Default::default()
+                },
+            )
+            .await;
+            connected_controller(&bus).await;
+            assert_eq!(player.calls(), ["TransferPlayback"]);
+        });
+    }
+
+    #[test]
+    fn signals_update_state() {
+        let (bus, runtime) = bus();
+        runtime.block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let controller = connected_controller(&bus).await;
+            let mut rx = controller.subscribe_state_changes();
+
+            player.set_playback_status("Playing").await;
+            next_state(&mut rx, |s| s.is_playing).await;
+
+            player
+                .set_track(Some(MockTrack::new("2", "Second Song", "Other Artist")))
+                .await;
+            let state = next_state(&mut rx, |s| {
+                s.track.as_ref().is_some_and(|t| t.title == "Second Song")
+            })
+            .await;
+            assert_eq!(state.track.unwrap().artist, "Other Artist");
+            assert!(state.is_playing);
+        });
+    }
+
+    #[test]
+    fn player_faults_surface_as_errors() {
+        let (bus, runtime) = bus();
+        runtime.block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let controller = connected_controller(&bus).await;
+
+            player.fail("Next", "no next track");
+            player.fail("Volume=0.3", "volume is fixed");
+            let err = controller.next().await.unwrap_err();
+            assert!(err.to_string().contains("no next track"), "{}", err);
+            assert!(controller.set_volume(0.3).await.is_err());
+
+            player.clear_faults();
+            player.set_delay(Duration::from_millis(200));
+            let started = Instant::now();
+            controller.next().await.unwrap();
+            assert!(started.elapsed() >= Duration::from_millis(200));
+        });
+    }
+
+    #[test]
+    fn reconnect_follows_a_restarted_player() {
+        let (bus, runtime) = bus();
+        let (controller, second) = runtime.block_on(async {
+            let first = MockPlayer::start(&bus, MockOptions::default()).await;
+            let controller = connected_controller(&bus).await;
+            first.vanish().await;
+            assert!(controller.next().await.is_err());
+
+            let second = MockPlayer::start(
+                &bus,
+                MockOptions {
+                    instance: 2,
+                    state: MockState {
+                        track: Some(MockTrack::new("3", "After Restart", "Artist")),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            )
+            .await;
+            controller.reconnect().await.unwrap();
+            controller.next().await.unwrap();
+            (controller, second)
+        });
+
+        assert_eq!(second.calls(), ["Next"]);
+        assert_eq!(controller.get_state().track.unwrap().title, "After Restart");
+        assert_eq!(controller.listeners.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn player_gone_resolves_when_the_player_leaves() {
+        let (bus, runtime) = bus();
+        runtime.block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let controller = connected_controller(&bus).await;
+
+            let gone = controller.player_gone();
+            tokio::pin!(gone);
+            assert!(tokio::time::timeout(Duration::from_millis(200), &mut gone).await.is_err());
+
+            player.vanish().await;
+            tokio::time::timeout(Duration::from_secs(5), gone).await.unwrap();
+            // Already gone by the time we ask
+            tokio::time::timeout(Duration::from_secs(5), controller.player_gone()).await.unwrap();
+        });
+    }
+
+    #[test]
+    fn concurrent_sleep_timer_starts_leave_one_timer() {
+        let (bus, runtime) = bus();
+        runtime.block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let controller = connected_controller(&bus).await;
+            let options = || SleepTimerOptions {
+                mode: SleepTimerMode::Duration,
+                duration_ms: Some(300),
+                tracks: None,
+                fade_out_ms: None,
+            };
+
+            // Both find the slot empty, then wait here for the state
+            let state = controller.state.write().await;
+            let starts = [options(), options()].map(|options| {
+                let controller = controller.clone();
+                tokio::spawn(async move { controller.start_sleep_timer(options).await })
+            });
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(state);
+            for start in starts {
+                start.await.unwrap().unwrap();
+            }
+
+            assert!(controller.cancel_sleep_timer().await);
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            assert!(player.calls().is_empty(), "{:?}", player.calls());
+            assert_eq!(controller.state().await.sleep_timer, None);
+        });
+    }
 }
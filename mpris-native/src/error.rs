@@ -29,6 +29,39 @@ pub enum MprisError {
     #[error("Process spawn failed: {0}")]
     ProcessSpawn(String),
 
+    #[error("Failed to execute spotifyd binary {path}: {source}")]
+    ExecFailed {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("spotifyd exited immediately after starting")]
+    ExitedImmediately,
+
+    #[error("spotifyd authentication failed: {0}")]
+    AuthFailed(String),
+
+    #[error("No usable spotifyd binary found: {0}")]
+    SpotifydNotFound(String),
+
+    #[error("spotifyd authentication already in progress")]
+    AuthInProgress,
+
+    #[error("spotifyd install failed: {0}")]
+    InstallFailed(String),
+
+    #[error("Checksum mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+
+    #[error("IPC server error: {0}")]
+    IpcFailed(String),
+
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+
+    #[error("Invalid spotifyd configuration: {0}")]
+    InvalidConfig(String),
+
     #[error("D-Bus registration timeout")]
     RegistrationTimeout,
 }
@@ -0,0 +1,655 @@
+//! Listening history, recorded locally so it doesn't depend on the Web API's
+//! "recently played" endpoint.
+//!
+//! The recorder follows the controller's state (fed by the metadata and
+//! playback status listeners) and the player's Seeked signal. When a track
+//! stops being current, it is written as a play if it was listened to long
+//! enough, with the time actually played, whether it was skipped and how
+//! often it was seeked. Plays are appended as JSON lines to
+//! ~/.spotify-tui/history.jsonl, which the queries read back.
+
+use crate::controller::{track_changed, ControllerInner};
+use crate::error::MprisError;
+use crate::types::{
+    HistoryEntry, HistoryOptions, HistoryRange, HistoryTopEntry, PlaybackState, TrackInfo,
+};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use tokio::task::AbortHandle;
+use tracing::{debug, info, warn};
+
+const HISTORY_FILE: &str = "history.jsonl";
+const DEFAULT_MIN_PLAYED: Duration = Duration::from_secs(30);
+/// Leaving a track more than this before its end is a skip
+const SKIP_MARGIN_MS: i64 = 5_000;
+/// A Seeked this close to where playback should be isn't a seek (players
+/// also send it when a track starts)
+const SEEK_TOLERANCE_MS: i64 = 1_000;
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// ~/.spotify-tui/history.jsonl
+fn default_path() -> Option<PathBuf> {
+    crate::state_file::spotify_tui_dir().map(|dir| dir.join(HISTORY_FILE))
+}
+
+// ─────────────────────────────────────────────────────────────
+// Store
+// ─────────────────────────────────────────────────────────────
+
+/// Append-only history file, one JSON entry per line
+struct HistoryStore {
+    path: PathBuf,
+}
+
+impl HistoryStore {
+    fn append(&self, entry: &HistoryEntry) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        // One write per entry, so concurrent writers don't interleave lines
+        std::fs::File::options()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())
+    }
+
+    /// All entries, oldest first. Unreadable lines (e.g. a write cut short)
+    /// are skipped.
+    fn read(&self) -> std::io::Result<Vec<HistoryEntry>> {
+        let contents = match std::fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        Ok(contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    warn!("Skipping unreadable history line: {}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// Write `entry` if there is one, logging the outcome
+    fn record(&self, entry: Option<HistoryEntry>) {
+        let Some(entry) = entry else { return };
+        match self.append(&entry) {
+            Ok(()) => debug!("Recorded play of {:?}", entry.title),
+            Err(e) => warn!("Could not record play of {:?}: {}", entry.title, e),
+        }
+    }
+
+    /// Entries started within `range` of `now_ms`, oldest first
+    fn read_range(&self, range: HistoryRange, now_ms: i64) -> std::io::Result<Vec<HistoryEntry>> {
+        let since = match range {
+            HistoryRange::Day => Some(now_ms - DAY_MS),
+            HistoryRange::Week => Some(now_ms - 7 * DAY_MS),
+            HistoryRange::Month => Some(now_ms - 30 * DAY_MS),
+            HistoryRange::Year => Some(now_ms - 365 * DAY_MS),
+            HistoryRange::All => None,
+        };
+        let mut entries = self.read()?;
+        if let Some(since) = since {
+            entries.retain(|entry| entry.started_at_ms >= since);
+        }
+        Ok(entries)
+    }
+}
+
+/// Count plays per key, most played first (ties: longest listened, then name)
+fn rank(
+    entries: &[HistoryEntry],
+    limit: usize,
+    key: impl Fn(&HistoryEntry) -> Option<(String, Option<String>)>,
+) -> Vec<HistoryTopEntry> {
+    let mut counts: HashMap<(String, Option<String>), HistoryTopEntry> = HashMap::new();
+    for entry in entries {
+        let Some((name, artist)) = key(entry) else { continue };
+        let top = counts
+            .entry((name.clone(), artist.clone()))
+            .or_insert(HistoryTopEntry {
+                name,
+                artist,
+                plays: 0,
+                played_ms: 0,
+            });
+        top.plays += 1;
+        top.played_ms += entry.played_ms;
+    }
+    let mut top: Vec<_> = counts.into_values().collect();
+    top.sort_by(|a, b| {
+        b.plays
+            .cmp(&a.plays)
+            .then(b.played_ms.cmp(&a.played_ms))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    top.truncate(limit);
+    top
+}
+
+// ─────────────────────────────────────────────────────────────
+// Recording
+// ─────────────────────────────────────────────────────────────
+
+/// The current track, while it's being listened to
+struct Play {
+    track: TrackInfo,
+    duration_ms: i64,
+    started: Instant,
+    started_at_ms: i64,
+    /// Time played before `resumed`
+    played: Duration,
+    /// Position at `resumed`, or where playback is paused
+    position_ms: i64,
+    /// When playback last started, while playing
+    resumed: Option<Instant>,
+    seek_count: u32,
+}
+
+impl Play {
+    fn new(track: TrackInfo, position_ms: i64, now: Instant) -> Self {
+        Self {
+            track,
+            duration_ms: 0,
+            started: now,
+            started_at_ms: now_ms(),
+            played: Duration::ZERO,
+            position_ms,
+            resumed: None,
+            seek_count: 0,
+        }
+    }
+
+    fn since_resumed(&self, now: Instant) -> Duration {
+        self.resumed.map_or(Duration::ZERO, |at| now.saturating_duration_since(at))
+    }
+
+    fn played(&self, now: Instant) -> Duration {
+        self.played + self.since_resumed(now)
+    }
+
+    fn position_ms(&self, now: Instant) -> i64 {
+        self.position_ms + self.since_resumed(now).as_millis() as i64
+    }
+
+    fn set_playing(&mut self, playing: bool, now: Instant) {
+        match (playing, self.resumed) {
+            (true, None) => self.resumed = Some(now),
+            (false, Some(_)) => {
+                self.played = self.played(now);
+                self.position_ms = self.position_ms(now);
+                self.resumed = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn seek(&mut self, position_ms: i64, now: Instant) {
+        if (position_ms - self.position_ms(now)).abs() <= SEEK_TOLERANCE_MS {
+            return;
+        }
+        let playing = self.resumed.is_some();
+        self.set_playing(false, now);
+        self.position_ms = position_ms;
+        self.seek_count += 1;
+        self.set_playing(playing, now);
+    }
+
+    /// The history entry for this play, if it was listened to long enough
+    fn finish(self, now: Instant, min_played: Duration) -> Option<HistoryEntry> {
+        let played = self.played(now);
+        let needed = match u64::try_from(self.duration_ms / 2) {
+            Ok(half) if half > 0 => min_played.min(Duration::from_millis(half)),
+            _ => min_played,
+        };
+        if played < needed {
+            debug!("Not a play: {:?} after {:?}", self.track.title, played);
+            return None;
+        }
+        let skipped =
+            self.duration_ms > 0 && self.position_ms(now) < self.duration_ms - SKIP_MARGIN_MS;
+        let elapsed = now.saturating_duration_since(self.started);
+        Some(HistoryEntry {
+            title: self.track.title,
+            artist: self.track.artist,
+            album: self.track.album,
+            uri: self.track.uri,
+            started_at_ms: self.started_at_ms,
+            ended_at_ms: self.started_at_ms + elapsed.as_millis() as i64,
+            duration_ms: self.duration_ms,
+            played_ms: played.as_millis() as i64,
+            skipped,
+            seek_count: self.seek_count,
+        })
+    }
+}
+
+/// Follows the current play; finished plays are handed back to be stored
+/// outside the lock
+struct Recorder {
+    min_played: Duration,
+    current: Option<Play>,
+}
+
+impl Recorder {
+    /// Follow `state`. A track that is already playing counts from its
+    /// current position.
+    fn begin(&mut self, state: &PlaybackState, now: Instant) {
+        self.current = state
+            .track
+            .clone()
+            .map(|track| Play::new(track, state.position_ms, now));
+        self.update(state, now);
+    }
+
+    /// Follow a state change; returns the play it finished, if any
+    fn on_state(&mut self, state: &PlaybackState, now: Instant) -> Option<HistoryEntry> {
+        let current = self.current.as_ref().map(|play| &play.track);
+        let mut finished = None;
+        if track_changed(current, state.track.as_ref()) {
+            finished = self.finish(now);
+            self.current = state.track.clone().map(|track| Play::new(track, 0, now));
+        }
+        self.update(state, now);
+        finished
+    }
+
+    fn update(&mut self, state: &PlaybackState, now: Instant) {
+        if let Some(ref mut play) = self.current {
+            if state.duration_ms > 0 {
+                play.duration_ms = state.duration_ms;
+            }
+            play.set_playing(state.is_playing, now);
+        }
+    }
+
+    fn on_seek(&mut self, position_ms: i64, now: Instant) {
+        if let Some(ref mut play) = self.current {
+            play.seek(position_ms, now);
+        }
+    }
+
+    /// Stop following the current track; returns it if it counts as a play
+    fn finish(&mut self, now: Instant) -> Option<HistoryEntry> {
+        self.current
+            .take()
+            .and_then(|play| play.finish(now, self.min_played))
+    }
+}
+
+async fn run(
+    store: Arc<HistoryStore>,
+    recorder: Arc<Mutex<Recorder>>,
+    controller: Arc<ControllerInner>,
+    mut states: broadcast::Receiver<PlaybackState>,
+    mut seeks: broadcast::Receiver<i64>,
+) {
+    loop {
+        tokio::select! {
+            received = states.recv() => {
+                let state = match received {
+                    Ok(state) => state,
+                    Err(broadcast::error::RecvError::Lagged(_)) => controller.state().await,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let finished = recorder.lock().unwrap().on_state(&state, Instant::now());
+                store.record(finished);
+            }
+            received = seeks.recv() => match received {
+                Ok(position_ms) => recorder.lock().unwrap().on_seek(position_ms, Instant::now()),
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        }
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// History recorder
+// ─────────────────────────────────────────────────────────────
+
+pub struct HistoryInner {
+    controller: Arc<ControllerInner>,
+    /// None without a path: no option given and no HOME to default to
+    store: Option<Arc<HistoryStore>>,
+    recorder: Arc<Mutex<Recorder>>,
+    task: Mutex<Option<AbortHandle>>,
+}
+
+impl HistoryInner {
+    pub fn new(controller: Arc<ControllerInner>, options: HistoryOptions) -> Self {
+        let path = options.path.map(PathBuf::from).or_else(default_path);
+        let min_played = options
+            .min_played_ms
+            .map(|ms| Duration::from_millis(ms.into()))
+            .unwrap_or(DEFAULT_MIN_PLAYED);
+        Self {
+            controller,
+            store: path.map(|path| Arc::new(HistoryStore { path })),
+            recorder: Arc::new(Mutex::new(Recorder {
+                min_played,
+                current: None,
+            })),
+            task: Mutex::new(None),
+        }
+    }
+
+    fn store(&self) -> Result<&Arc<HistoryStore>, MprisError> {
+        self.store.as_ref().ok_or_else(|| {
+            MprisError::InvalidConfig("HOME not set; pass a history path".to_string())
+        })
+    }
+
+    /// Start recording, beginning with the track playing now. Fails
+    /// without a history path.
+    pub async fn start(&self) -> Result<(), MprisError> {
+        let store = self.store()?.clone();
+        if self.is_running() {
+            return Ok(());
+        }
+
+        let states = self.controller.subscribe_state_changes();
+        let seeks = self.controller.subscribe_seeks();
+        let state = self.controller.state().await;
+        self.recorder.lock().unwrap().begin(&state, Instant::now());
+        let task = tokio::spawn(run(
+            store,
+            self.recorder.clone(),
+            self.controller.clone(),
+            states,
+            seeks,
+        ));
+
+        let mut slot = self.task.lock().unwrap();
+        if slot.is_some() {
+            // Lost a race with a concurrent start()
+            task.abort();
+        } else {
+            *slot = Some(task.abort_handle());
+            info!("Recording listening history");
+        }
+        Ok(())
+    }
+
+    /// Stop recording, recording the current track if it counts as a play.
+    /// Returns false if it wasn't running.
+    pub fn stop(&self) -> bool {
+        let Some(task) = self.task.lock().unwrap().take() else {
+            return false;
+        };
+        task.abort();
+        let finished = self.recorder.lock().unwrap().finish(Instant::now());
+        if let Some(store) = self.store.as_ref() {
+            store.record(finished);
+        }
+        info!("Stopped recording listening history");
+        true
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.task.lock().unwrap().is_some()
+    }
+
+    fn read_range(&self, range: HistoryRange) -> Result<Vec<HistoryEntry>, MprisError> {
+        Ok(self.store()?.read_range(range, now_ms())?)
+    }
+
+    /// Plays within `range`, newest first
+    pub fn history(
+        &self,
+        range: HistoryRange,
+        limit: usize,
+    ) -> Result<Vec<HistoryEntry>, MprisError> {
+        let mut entries = self.read_range(range)?;
+        entries.reverse();
+        entries.truncate(limit);
+        Ok(entries)
+    }
+
+    /// Most played tracks within `range`
+    pub fn top_tracks(
+        &self,
+        range: HistoryRange,
+        limit: usize,
+    ) -> Result<Vec<HistoryTopEntry>, MprisError> {
+        let entries = self.read_range(range)?;
+        Ok(rank(&entries, limit, |entry| {
+            Some((entry.title.clone(), Some(entry.artist.clone())))
+        }))
+    }
+
+    /// Most played artists within `range`
+    pub fn top_artists(
+        &self,
+        range: HistoryRange,
+        limit: usize,
+    ) -> Result<Vec<HistoryTopEntry>, MprisError> {
+        let entries = self.read_range(range)?;
+        Ok(rank(&entries, limit, |entry| {
+            (!entry.artist.is_empty()).then(|| (entry.artist.clone(), None))
+        }))
+    }
+}
+
+impl Drop for HistoryInner {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_player::{connected_controller, DbusDaemon, MockOptions, MockPlayer, MockTrack};
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn track(id: u32, title: &str, artist: &str) -> TrackInfo {
+        TrackInfo {
+            title: title.to_string(),
+            artist: artist.to_string(),
+            album: "Album".to_string(),
+            art_url: None,
+            uri: format!("/org/mpris/MediaPlayer2/Track/{}", id),
+        }
+    }
+
+    fn playing(track: &TrackInfo, is_playing: bool) -> PlaybackState {
+        PlaybackState {
+            is_playing,
+            duration_ms: 180_000,
+            track: Some(track.clone()),
+            ..Default::default()
+        }
+    }
+
+    fn recorder() -> Recorder {
+        Recorder {
+            min_played: DEFAULT_MIN_PLAYED,
+            current: None,
+        }
+    }
+
+    fn entry(title: &str, artist: &str, started_at_ms: i64, played_ms: i64) -> HistoryEntry {
+        HistoryEntry {
+            title: title.to_string(),
+            artist: artist.to_string(),
+            album: String::new(),
+            uri: String::new(),
+            started_at_ms,
+            ended_at_ms: started_at_ms + played_ms,
+            duration_ms: 180_000,
+            played_ms,
+            skipped: false,
+            seek_count: 0,
+        }
+    }
+
+    #[test]
+    fn records_plays_with_skips_and_seeks() {
+        let mut rec = recorder();
+        let mut entries = Vec::new();
+        let first = track(1, "One", "A");
+        let second = track(2, "Two", "B");
+        let third = track(3, "Three", "C");
+        let t0 = Instant::now();
+
+        // Played to the end, with a pause in the middle
+        rec.begin(&playing(&first, true), t0);
+        entries.extend(rec.on_state(&playing(&first, false), t0 + MINUTE));
+        entries.extend(rec.on_state(&playing(&first, true), t0 + 10 * MINUTE));
+        // Seeked on track start, where playback already is: not a seek
+        entries.extend(rec.on_state(&playing(&second, true), t0 + 12 * MINUTE));
+        rec.on_seek(0, t0 + 12 * MINUTE);
+        // Seeked ahead twice, then left a minute early
+        rec.on_seek(60_000, t0 + 12 * MINUTE + Duration::from_secs(10));
+        rec.on_seek(30_000, t0 + 12 * MINUTE + Duration::from_secs(20));
+        // Only 10 seconds of this one: not a play
+        let t1 = t0 + 13 * MINUTE;
+        entries.extend(rec.on_state(&playing(&third, true), t1 + Duration::from_secs(30)));
+        entries.extend(rec.on_state(&PlaybackState::default(), t1 + Duration::from_secs(40)));
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].title, "One");
+        assert_eq!(entries[0].played_ms, 180_000);
+        assert_eq!(entries[0].ended_at_ms - entries[0].started_at_ms, 720_000);
+        assert!(!entries[0].skipped);
+        assert_eq!(entries[0].seek_count, 0);
+        assert_eq!(entries[1].title, "Two");
+        assert_eq!(entries[1].played_ms, 90_000);
+        assert!(entries[1].skipped);
+        assert_eq!(entries[1].seek_count, 2);
+    }
+
+    #[test]
+    fn short_tracks_need_half_their_length() {
+        let mut rec = recorder();
+        let jingle = track(1, "Jingle", "A");
+        let t0 = Instant::now();
+
+        let short = |is_playing| PlaybackState {
+            duration_ms: 20_000,
+            ..playing(&jingle, is_playing)
+        };
+        rec.begin(&short(true), t0);
+        let entry = rec.on_state(&PlaybackState::default(), t0 + Duration::from_secs(16));
+        assert!(!entry.unwrap().skipped);
+    }
+
+    #[test]
+    fn queries_filter_rank_and_tolerate_bad_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let store = HistoryStore { path: path.clone() };
+        let now = now_ms();
+        store.append(&entry("Old", "A", now - 40 * DAY_MS, 100_000)).unwrap();
+        store.append(&entry("One", "A", now - 2 * DAY_MS, 100_000)).unwrap();
+        store.append(&entry("Two", "B", now - DAY_MS / 2, 150_000)).unwrap();
+        // A write cut short
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"title\":\"Tru\n")
+            .unwrap();
+        store.append(&entry("One", "A", now - 1000, 120_000)).unwrap();
+
+        let controller = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(ControllerInner::new())
+            .unwrap();
+        let options = HistoryOptions {
+            path: Some(path.display().to_string()),
+            ..Default::default()
+        };
+        let history = HistoryInner::new(Arc::new(controller), options);
+
+        let recent = history.history(HistoryRange::Week, 2).unwrap();
+        let titles: Vec<_> = recent.iter().map(|e| e.title.as_str()).collect();
+        assert_eq!(titles, ["One", "Two"]);
+        assert_eq!(history.history(HistoryRange::Day, 10).unwrap().len(), 2);
+        assert_eq!(history.history(HistoryRange::All, 10).unwrap().len(), 4);
+
+        let tracks = history.top_tracks(HistoryRange::Month, 10).unwrap();
+        assert_eq!(
+            tracks[0],
+            HistoryTopEntry {
+                name: "One".to_string(),
+                artist: Some("A".to_string()),
+                plays: 2,
+                played_ms: 220_000,
+            }
+        );
+        assert_eq!(tracks.len(), 2);
+        let artists = history.top_artists(HistoryRange::All, 1).unwrap();
+        assert_eq!(artists.len(), 1);
+        assert_eq!((artists[0].name.as_str(), artists[0].plays), ("A", 3));
+        assert_eq!(artists[0].artist, None);
+    }
+
+    #[test]
+    fn refuses_to_record_without_a_path() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let controller = runtime.block_on(ControllerInner::new()).unwrap();
+        let mut history = HistoryInner::new(Arc::new(controller), HistoryOptions::default());
+        // As if HOME weren't set
+        history.store = None;
+
+        let err = runtime.block_on(history.start()).unwrap_err();
+        assert!(matches!(err, MprisError::InvalidConfig(_)), "{}", err);
+        assert!(!history.is_running());
+        assert!(!history.stop());
+        assert!(history.history(HistoryRange::All, 10).is_err());
+    }
+
+    #[test]
+    fn follows_the_player() {
+        let bus = DbusDaemon::start();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let controller = connected_controller(&bus).await;
+            let options = HistoryOptions {
+                path: Some(path.display().to_string()),
+                min_played_ms: Some(100),
+            };
+            let history = HistoryInner::new(controller.clone(), options);
+
+            history.start().await.unwrap();
+            player.set_playback_status("Playing").await;
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            controller.seek(60_000).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            player.set_track(Some(MockTrack::new("2", "Second Song", "Band"))).await;
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            assert!(history.stop());
+            assert!(!history.stop());
+
+            let entries = history.history(HistoryRange::All, 10).unwrap();
+            let titles: Vec<_> = entries.iter().map(|e| e.title.as_str()).collect();
+            assert_eq!(titles, ["Second Song", "First Song"]);
+            assert_eq!(entries[1].seek_count, 1);
+            assert!(entries[1].skipped);
+            assert!(entries[1].played_ms >= 200);
+        });
+    }
+}
@@ -0,0 +1,647 @@
+use crate::error::MprisError;
+use crate::state_file::spotify_tui_dir;
+use crate::types::{InstallProgress, InstallStage};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Release archives we know the digests of. Artifacts whose `sha256` is
+/// null are refused unless the caller passes a digest explicitly.
+const PINNED_MANIFEST: &str = include_str!("../spotifyd-manifest.json");
+/// Name of the installed binary, and of the copy kept for rollback
+const BINARY_NAME: &str = "spotifyd";
+const PREVIOUS_NAME: &str = "spotifyd.previous";
+/// Binary extracted but not yet moved into place
+const STAGED_NAME: &str = ".spotifyd.new";
+/// Archive being downloaded
+const DOWNLOAD_NAME: &str = ".spotifyd-download.tar.gz";
+/// Held for the whole install so two TUIs don't swap binaries under each other
+const LOCK_NAME: &str = ".native-install.lock";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+const DOWNLOAD_ATTEMPTS: u32 = 3;
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    pub version: String,
+    pub artifacts: Vec<Artifact>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Artifact {
+    /// `<os>-<arch>` as in std::env::consts, e.g. "linux-x86_64"
+    pub target: String,
+    pub url: String,
+    /// Lowercase hex SHA-256 of the archive
+    pub sha256: Option<String>,
+}
+
+impl Manifest {
+    pub fn pinned() -> Self {
+        serde_json::from_str(PINNED_MANIFEST).expect("bundled spotifyd manifest is valid JSON")
+    }
+
+    /// Release archive for the platform we're running on
+    pub fn for_current_target(&self) -> Option<&Artifact> {
+        self.artifacts.iter().find(|a| a.target == current_target())
+    }
+}
+
+pub fn current_target() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// ~/.spotify-tui/bin
+pub fn default_install_dir() -> Option<PathBuf> {
+    spotify_tui_dir().map(|dir| dir.join("bin"))
+}
+
+/// Where the archive comes from
+#[derive(Debug, Clone)]
+pub enum InstallSource {
+    Url(String),
+    Archive(PathBuf),
+}
+
+#[derive(Debug)]
+pub struct InstallRequest {
+    pub source: InstallSource,
+    /// Overrides the manifest lookup
+    pub sha256: Option<String>,
+    pub install_dir: PathBuf,
+}
+
+/// A binary moved into place
+#[derive(Debug)]
+pub struct Installed {
+    pub path: PathBuf,
+    pub sha256: String,
+    /// Binary it replaced, kept for `rollback`
+    pub previous: Option<PathBuf>,
+}
+
+fn progress(stage: InstallStage, bytes: u64, total: Option<u64>) -> InstallProgress {
+    InstallProgress {
+        stage,
+        bytes: bytes as i64,
+        total_bytes: total.map(|t| t as i64),
+        percent: total
+            .filter(|&t| t > 0)
+            .map(|t| ((bytes.min(t) * 100) / t) as u32),
+    }
+}
+
+fn failed(message: impl Into<String>) -> MprisError {
+    MprisError::InstallFailed(message.into())
+}
+
+/// Normalise a user-supplied digest, rejecting anything that isn't SHA-256 hex
+fn normalise_digest(digest: &str) -> Result<String, MprisError> {
+    let digest = digest.trim().to_ascii_lowercase();
+    if digest.len() != 64 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(MprisError::InvalidConfig(format!(
+            "sha256 must be 64 hex characters, got {:?}",
+            digest
+        )));
+    }
+    Ok(digest)
+}
+
+/// Digests the archive may have: the explicit one or, failing that, the
+/// manifest entries for this source: matching URL, or this platform for a
+/// local archive. A source with no pinned digest is refused.
+pub fn expected_digests(
+    manifest: &Manifest,
+    request: &InstallRequest,
+) -> Result<Vec<String>, MprisError> {
+    let expected: Vec<String> = match request.sha256 {
+        Some(ref digest) => vec![normalise_digest(digest)?],
+        None => {
+            let target = current_target();
+            manifest
+                .artifacts
+                .iter()
+                .filter(|a| match request.source {
+                    InstallSource::Url(ref url) => a.url == *url,
+                    InstallSource::Archive(_) => a.target == target,
+                })
+                .filter_map(|a| a.sha256.as_deref().map(normalise_digest))
+                .collect::<Result<_, _>>()?
+        }
+    };
+
+    if expected.is_empty() {
+        return Err(failed(format!(
+            "no pinned checksum for {:?}; pass sha256 to install it anyway",
+            request.source
+        )));
+    }
+    Ok(expected)
+}
+
+/// Check `actual` against the digests from `expected_digests`
+pub fn verify(expected: &[String], actual: &str) -> Result<(), MprisError> {
+    if !expected.iter().any(|e| e == actual) {
+        return Err(MprisError::ChecksumMismatch {
+            expected: expected.join(" or "),
+            actual: actual.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Copy `reader` to `writer` in chunks, hashing and reporting as we go
+fn copy_hashed(
+    mut reader: impl Read,
+    mut writer: impl Write,
+    total: Option<u64>,
+    on_progress: &impl Fn(InstallProgress),
+) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut done = 0u64;
+    on_progress(progress(InstallStage::Downloading, 0, total));
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buf[..n]);
+        writer.write_all(&buf[..n])?;
+        done += n as u64;
+        on_progress(progress(InstallStage::Downloading, done, total));
+    }
+    writer.flush()?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Write a response body to `dest`, returning its SHA-256
+fn save(
+    response: ureq::Response,
+    dest: &Path,
+    on_progress: &impl Fn(InstallProgress),
+) -> std::io::Result<String> {
+    let total = response
+        .header("Content-Length")
+        .and_then(|len| len.parse().ok());
+    let file = File::create(dest)?;
+    let digest = copy_hashed(response.into_reader(), &file, total, on_progress)?;
+    file.sync_all()?;
+    Ok(digest)
+}
+
+/// Download `url` to `dest`, retrying dropped connections (HTTP errors
+/// won't fix themselves). Returns its SHA-256.
+fn download(
+    url: &str,
+    dest: &Path,
+    on_progress: &impl Fn(InstallProgress),
+) -> Result<String, MprisError> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT)
+        .build();
+
+    let mut attempt = 1;
+    loop {
+        let error = match agent.get(url).call() {
+            Err(ureq::Error::Status(code, _)) => {
+                return Err(failed(format!("{} returned HTTP {}", url, code)))
+            }
+            Err(ureq::Error::Transport(t)) => failed(format!("downloading {}: {}", url, t)),
+            Ok(response) => match save(response, dest, on_progress) {
+                Ok(digest) => return Ok(digest),
+                Err(e) => failed(format!("downloading {}: {}", url, e)),
+            },
+        };
+        if attempt >= DOWNLOAD_ATTEMPTS {
+            return Err(error);
+        }
+        warn!("Download attempt {} failed: {}", attempt, error);
+        std::thread::sleep(Duration::from_secs(attempt as u64));
+        attempt += 1;
+    }
+}
+
+/// SHA-256 of a local archive
+fn hash_file(path: &Path, on_progress: &impl Fn(InstallProgress)) -> Result<String, MprisError> {
+    let file =
+        File::open(path).map_err(|e| failed(format!("opening {}: {}", path.display(), e)))?;
+    let total = file.metadata().ok().map(|m| m.len());
+    Ok(copy_hashed(file, std::io::sink(), total, on_progress)?)
+}
+
+/// Pull the `spotifyd` binary out of a .tar.gz into `dest` (mode 0755).
+/// Only that one regular file is written, so paths in the archive can't
+/// escape the install directory.
+pub fn extract_binary(archive: &Path, dest: &Path) -> Result<(), MprisError> {
+    let file = File::open(archive)?;
+    let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(file));
+
+    for entry in tar
+        .entries()
+        .map_err(|e| failed(format!("reading archive: {}", e)))?
+    {
+        let mut entry = entry.map_err(|e| failed(format!("reading archive: {}", e)))?;
+        let is_binary = entry.header().entry_type().is_file()
+            && entry
+                .path()
+                .is_ok_and(|p| p.file_name().is_some_and(|name| name == BINARY_NAME));
+        if !is_binary {
+            continue;
+        }
+
+        let mut out = File::options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o755)
+            .open(dest)?;
+        std::io::copy(&mut entry, &mut out)
+            .map_err(|e| failed(format!("extracting {}: {}", BINARY_NAME, e)))?;
+        out.sync_all()?;
+        return Ok(());
+    }
+
+    Err(failed(format!(
+        "archive contains no `{}` binary",
+        BINARY_NAME
+    )))
+}
+
+/// Make renames in `dir` durable
+fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+/// Move `staged` over the installed binary. The current one is hard-linked
+/// to spotifyd.previous first, so there's never a moment without a binary.
+fn swap_in(dir: &Path, staged: &Path) -> Result<Option<PathBuf>, MprisError> {
+    let target = dir.join(BINARY_NAME);
+    let previous = dir.join(PREVIOUS_NAME);
+
+    let kept = match target.is_file() {
+        true => {
+            match std::fs::remove_file(&previous) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+            std::fs::hard_link(&target, &previous)?;
+            Some(previous)
+        }
+        false => None,
+    };
+    std::fs::rename(staged, &target)?;
+    sync_dir(dir)?;
+    Ok(kept)
+}
+
+/// Exclusive flock on the install lock, released on drop
+struct InstallLock {
+    _file: File,
+}
+
+impl InstallLock {
+    fn acquire(dir: &Path) -> std::io::Result<Self> {
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join(LOCK_NAME))?;
+        loop {
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+                return Ok(Self { _file: file });
+            }
+            let err = std::io::Error::last_os_error();
+            if err.kind() != std::io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+}
+
+/// Fetch, verify, extract and atomically install spotifyd. Blocking.
+pub fn install(
+    manifest: &Manifest,
+    request: &InstallRequest,
+    on_progress: impl Fn(InstallProgress),
+) -> Result<Installed, MprisError> {
+    // Before any download, so an unpinned source fails without network traffic
+    let expected = expected_digests(manifest, request)?;
+    let dir = &request.install_dir;
+    std::fs::create_dir_all(dir)?;
+    let _lock = InstallLock::acquire(dir)?;
+
+    let download_path = dir.join(DOWNLOAD_NAME);
+    let staged = dir.join(STAGED_NAME);
+    let result = (|| {
+        let (archive, digest) = match request.source {
+            InstallSource::Url(ref url) => {
+                info!("Downloading spotifyd from {}", url);
+                let digest = download(url, &download_path, &on_progress)?;
+                (download_path.as_path(), digest)
+            }
+            InstallSource::Archive(ref path) => (path.as_path(), hash_file(path, &on_progress)?),
+        };
+
+        on_progress(progress(InstallStage::Verifying, 0, None));
+        verify(&expected, &digest)?;
+
+        on_progress(progress(InstallStage::Extracting, 0, None));
+        extract_binary(archive, &staged)?;
+
+        on_progress(progress(InstallStage::Installing, 0, None));
+        let previous = swap_in(dir, &staged)?;
+        Ok(Installed {
+            path: dir.join(BINARY_NAME),
+            sha256: digest,
+            previous,
+        })
+    })();
+
+    let _ = std::fs::remove_file(&download_path);
+    let _ = std::fs::remove_file(&staged);
+    if let Ok(ref installed) = result {
+        info!(
+            "Installed spotifyd at {} (sha256 {})",
+            installed.path.display(),
+            installed.sha256
+        );
+    }
+    result
+}
+
+/// Put spotifyd.previous back in place of the installed binary
+pub fn rollback(dir: &Path) -> Result<PathBuf, MprisError> {
+    std::fs::create_dir_all(dir)?;
+    let _lock = InstallLock::acquire(dir)?;
+
+    let previous = dir.join(PREVIOUS_NAME);
+    let target = dir.join(BINARY_NAME);
+    if !previous.is_file() {
+        return Err(failed(format!("no previous spotifyd in {}", dir.display())));
+    }
+    std::fs::rename(&previous, &target)?;
+    sync_dir(dir)?;
+    info!("Rolled spotifyd back to the previous version");
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::write_archive;
+    use std::io::BufRead;
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+
+    /// Serve `body` at any path, `requests` times; returns the base URL
+    fn serve(status: u16, body: Vec<u8>, requests: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                write!(
+                    stream,
+                    "HTTP/1.1 {} X\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                )
+                .unwrap();
+                stream.write_all(&body).unwrap();
+            }
+        });
+        url
+    }
+
+    fn manifest(url: &str, sha256: Option<&str>) -> Manifest {
+        Manifest {
+            version: "0.4.2".to_string(),
+            artifacts: vec![Artifact {
+                target: current_target(),
+                url: url.to_string(),
+                sha256: sha256.map(str::to_string),
+            }],
+        }
+    }
+
+    const V1: &str = "#!/bin/sh\necho 'spotifyd 0.4.1'\n";
+    const V2: &str = "#!/bin/sh\necho 'spotifyd 0.4.2'\n";
+
+    #[test]
+    fn bundled_manifest_parses() {
+        let manifest = Manifest::pinned();
+        assert!(!manifest.artifacts.is_empty());
+        assert!(manifest
+            .artifacts
+            .iter()
+            .any(|a| a.target == "linux-x86_64"));
+    }
+
+    #[test]
+    #[ignore = "digests unpinned: run scripts/pin-spotifyd-digests.sh with network access"]
+    fn bundled_manifest_pins_every_artifact() {
+        for artifact in Manifest::pinned().artifacts {
+            let digest = artifact.sha256.as_deref();
+            assert!(
+                digest.is_some_and(|d| normalise_digest(d).is_ok()),
+                "{} has no sha256",
+                artifact.target
+            );
+        }
+    }
+
+    #[test]
+    fn installs_from_url_verified_against_manifest() {
+        let work = tempfile::tempdir().unwrap();
+        let archive = work.path().join("spotifyd.tar.gz");
+        let digest = write_archive(&archive, &[("spotifyd", V2, 0o755)]);
+        let url = format!(
+            "{}/spotifyd.tar.gz",
+            serve(200, std::fs::read(&archive).unwrap(), 1)
+        );
+
+        let dir = work.path().join("bin");
+        let events = Mutex::new(Vec::new());
+        let installed = install(
+            &manifest(&url, Some(&digest)),
+            &InstallRequest {
+                source: InstallSource::Url(url.clone()),
+                sha256: None,
+                install_dir: dir.clone(),
+            },
+            |p| events.lock().unwrap().push(p),
+        )
+        .unwrap();
+
+        assert_eq!(installed.path, dir.join("spotifyd"));
+        assert_eq!(installed.sha256, digest);
+        assert!(installed.previous.is_none());
+        assert_eq!(std::fs::read_to_string(&installed.path).unwrap(), V2);
+        let mode = std::fs::metadata(&installed.path).unwrap().permissions();
+        assert_eq!(
+            std::os::unix::fs::PermissionsExt::mode(&mode) & 0o777,
+            0o755
+        );
+        assert!(!dir.join(DOWNLOAD_NAME).exists() && !dir.join(STAGED_NAME).exists());
+
+        let events = events.into_inner().unwrap();
+        let last_download = events
+            .iter()
+            .rfind(|p| p.stage == InstallStage::Downloading)
+            .unwrap();
+        assert_eq!(last_download.percent, Some(100));
+        let stages: Vec<_> = events
+            .iter()
+            .map(|p| p.stage)
+            .filter(|s| *s != InstallStage::Downloading)
+            .collect();
+        assert_eq!(
+            stages,
+            [
+                InstallStage::Verifying,
+                InstallStage::Extracting,
+                InstallStage::Installing
+            ]
+        );
+    }
+
+    #[test]
+    fn checksum_mismatch_leaves_existing_binary() {
+        let work = tempfile::tempdir().unwrap();
+        let dir = work.path().join("bin");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("spotifyd"), V1).unwrap();
+
+        let archive = work.path().join("spotifyd.tar.gz");
+        write_archive(&archive, &[("spotifyd", V2, 0o755)]);
+        let url = format!(
+            "{}/spotifyd.tar.gz",
+            serve(200, std::fs::read(&archive).unwrap(), 1)
+        );
+
+        let err = install(
+            &manifest(&url, Some(&"0".repeat(64))),
+            &InstallRequest {
+                source: InstallSource::Url(url),
+                sha256: None,
+                install_dir: dir.clone(),
+            },
+            |_| {},
+        )
+        .unwrap_err();
+        assert!(
+            matches!(err, MprisError::ChecksumMismatch { .. }),
+            "{}",
+            err
+        );
+        assert_eq!(std::fs::read_to_string(dir.join("spotifyd")).unwrap(), V1);
+        assert!(!dir.join(PREVIOUS_NAME).exists());
+    }
+
+    #[test]
+    fn refuses_unpinned_sources_and_http_errors() {
+        let work = tempfile::tempdir().unwrap();
+        let archive = work.path().join("spotifyd.tar.gz");
+        write_archive(&archive, &[("spotifyd", V2, 0o755)]);
+
+        let request = InstallRequest {
+            source: InstallSource::Archive(archive),
+            sha256: None,
+            install_dir: work.path().join("bin"),
+        };
+        let err = install(&manifest("http://unused", None), &request, |_| {}).unwrap_err();
+        assert!(err.to_string().contains("no pinned checksum"), "{}", err);
+
+        // Refused before downloading: the server is never asked
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let url = format!("http://{}/spotifyd.tar.gz", listener.local_addr().unwrap());
+        let request = InstallRequest {
+            source: InstallSource::Url(url.clone()),
+            sha256: None,
+            install_dir: work.path().join("unpinned"),
+        };
+        let err = install(&manifest(&url, None), &request, |_| {}).unwrap_err();
+        assert!(err.to_string().contains("no pinned checksum"), "{}", err);
+        assert!(listener.accept().is_err());
+        assert!(!work.path().join("unpinned").exists());
+
+        let url = format!("{}/missing.tar.gz", serve(404, Vec::new(), 1));
+        let request = InstallRequest {
+            source: InstallSource::Url(url.clone()),
+            sha256: Some("0".repeat(64)),
+            install_dir: work.path().join("bin"),
+        };
+        let err = install(&manifest(&url, None), &request, |_| {}).unwrap_err();
+        assert!(err.to_string().contains("HTTP 404"), "{}", err);
+    }
+
+    #[test]
+    fn reinstall_keeps_previous_for_rollback() {
+        let work = tempfile::tempdir().unwrap();
+        let dir = work.path().join("bin");
+        let old = work.path().join("old.tar.gz");
+        let new = work.path().join("new.tar.gz");
+        let old_digest = write_archive(&old, &[("spotifyd-0.4.1/spotifyd", V1, 0o755)]);
+        let new_digest = write_archive(&new, &[("README", "hi", 0o644), ("spotifyd", V2, 0o755)]);
+
+        for (archive, digest) in [(&old, &old_digest), (&new, &new_digest)] {
+            install(
+                &manifest("http://unused", None),
+                &InstallRequest {
+                    source: InstallSource::Archive(archive.clone()),
+                    sha256: Some(digest.to_uppercase()),
+                    install_dir: dir.clone(),
+                },
+                |_| {},
+            )
+            .unwrap();
+        }
+        assert_eq!(std::fs::read_to_string(dir.join("spotifyd")).unwrap(), V2);
+        assert_eq!(
+            std::fs::read_to_string(dir.join(PREVIOUS_NAME)).unwrap(),
+            V1
+        );
+
+        rollback(&dir).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("spotifyd")).unwrap(), V1);
+        assert!(!dir.join(PREVIOUS_NAME).exists());
+        assert!(rollback(&dir).is_err());
+    }
+
+    #[test]
+    fn archive_without_binary_is_rejected() {
+        let work = tempfile::tempdir().unwrap();
+        let archive = work.path().join("empty.tar.gz");
+        let digest = write_archive(&archive, &[("spotifyd-docs/README", "hi", 0o644)]);
+
+        let err = install(
+            &manifest("http://unused", None),
+            &InstallRequest {
+                source: InstallSource::Archive(archive),
+                sha256: Some(digest),
+                install_dir: work.path().join("bin"),
+            },
+            |_| {},
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("no `spotifyd` binary"), "{}", err);
+        assert!(!work.path().join("bin/spotifyd").exists());
+    }
+}
@@ -0,0 +1,651 @@
+//! Local control socket: newline-delimited JSON-RPC 2.0 over a Unix socket,
+//! so status bar widgets and scripts talk to the TUI's state instead of
+//! going around it to spotifyd.
+//!
+//! Methods mirror `MprisController` (`getState`, `playPause`, `setVolume`,
+//! `startSleepTimer`...) and, when a supervisor is attached,
+//! `SpotifydSupervisor` under a `spotifyd.` prefix. `subscribe` turns on `state` and `spotifydStatus`
+//! notifications for the connection. Only processes of our own user may
+//! connect.
+
+use crate::controller::ControllerInner;
+use crate::error::MprisError;
+use crate::procfs;
+use crate::supervisor::SupervisorInner;
+use crate::types::{FadeCurve, RepeatMode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::task::AbortHandle;
+use tracing::{debug, info, warn};
+
+/// Socket file name, in $XDG_RUNTIME_DIR or ~/.spotify-tui
+const SOCKET_FILE: &str = "spotify-tui.sock";
+/// Requests longer than this are rejected and the connection closed
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+/// Outgoing messages buffered per connection before it's considered stuck
+const OUTBOX_SIZE: usize = 64;
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+/// $XDG_RUNTIME_DIR/spotify-tui.sock, or ~/.spotify-tui/spotify-tui.sock
+pub fn default_socket_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(crate::state_file::spotify_tui_dir)
+        .map(|dir| dir.join(SOCKET_FILE))
+}
+
+// ─────────────────────────────────────────────────────────────
+// Requests
+// ─────────────────────────────────────────────────────────────
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    jsonrpc: Option<String>,
+    /// Absent for notifications, which get no response
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, PartialEq)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<MprisError> for RpcError {
+    fn from(err: MprisError) -> Self {
+        Self::new(SERVER_ERROR, err.to_string())
+    }
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": e.code, "message": e.message },
+        }),
+    }
+}
+
+fn notification(method: &str, params: impl serde::Serialize) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+/// Named parameter `name`, deserialized
+fn param<T: serde::de::DeserializeOwned>(params: &Value, name: &str) -> Result<T, RpcError> {
+    let value = params
+        .get(name)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("missing parameter {:?}", name)))?;
+    serde_json::from_value(value.clone())
+        .map_err(|e| RpcError::new(INVALID_PARAMS, format!("invalid {:?}: {}", name, e)))
+}
+
+/// Optional named parameter `name`
+fn optional_param<T: serde::de::DeserializeOwned>(
+    params: &Value,
+    name: &str,
+) -> Result<Option<T>, RpcError> {
+    match params.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(_) => param(params, name).map(Some),
+    }
+}
+
+fn to_value(value: impl serde::Serialize) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))
+}
+
+/// Result of a command with nothing to return
+fn done(result: Result<(), MprisError>) -> Result<Value, RpcError> {
+    result.map(|()| Value::Null).map_err(RpcError::from)
+}
+
+// ─────────────────────────────────────────────────────────────
+// Connections
+// ─────────────────────────────────────────────────────────────
+
+/// Whether a peer with this uid may use the socket
+fn peer_allowed(uid: u32) -> bool {
+    uid == procfs::current_uid()
+}
+
+/// What a connection is talking to
+#[derive(Clone)]
+struct Handler {
+    controller: Arc<ControllerInner>,
+    supervisor: Option<Arc<SupervisorInner>>,
+}
+
+/// Per-connection state: its outbox and notification forwarders
+struct Session {
+    outbox: mpsc::Sender<String>,
+    subscriptions: Vec<AbortHandle>,
+}
+
+impl Session {
+    fn unsubscribe(&mut self) {
+        for task in self.subscriptions.drain(..) {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.unsubscribe();
+    }
+}
+
+impl Handler {
+    fn supervisor(&self) -> Result<&SupervisorInner, RpcError> {
+        self.supervisor
+            .as_deref()
+            .ok_or_else(|| RpcError::new(METHOD_NOT_FOUND, "no spotifyd supervisor attached"))
+    }
+
+    async fn call(
+        &self,
+        session: &mut Session,
+        method: &str,
+        params: &Value,
+    ) -> Result<Value, RpcError> {
+        let controller = &self.controller;
+        match method {
+            "getState" => to_value(controller.state().await),
+            "playPause" => Ok(json!(controller.play_pause().await?)),
+            "play" => done(controller.play().await),
+            "pause" => done(controller.pause().await),
+            "next" => done(controller.next().await),
+            "previous" => done(controller.previous().await),
+            "seek" => done(controller.seek(param(params, "offsetMs")?).await),
+            "setVolume" => {
+                let volume: f64 = param(params, "volume")?;
+                if !(0.0..=1.0).contains(&volume) {
+                    return Err(RpcError::new(INVALID_PARAMS, "volume must be 0.0-1.0"));
+                }
+                done(controller.set_volume(volume).await)
+            }
+            "adjustVolume" => Ok(json!(controller.adjust_volume(param(params, "delta")?).await?)),
+            "fadeTo" => {
+                let target: f64 = param(params, "target")?;
+                if !(0.0..=1.0).contains(&target) {
+                    return Err(RpcError::new(INVALID_PARAMS, "target must be 0.0-1.0"));
+                }
+                let duration_ms = param(params, "durationMs")?;
+                let curve: Option<FadeCurve> = optional_param(params, "curve")?;
+                let faded = controller
+                    .fade_to(target, duration_ms, curve.unwrap_or_default())
+                    .await?;
+                Ok(json!(faded))
+            }
+            "mute" => Ok(json!(controller.mute().await?)),
+            "unmute" => Ok(json!(controller.unmute().await?)),
+            "setShuffle" => done(controller.set_shuffle(param(params, "shuffle")?).await),
+            "setRepeat" => {
+                let repeat: RepeatMode = param(params, "repeat")?;
+                done(controller.set_repeat(repeat).await)
+            }
+            "refreshState" => done(controller.refresh_state().await),
+            "startSleepTimer" => {
+                let options = serde_json::from_value(params.clone())
+                    .map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))?;
+                match controller.start_sleep_timer(options).await {
+                    Err(MprisError::InvalidArgument(message)) => {
+                        Err(RpcError::new(INVALID_PARAMS, message))
+                    }
+                    result => to_value(result?),
+                }
+            }
+            "cancelSleepTimer" => Ok(json!(controller.cancel_sleep_timer().await)),
+            "subscribe" => Ok(self.subscribe(session)),
+            "unsubscribe" => {
+                session.unsubscribe();
+                Ok(Value::Null)
+            }
+            "spotifyd.getStatus" => to_value(self.supervisor()?.get_status()),
+            "spotifyd.startOrAdopt" => to_value(self.supervisor()?.start_or_adopt().await?),
+            "spotifyd.stop" => {
+                let force = optional_param(params, "force")?.unwrap_or(false);
+                to_value(self.supervisor()?.stop(force, None).await?)
+            }
+            "spotifyd.restart" => to_value(self.supervisor()?.restart(None).await?),
+            "spotifyd.isRunning" => Ok(json!(self.supervisor()?.is_alive().await)),
+            "spotifyd.isHealthy" => Ok(json!(self.supervisor()?.is_healthy().await)),
+            "spotifyd.getPid" => Ok(json!(self.supervisor()?.get_tracked_pid().await)),
+            "spotifyd.checkAuth" => to_value(self.supervisor()?.refresh_auth().await),
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method {:?}", method))),
+        }
+    }
+
+    /// Forward state (and spotifyd status) changes to the connection as
+    /// notifications. Returns the notifications enabled.
+    fn subscribe(&self, session: &mut Session) -> Value {
+        session.unsubscribe();
+        let mut events = vec!["state"];
+
+        let outbox = session.outbox.clone();
+        let controller = self.controller.clone();
+        let mut changes = self.controller.subscribe_state_changes();
+        let state_task = tokio::spawn(async move {
+            loop {
+                let state = match changes.recv().await {
+                    Ok(state) => state,
+                    // Only the latest state matters
+                    Err(broadcast::error::RecvError::Lagged(_)) => controller.state().await,
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+                let message = notification("state", state).to_string();
+                if outbox.send(message).await.is_err() {
+                    return;
+                }
+            }
+        });
+        session.subscriptions.push(state_task.abort_handle());
+
+        if let Some(ref supervisor) = self.supervisor {
+            events.push("spotifydStatus");
+            let outbox = session.outbox.clone();
+            let mut status = supervisor.subscribe_status();
+            let status_task = tokio::spawn(async move {
+                while status.changed().await.is_ok() {
+                    let message = notification("spotifydStatus", &*status.borrow()).to_string();
+                    if outbox.send(message).await.is_err() {
+                        return;
+                    }
+                }
+            });
+            session.subscriptions.push(status_task.abort_handle());
+        }
+
+        json!(events)
+    }
+
+    /// Handle one request line; None when no response is due
+    async fn handle_line(&self, session: &mut Session, line: &str) -> Option<Value> {
+        let value: Value = match serde_json::from_str(line) {
+            Ok(value) => value,
+            Err(e) => {
+                let error = RpcError::new(PARSE_ERROR, e.to_string());
+                return Some(response(Value::Null, Err(error)));
+            }
+        };
+        let id_of = |value: &Value| value.get("id").cloned().unwrap_or(Value::Null);
+        let request: Request = match serde_json::from_value(value.clone()) {
+            Ok(request) => request,
+            Err(e) => {
+                let error = RpcError::new(INVALID_REQUEST, e.to_string());
+                return Some(response(id_of(&value), Err(error)));
+            }
+        };
+        if request.jsonrpc.as_deref() != Some("2.0") {
+            let error = RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\"");
+            return Some(response(id_of(&value), Err(error)));
+        }
+
+        debug!("IPC request {}", request.method);
+        let result = self.call(session, &request.method, &request.params).await;
+        request.id.map(|id| response(id, result))
+    }
+
+    async fn serve(self, stream: UnixStream, mut shutdown: watch::Receiver<bool>) {
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        let (outbox, mut outgoing) = mpsc::channel::<String>(OUTBOX_SIZE);
+
+        let write_task = tokio::spawn(async move {
+            while let Some(mut message) = outgoing.recv().await {
+                message.push('\n');
+                if writer.write_all(message.as_bytes()).await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        let mut session = Session {
+            outbox,
+            subscriptions: Vec::new(),
+        };
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let mut limited = (&mut reader).take(MAX_REQUEST_BYTES as u64 + 1);
+            let read = tokio::select! {
+                _ = shutdown.changed() => break,
+                read = limited.read_line(&mut line) => read,
+            };
+            match read {
+                Ok(0) | Err(_) => break,
+                Ok(_) if line.len() > MAX_REQUEST_BYTES => {
+                    let error = RpcError::new(INVALID_REQUEST, "request too large");
+                    let reply = response(Value::Null, Err(error)).to_string();
+                    let _ = session.outbox.send(reply).await;
+                    break;
+                }
+                Ok(_) => {}
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(reply) = self.handle_line(&mut session, line.trim()).await {
+                if session.outbox.send(reply.to_string()).await.is_err() {
+                    break;
+                }
+            }
+        }
+
+        // Let queued replies go out, then hang up
+        drop(session);
+        let _ = tokio::time::timeout(Duration::from_secs(1), write_task).await;
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// Server
+// ─────────────────────────────────────────────────────────────
+
+struct Running {
+    path: PathBuf,
+    shutdown: watch::Sender<bool>,
+    accept_task: AbortHandle,
+}
+
+pub struct IpcServerInner {
+    handler: Handler,
+    running: std::sync::Mutex<Option<Running>>,
+}
+
+/// Clear a socket left behind by a server that didn't shut down cleanly.
+/// Refuses to touch anything that isn't a socket, or one still being served.
+async fn remove_stale_socket(path: &Path) -> Result<(), MprisError> {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(MprisError::IpcFailed(format!(
+            "{} exists and is not a socket",
+            path.display()
+        )));
+    }
+    if UnixStream::connect(path).await.is_ok() {
+        return Err(MprisError::IpcFailed(format!(
+            "another server is listening on {}",
+            path.display()
+        )));
+    }
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
+impl IpcServerInner {
+    pub fn new(controller: Arc<ControllerInner>, supervisor: Option<Arc<SupervisorInner>>) -> Self {
+        Self {
+            handler: Handler {
+                controller,
+                supervisor,
+            },
+            running: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Listen on `path` (default: see `default_socket_path`). Returns the
+    /// socket path; if already running, the one in use.
+    pub async fn start(&self, path: Option<PathBuf>) -> Result<PathBuf, MprisError> {
+        if let Some(ref running) = *self.running.lock().unwrap() {
+            return Ok(running.path.clone());
+        }
+
+        let path = path
+            .or_else(default_socket_path)
+            .ok_or_else(|| MprisError::IpcFailed("cannot determine socket path".to_string()))?;
+        if let Some(dir) = path.parent() {
+            std::fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+        }
+        remove_stale_socket(&path).await?;
+
+        let listener = UnixListener::bind(&path)?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+        info!("IPC server listening on {}", path.display());
+
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        let handler = self.handler.clone();
+        let accept_task = tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!("IPC accept failed: {}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+                match stream.peer_cred() {
+                    Ok(cred) if peer_allowed(cred.uid()) => {
+                        tokio::spawn(handler.clone().serve(stream, shutdown_rx.clone()));
+                    }
+                    Ok(cred) => warn!("Rejected IPC connection from uid {}", cred.uid()),
+                    Err(e) => warn!("Rejected IPC connection without credentials: {}", e),
+                }
+            }
+        });
+
+        let mut running = self.running.lock().unwrap();
+        if let Some(ref other) = *running {
+            // Lost a race with a concurrent start()
+            accept_task.abort();
+            return Ok(other.path.clone());
+        }
+        *running = Some(Running {
+            path: path.clone(),
+            shutdown,
+            accept_task: accept_task.abort_handle(),
+        });
+        Ok(path)
+    }
+
+    /// Stop listening, close connections and remove the socket.
+    /// Returns false if it wasn't running.
+    pub fn stop(&self) -> bool {
+        let Some(running) = self.running.lock().unwrap().take() else {
+            return false;
+        };
+        running.accept_task.abort();
+        let _ = running.shutdown.send(true);
+        if let Err(e) = std::fs::remove_file(&running.path) {
+            warn!("Could not remove {}: {}", running.path.display(), e);
+        }
+        info!("IPC server stopped");
+        true
+    }
+
+    /// Socket path while running
+    pub fn path(&self) -> Option<PathBuf> {
+        self.running.lock().unwrap().as_ref().map(|r| r.path.clone())
+    }
+}
+
+impl Drop for IpcServerInner {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_player::{connected_controller, DbusDaemon, MockOptions, MockPlayer};
+    use tokio::io::{BufReader, Lines};
+    use tokio::net::unix::OwnedReadHalf;
+    use tokio::net::unix::OwnedWriteHalf;
+
+    struct Client {
+        lines: Lines<BufReader<OwnedReadHalf>>,
+        writer: OwnedWriteHalf,
+    }
+
+    impl Client {
+        async fn connect(path: &Path) -> Self {
+            let (reader, writer) = UnixStream::connect(path).await.unwrap().into_split();
+            Self {
+                lines: BufReader::new(reader).lines(),
+                writer,
+            }
+        }
+
+        async fn send(&mut self, line: &str) {
+            self.writer.write_all(format!("{}\n", line).as_bytes()).await.unwrap();
+        }
+
+        async fn next(&mut self) -> Value {
+            let line = tokio::time::timeout(Duration::from_secs(2), self.lines.next_line())
+                .await
+                .expect("no message from server")
+                .unwrap()
+                .expect("connection closed");
+            serde_json::from_str(&line).unwrap()
+        }
+
+        async fn call(&mut self, id: u32, method: &str, params: Value) -> Value {
+            let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+            self.send(&request.to_string()).await;
+            self.next().await
+        }
+    }
+
+    /// Server on `dir`/ctl.sock with a controller connected over `bus`
+    async fn server(bus: &DbusDaemon, dir: &Path) -> (IpcServerInner, PathBuf) {
+        let server = IpcServerInner::new(connected_controller(bus).await, None);
+        let path = server.start(Some(dir.join("ctl.sock"))).await.unwrap();
+        (server, path)
+    }
+
+    #[test]
+    fn answers_requests_and_reports_errors() {
+        let bus = DbusDaemon::start();
+        let dir = tempfile::tempdir().unwrap();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let (_server, path) = server(&bus, dir.path()).await;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+
+            let mut client = Client::connect(&path).await;
+            let reply = client.call(1, "setVolume", json!({ "volume": 0.6 })).await;
+            assert_eq!(reply, json!({ "jsonrpc": "2.0", "id": 1, "result": null }));
+            assert!(player.calls().contains(&"Volume=0.6".to_string()));
+            let reply = client.call(7, "adjustVolume", json!({ "delta": -0.1 })).await;
+            assert_eq!(reply["result"], json!(0.5));
+            let reply = client.call(8, "adjustVolume", json!({ "delta": 0.1 })).await;
+            assert_eq!(reply["result"], json!(0.6));
+
+            let reply = client.call(2, "getState", Value::Null).await;
+            assert_eq!(reply["result"]["volume"], json!(0.6));
+            assert_eq!(reply["result"]["repeat"], json!("None"));
+
+            let reply = client.call(3, "setRepeat", json!({ "repeat": "Sometimes" })).await;
+            assert_eq!(reply["error"]["code"], json!(INVALID_PARAMS));
+            let reply = client.call(4, "rewind", Value::Null).await;
+            assert_eq!(reply["error"]["code"], json!(METHOD_NOT_FOUND));
+            let reply = client.call(5, "spotifyd.getStatus", Value::Null).await;
+            assert_eq!(reply["error"]["code"], json!(METHOD_NOT_FOUND));
+            let reply = client.call(11, "startSleepTimer", json!({ "mode": "AfterTracks" })).await;
+            assert_eq!(reply["error"]["code"], json!(INVALID_PARAMS));
+            let timer = json!({ "mode": "Duration", "durationMs": 60_000 });
+            let reply = client.call(12, "startSleepTimer", timer).await;
+            assert_eq!(reply["result"]["tracksRemaining"], Value::Null);
+            let reply = client.call(13, "cancelSleepTimer", Value::Null).await;
+            assert_eq!(reply["result"], json!(true));
+
+            client.send("{not json").await;
+            assert_eq!(client.next().await["error"]["code"], json!(PARSE_ERROR));
+
+            // Notifications get no reply; the next message answers id 6
+            client.send(r#"{"jsonrpc":"2.0","method":"next"}"#).await;
+            let reply = client.call(6, "playPause", Value::Null).await;
+            assert_eq!(reply["id"], json!(6));
+            assert_eq!(reply["result"], json!(true));
+        });
+    }
+
+    #[test]
+    fn subscribers_get_state_notifications() {
+        let bus = DbusDaemon::start();
+        let dir = tempfile::tempdir().unwrap();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let (_server, path) = server(&bus, dir.path()).await;
+            let mut client = Client::connect(&path).await;
+
+            let reply = client.call(1, "subscribe", Value::Null).await;
+            assert_eq!(reply["result"], json!(["state"]));
+
+            player.set_playback_status("Playing").await;
+            let message = client.next().await;
+            assert_eq!(message["method"], json!("state"));
+            assert_eq!(message["params"]["isPlaying"], json!(true));
+            assert!(message.get("id").is_none());
+        });
+    }
+
+    #[test]
+    fn stop_removes_socket_and_stale_sockets_are_replaced() {
+        let bus = DbusDaemon::start();
+        let dir = tempfile::tempdir().unwrap();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let _player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let (server, path) = server(&bus, dir.path()).await;
+
+            // A second server must not steal a live socket
+            let controller = ControllerInner::new().await.unwrap();
+            let other = IpcServerInner::new(Arc::new(controller), None);
+            assert!(other.start(Some(path.clone())).await.is_err());
+
+            assert!(server.stop());
+            assert!(!path.exists());
+            assert!(!server.stop());
+
+            // Leftover socket from a crashed server
+            drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+            assert_eq!(other.start(Some(path.clone())).await.unwrap(), path);
+
+            // Never delete files that aren't sockets
+            let file = dir.path().join("regular");
+            std::fs::write(&file, "keep").unwrap();
+            let third = IpcServerInner::new(Arc::new(ControllerInner::new().await.unwrap()), None);
+            assert!(third.start(Some(file.clone())).await.is_err());
+            assert!(file.exists());
+        });
+    }
+
+    #[test]
+    fn only_our_uid_may_connect() {
+        assert!(peer_allowed(procfs::current_uid()));
+        assert!(!peer_allowed(procfs::current_uid().wrapping_add(1)));
+    }
+}
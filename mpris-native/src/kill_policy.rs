@@ -0,0 +1,184 @@
+use crate::procfs::ProcessEntry;
+use crate::state_file::SpawnedDaemon;
+use crate::types::SpotifydKillPolicy;
+
+/// Value of `--flag value` or `--flag=value` in an argument list
+pub fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == flag {
+            return iter.next().map(|v| v.as_str());
+        }
+        if let Some(value) = arg.strip_prefix(flag).and_then(|rest| rest.strip_prefix('=')) {
+            return Some(value);
+        }
+    }
+    None
+}
+
+impl SpotifydKillPolicy {
+    pub fn match_device_name(&self) -> bool {
+        self.match_device_name.unwrap_or(true)
+    }
+
+    pub fn match_config_path(&self) -> bool {
+        self.match_config_path.unwrap_or(false)
+    }
+
+    pub fn only_spawned(&self) -> bool {
+        self.only_spawned.unwrap_or(false)
+    }
+
+    /// Decide whether a spotifyd process may be killed to make room for ours.
+    ///
+    /// `our_args` are the arguments we would launch spotifyd with; device name
+    /// and config path are compared against the candidate's own command line.
+    /// Returns the reason for skipping it otherwise.
+    pub fn check(
+        &self,
+        process: &ProcessEntry,
+        uid: u32,
+        our_args: &[String],
+        spawned: &[SpawnedDaemon],
+    ) -> Result<(), String> {
+        if process.uid != uid {
+            return Err(format!("owned by another user (uid {})", process.uid));
+        }
+
+        if self.only_spawned() && !spawned.iter().any(|d| d.matches(&process.identity())) {
+            return Err("not spawned by spotify-tui".to_string());
+        }
+
+        let their_args = process.cmdline.get(1..).unwrap_or_default();
+
+        if self.match_device_name() {
+            let ours = arg_value(our_args, "--device-name");
+            let theirs = arg_value(their_args, "--device-name");
+            if ours != theirs {
+                return Err(format!(
+                    "device name {:?} does not match {:?}",
+                    theirs.unwrap_or("<default>"),
+                    ours.unwrap_or("<default>")
+                ));
+            }
+        }
+
+        if self.match_config_path() {
+            let ours = arg_value(our_args, "--config-path");
+            let theirs = arg_value(their_args, "--config-path");
+            if ours != theirs {
+                return Err(format!(
+                    "config path {:?} does not match {:?}",
+                    theirs.unwrap_or("<default>"),
+                    ours.unwrap_or("<default>")
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::procfs::ProcStat;
+
+    const UID: u32 = 1000;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn spotifyd(uid: u32, cmdline: &[&str]) -> ProcessEntry {
+        ProcessEntry {
+            pid: 4242,
+            uid,
+            stat: ProcStat {
+                comm: "spotifyd".to_string(),
+                state: 'S',
+                start_ticks: 777,
+                rss_pages: 0,
+            },
+            exe: Some("/usr/bin/spotifyd".to_string()),
+            cmdline: args(cmdline),
+        }
+    }
+
+    fn spawned(pid: u32, start_ticks: u64) -> SpawnedDaemon {
+        SpawnedDaemon {
+            pid,
+            start_ticks,
+            binary_path: "/usr/bin/spotifyd".to_string(),
+            args: Vec::new(),
+            device_name: None,
+            spawned_at: 0,
+        }
+    }
+
+    #[test]
+    fn never_kills_other_users_processes() {
+        let policy = SpotifydKillPolicy {
+            match_device_name: Some(false),
+            ..Default::default()
+        };
+        let theirs = spotifyd(UID + 1, &["spotifyd", "--no-daemon"]);
+        let err = policy.check(&theirs, UID, &[], &[spawned(4242, 777)]).unwrap_err();
+        assert!(err.contains("another user"), "{}", err);
+        assert!(policy.check(&spotifyd(UID, &["spotifyd"]), UID, &[], &[]).is_ok());
+    }
+
+    #[test]
+    fn device_name_must_match_by_default() {
+        let policy = SpotifydKillPolicy::default();
+        let ours = args(&["--device-name", "spotify-tui"]);
+        let kitchen = spotifyd(UID, &["spotifyd", "--device-name=kitchen"]);
+        let err = policy.check(&kitchen, UID, &ours, &[]).unwrap_err();
+        assert!(err.contains("kitchen"), "{}", err);
+        // An unnamed spotifyd is a different device from a named one
+        assert!(policy.check(&spotifyd(UID, &["spotifyd"]), UID, &ours, &[]).is_err());
+
+        let same = spotifyd(UID, &["spotifyd", "--device-name", "spotify-tui"]);
+        assert!(policy.check(&same, UID, &ours, &[]).is_ok());
+    }
+
+    #[test]
+    fn config_path_must_match_when_asked() {
+        let ours = args(&["--config-path", "/home/me/.spotify-tui/spotifyd.conf"]);
+        let other = spotifyd(UID, &["spotifyd", "--config-path", "/etc/spotifyd.conf"]);
+        assert!(SpotifydKillPolicy::default().check(&other, UID, &ours, &[]).is_ok());
+
+        let policy = SpotifydKillPolicy {
+            match_config_path: Some(true),
+            ..Default::default()
+        };
+        let err = policy.check(&other, UID, &ours, &[]).unwrap_err();
+        assert!(err.contains("/etc/spotifyd.conf"), "{}", err);
+        let config = "--config-path=/home/me/.spotify-tui/spotifyd.conf";
+        let same = spotifyd(UID, &["spotifyd", config]);
+        assert!(policy.check(&same, UID, &ours, &[]).is_ok());
+    }
+
+    #[test]
+    fn only_spawned_needs_a_state_file_match() {
+        let policy = SpotifydKillPolicy {
+            only_spawned: Some(true),
+            ..Default::default()
+        };
+        let process = spotifyd(UID, &["spotifyd"]);
+        assert!(policy.check(&process, UID, &[], &[]).is_err());
+        // Same PID, different start time: a recycled PID, not ours
+        let err = policy.check(&process, UID, &[], &[spawned(4242, 778)]).unwrap_err();
+        assert!(err.contains("not spawned"), "{}", err);
+        assert!(policy.check(&process, UID, &[], &[spawned(4242, 777)]).is_ok());
+    }
+
+    #[test]
+    fn reads_flag_values_in_both_forms() {
+        let cmdline = args(&["--device-name", "a", "--config-path=/b", "--backend"]);
+        assert_eq!(arg_value(&cmdline, "--device-name"), Some("a"));
+        assert_eq!(arg_value(&cmdline, "--config-path"), Some("/b"));
+        assert_eq!(arg_value(&cmdline, "--backend"), None);
+        assert_eq!(arg_value(&cmdline, "--device"), None);
+    }
+}
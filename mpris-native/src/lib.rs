@@ -1,10 +1,32 @@
-mod controller;
-mod error;
-mod supervisor;
-mod types;
+mod auth;
+mod binary;
+mod bus;
+pub mod controller;
+pub mod error;
+pub mod history;
+mod installer;
+pub mod ipc;
+mod kill_policy;
+pub mod media_keys;
+#[cfg(any(test, feature = "test-support"))]
+pub mod mock_player;
+pub mod mpris_server;
+pub mod notifier;
+mod procfs;
+mod sleep_timer;
+mod spotifyd_config;
+mod state_file;
+pub mod supervisor;
+#[cfg(test)]
+mod test_support;
+pub mod types;
 
 use controller::ControllerInner;
-use error::MprisError;
+use history::HistoryInner;
+use ipc::IpcServerInner;
+use media_keys::MediaKeysInner;
+use mpris_server::MprisServerInner;
+use notifier::NotifierInner;
 use napi::bindgen_prelude::*;
 use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
 use napi::JsFunction;
@@ -15,7 +37,14 @@ use supervisor::SupervisorInner;
 use tokio::runtime::Runtime;
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
-use types::{PlaybackState, RepeatMode, SpotifydConfig, SpotifydStartResult, SpotifydStatus};
+use types::{
+    AuthProgress, AuthenticateOptions, AuthenticateResult, FadeCurve, HistoryEntry,
+    HistoryOptions, HistoryRange, HistoryTopEntry, InstallOptions, InstallProgress,
+    InstallResult, MprisExtraMetadata, NotifierOptions, PlaybackState, RepeatMode,
+    SleepTimerOptions, SleepTimerState, SpotifydConfig, SpotifydDetection,
+    SpotifydProcessInfo, SpotifydRestartResult, SpotifydStartResult, SpotifydStatus,
+    SpotifydStopPolicy, SpotifydStopResult,
+};
 
 // Re-export types for TypeScript
 pub use types::{ConnectionStatus, TrackInfo};
@@ -70,11 +99,15 @@ pub struct MprisController {
 
 #[napi]
 impl MprisController {
+    /// @param busAddress - D-Bus address to use instead of the session bus
     #[napi(constructor)]
-    pub fn new() -> Result<Self> {
+    pub fn new(bus_address: Option<String>) -> Result<Self> {
         Lazy::force(&INIT_TRACING);
 
-        let inner = RUNTIME.block_on(async { ControllerInner::new().await })?;
+        let mut inner = RUNTIME.block_on(async { ControllerInner::new().await })?;
+        if let Some(address) = bus_address {
+            inner = inner.with_bus_address(address);
+        }
 
         Ok(Self {
             inner: Arc::new(inner),
@@ -147,6 +180,58 @@ impl MprisController {
         Ok(())
     }
 
+    /// Change volume by `delta` relative to the player's live volume.
+    /// Returns the new volume.
+    #[napi]
+    pub async fn adjust_volume(&self, delta: f64) -> Result<f64> {
+        let inner = self.inner.clone();
+        let volume = RUNTIME
+            .spawn(async move { inner.adjust_volume(delta).await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(volume)
+    }
+
+    /// Fade volume to `target` over `durationMs`. Resolves to false if
+    /// another volume change cut it short.
+    #[napi]
+    pub async fn fade_to(
+        &self,
+        target: f64,
+        duration_ms: u32,
+        curve: Option<FadeCurve>,
+    ) -> Result<bool> {
+        let inner = self.inner.clone();
+        let curve = curve.unwrap_or_default();
+        let finished = RUNTIME
+            .spawn(async move { inner.fade_to(target, duration_ms, curve).await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(finished)
+    }
+
+    /// Mute, remembering the volume. Returns false if already muted.
+    #[napi]
+    pub async fn mute(&self) -> Result<bool> {
+        let inner = self.inner.clone();
+        let muted = RUNTIME
+            .spawn(async move { inner.mute().await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(muted)
+    }
+
+    /// Restore the volume from before mute. Returns false if not muted.
+    #[napi]
+    pub async fn unmute(&self) -> Result<bool> {
+        let inner = self.inner.clone();
+        let unmuted = RUNTIME
+            .spawn(async move { inner.unmute().await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(unmuted)
+    }
+
     /// Set shuffle mode
     #[napi]
     pub async fn set_shuffle(&self, shuffle: bool) -> Result<()> {
@@ -169,6 +254,28 @@ impl MprisController {
         Ok(())
     }
 
+    /// Pause later: after a duration, at the end of the track or album, or
+    /// after a number of tracks. Replaces any running timer.
+    #[napi]
+    pub async fn start_sleep_timer(&self, options: SleepTimerOptions) -> Result<SleepTimerState> {
+        let inner = self.inner.clone();
+        let state = RUNTIME
+            .spawn(async move { inner.start_sleep_timer(options).await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(state)
+    }
+
+    /// Cancel the sleep timer. Returns false if none was running.
+    #[napi]
+    pub async fn cancel_sleep_timer(&self) -> Result<bool> {
+        let inner = self.inner.clone();
+        RUNTIME
+            .spawn(async move { inner.cancel_sleep_timer().await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
     /// Get current playback state (synchronous)
     #[napi]
     pub fn get_state(&self) -> PlaybackState {
@@ -243,17 +350,45 @@ impl SpotifydSupervisor {
         Ok(())
     }
 
-    /// Stop spotifyd gracefully
-    /// @param force - If true, kill any spotifyd process. If false, only kill if we spawned it.
+    /// Stop spotifyd: pause, MPRIS Quit if supported, SIGTERM, then SIGKILL.
+    /// @param force - If true, also stop an adopted spotifyd, or an untracked one the kill
+    ///   policy allows. If false, only stop one we spawned.
+    /// @param policy - Overrides the configured stop policy
     #[napi]
-    pub async fn stop(&self, force: Option<bool>) -> Result<()> {
+    pub async fn stop(
+        &self,
+        force: Option<bool>,
+        policy: Option<SpotifydStopPolicy>,
+    ) -> Result<SpotifydStopResult> {
         let inner = self.inner.clone();
         let force = force.unwrap_or(false);
-        RUNTIME
-            .spawn(async move { inner.stop(force).await })
+        let result = RUNTIME
+            .spawn(async move { inner.stop(force, policy).await })
             .await
             .map_err(|e| Error::from_reason(e.to_string()))??;
-        Ok(())
+        Ok(result)
+    }
+
+    /// Stop and start spotifyd under one lock, optionally with new
+    /// configuration. Rolls back to the previous configuration if the new one
+    /// fails to come up, and reconnects the linked MprisController.
+    #[napi]
+    pub async fn restart(
+        &self,
+        new_config: Option<SpotifydConfig>,
+    ) -> Result<SpotifydRestartResult> {
+        let inner = self.inner.clone();
+        let result = RUNTIME
+            .spawn(async move { inner.restart(new_config).await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(result)
+    }
+
+    /// Reconnect `controller` to spotifyd after every restart()
+    #[napi]
+    pub fn link_controller(&self, controller: &MprisController) {
+        self.inner.link_controller(controller.inner.clone());
     }
 
     /// Get current spotifyd status (synchronous)
@@ -262,6 +397,99 @@ impl SpotifydSupervisor {
         self.inner.get_status()
     }
 
+    /// Log in to Spotify via `spotifyd authenticate` (OAuth in the browser).
+    /// Progress events: started, url-found, waiting, credentials-written.
+    #[napi(
+        ts_args_type = "options?: AuthenticateOptions, onProgress?: (progress: AuthProgress) => void"
+    )]
+    pub async fn authenticate(
+        &self,
+        options: Option<AuthenticateOptions>,
+        on_progress: Option<ThreadsafeFunction<AuthProgress, ErrorStrategy::Fatal>>,
+    ) -> Result<AuthenticateResult> {
+        let inner = self.inner.clone();
+        let result = RUNTIME
+            .spawn(async move {
+                inner
+                    .authenticate(options.unwrap_or_default(), |progress| {
+                        if let Some(ref tsfn) = on_progress {
+                            tsfn.call(progress, ThreadsafeFunctionCallMode::NonBlocking);
+                        }
+                    })
+                    .await
+            })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(result)
+    }
+
+    /// Cancel a running authenticate(); returns false if none is running
+    #[napi]
+    pub fn cancel_authenticate(&self) -> bool {
+        self.inner.cancel_authenticate()
+    }
+
+    /// Probe every spotifyd binary candidate (config, SPOTIFY_TUI_SPOTIFYD_PATH,
+    /// ~/.spotify-tui/bin, PATH) for version and feature support
+    #[napi]
+    pub async fn detect_spotifyd(&self) -> Result<SpotifydDetection> {
+        let inner = self.inner.clone();
+        let detection = RUNTIME
+            .spawn(async move { inner.detect_spotifyd().await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(detection)
+    }
+
+    /// Install spotifyd into ~/.spotify-tui/bin from a URL, a local archive or
+    /// the pinned release, verifying its SHA-256. The replaced binary is kept
+    /// for rollbackInstall().
+    #[napi(
+        ts_args_type = "options?: InstallOptions, onProgress?: (progress: InstallProgress) => void"
+    )]
+    pub async fn install(
+        &self,
+        options: Option<InstallOptions>,
+        on_progress: Option<ThreadsafeFunction<InstallProgress, ErrorStrategy::Fatal>>,
+    ) -> Result<InstallResult> {
+        let inner = self.inner.clone();
+        let result = RUNTIME
+            .spawn(async move {
+                inner
+                    .install(options.unwrap_or_default(), move |progress| {
+                        if let Some(ref tsfn) = on_progress {
+                            tsfn.call(progress, ThreadsafeFunctionCallMode::NonBlocking);
+                        }
+                    })
+                    .await
+            })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(result)
+    }
+
+    /// Restore the spotifyd binary replaced by the last install
+    #[napi]
+    pub async fn rollback_install(&self, install_dir: Option<String>) -> Result<InstallResult> {
+        let inner = self.inner.clone();
+        let result = RUNTIME
+            .spawn(async move { inner.rollback_install(install_dir.as_deref()).await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(result)
+    }
+
+    /// Re-check whether spotifyd is logged in and return the updated status
+    #[napi]
+    pub async fn check_auth(&self) -> Result<SpotifydStatus> {
+        let inner = self.inner.clone();
+        let status = RUNTIME
+            .spawn(async move { inner.refresh_auth().await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(status)
+    }
+
     /// Check if spotifyd is running (process alive)
     #[napi]
     pub async fn is_running(&self) -> Result<bool> {
@@ -295,6 +523,12 @@ impl SpotifydSupervisor {
         Ok(pid)
     }
 
+    /// List spotifyd processes owned by the current user (scanned from /proc)
+    #[napi]
+    pub fn list_spotifyd_processes(&self) -> Vec<SpotifydProcessInfo> {
+        self.inner.list_spotifyd_processes()
+    }
+
     /// Subscribe to status changes
     #[napi(ts_args_type = "callback: (status: SpotifydStatus) => void")]
     pub fn on_status_change(&self, callback: JsFunction) -> Result<()> {
@@ -316,6 +550,286 @@ impl SpotifydSupervisor {
     /// Legacy check_health method (alias for is_healthy)
     #[napi]
     pub async fn check_health(&self) -> Result<bool> {
-        self.is_healthy().await
+        let inner = self.inner.clone();
+        let healthy = RUNTIME
+            .spawn(async move { inner.check_health().await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(healthy)
+    }
+}
+
+/// JSON-RPC control socket for status bar widgets and scripts
+#[napi]
+pub struct IpcServer {
+    inner: Arc<IpcServerInner>,
+}
+
+#[napi]
+impl IpcServer {
+    /// @param supervisor - Also expose spotifyd management as `spotifyd.*` methods
+    #[napi(constructor)]
+    pub fn new(controller: &MprisController, supervisor: Option<&SpotifydSupervisor>) -> Self {
+        Lazy::force(&INIT_TRACING);
+
+        Self {
+            inner: Arc::new(IpcServerInner::new(
+                controller.inner.clone(),
+                supervisor.map(|s| s.inner.clone()),
+            )),
+        }
+    }
+
+    /// Start listening. Returns the socket path.
+    /// @param path - Defaults to $XDG_RUNTIME_DIR/spotify-tui.sock
+    #[napi]
+    pub async fn start(&self, path: Option<String>) -> Result<String> {
+        let inner = self.inner.clone();
+        let path = RUNTIME
+            .spawn(async move { inner.start(path.map(Into::into)).await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(path.to_string_lossy().into_owned())
+    }
+
+    /// Stop listening and remove the socket. Returns false if not running.
+    #[napi]
+    pub fn stop(&self) -> bool {
+        self.inner.stop()
+    }
+
+    /// Socket path while running
+    #[napi(getter)]
+    pub fn path(&self) -> Option<String> {
+        self.inner.path().map(|p| p.to_string_lossy().into_owned())
+    }
+}
+
+/// The TUI's own MPRIS player (`org.mpris.MediaPlayer2.spotify_tui`) for
+/// desktop widgets and playerctl
+#[napi]
+pub struct MprisServer {
+    inner: Arc<MprisServerInner>,
+}
+
+#[napi]
+impl MprisServer {
+    #[napi(constructor)]
+    pub fn new(controller: &MprisController) -> Self {
+        Lazy::force(&INIT_TRACING);
+
+        Self {
+            inner: Arc::new(MprisServerInner::new(controller.inner.clone())),
+        }
+    }
+
+    /// Publish the player. Returns the bus name claimed.
+    #[napi]
+    pub async fn start(&self) -> Result<String> {
+        let inner = self.inner.clone();
+        let name = RUNTIME
+            .spawn(async move { inner.start().await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(name)
+    }
+
+    /// Withdraw the player. Returns false if not running.
+    #[napi]
+    pub async fn stop(&self) -> Result<bool> {
+        let inner = self.inner.clone();
+        RUNTIME
+            .spawn(async move { inner.stop().await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Layer album art, lyrics etc. over spotifyd's metadata
+    #[napi]
+    pub async fn set_extra_metadata(&self, extra: MprisExtraMetadata) -> Result<()> {
+        let inner = self.inner.clone();
+        RUNTIME
+            .spawn(async move { inner.set_extra_metadata(extra).await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(())
+    }
+}
+
+/// "Now playing" desktop notifications on track change
+#[napi]
+pub struct Notifier {
+    inner: Arc<NotifierInner>,
+}
+
+#[napi]
+impl Notifier {
+    #[napi(constructor)]
+    pub fn new(controller: &MprisController, options: Option<NotifierOptions>) -> Self {
+        Lazy::force(&INIT_TRACING);
+
+        Self {
+            inner: Arc::new(NotifierInner::new(
+                controller.inner.clone(),
+                options.unwrap_or_default(),
+            )),
+        }
+    }
+
+    /// Start notifying on track changes
+    #[napi]
+    pub async fn start(&self) -> Result<()> {
+        let inner = self.inner.clone();
+        RUNTIME
+            .spawn(async move { inner.start().await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(())
+    }
+
+    /// Stop notifying. Returns false if not running.
+    #[napi]
+    pub fn stop(&self) -> bool {
+        self.inner.stop()
+    }
+
+    #[napi]
+    pub fn set_do_not_disturb(&self, enabled: bool) {
+        self.inner.set_do_not_disturb(enabled);
+    }
+
+    #[napi(getter)]
+    pub fn do_not_disturb(&self) -> bool {
+        self.inner.do_not_disturb()
+    }
+}
+
+/// Listening history, recorded to ~/.spotify-tui/history.jsonl
+#[napi]
+pub struct HistoryRecorder {
+    inner: Arc<HistoryInner>,
+}
+
+#[napi]
+impl HistoryRecorder {
+    #[napi(constructor)]
+    pub fn new(controller: &MprisController, options: Option<HistoryOptions>) -> Self {
+        Lazy::force(&INIT_TRACING);
+
+        Self {
+            inner: Arc::new(HistoryInner::new(
+                controller.inner.clone(),
+                options.unwrap_or_default(),
+            )),
+        }
+    }
+
+    /// Start recording plays. Fails without a history path (no HOME).
+    #[napi]
+    pub async fn start(&self) -> Result<()> {
+        let inner = self.inner.clone();
+        RUNTIME
+            .spawn(async move { inner.start().await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(())
+    }
+
+    /// Stop recording, saving the current track if it counts as a play.
+    /// Returns false if not running.
+    #[napi]
+    pub fn stop(&self) -> bool {
+        self.inner.stop()
+    }
+
+    /// Plays within `range`, newest first
+    #[napi]
+    pub async fn get_history(&self, range: HistoryRange, limit: u32) -> Result<Vec<HistoryEntry>> {
+        let inner = self.inner.clone();
+        let entries = RUNTIME
+            .spawn_blocking(move || inner.history(range, limit as usize))
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(entries)
+    }
+
+    /// Most played tracks within `range`
+    #[napi]
+    pub async fn get_top_tracks(
+        &self,
+        range: HistoryRange,
+        limit: u32,
+    ) -> Result<Vec<HistoryTopEntry>> {
+        let inner = self.inner.clone();
+        let tracks = RUNTIME
+            .spawn_blocking(move || inner.top_tracks(range, limit as usize))
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(tracks)
+    }
+
+    /// Most played artists within `range`
+    #[napi]
+    pub async fn get_top_artists(
+        &self,
+        range: HistoryRange,
+        limit: u32,
+    ) -> Result<Vec<HistoryTopEntry>> {
+        let inner = self.inner.clone();
+        let artists = RUNTIME
+            .spawn_blocking(move || inner.top_artists(range, limit as usize))
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(artists)
+    }
+}
+
+/// Media keys grabbed from gnome-settings-daemon
+#[napi]
+pub struct MediaKeys {
+    inner: Arc<MediaKeysInner>,
+}
+
+#[napi]
+impl MediaKeys {
+    #[napi(constructor)]
+    pub fn new(controller: &MprisController) -> Self {
+        Lazy::force(&INIT_TRACING);
+
+        Self {
+            inner: Arc::new(MediaKeysInner::new(controller.inner.clone())),
+        }
+    }
+
+    /// Grab the keys. Fails without gnome-settings-daemon.
+    #[napi]
+    pub async fn start(&self) -> Result<()> {
+        let inner = self.inner.clone();
+        RUNTIME
+            .spawn(async move { inner.start().await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(())
+    }
+
+    /// Grab again, when the TUI regains focus. Returns false if not started.
+    #[napi]
+    pub async fn regrab(&self) -> Result<bool> {
+        let inner = self.inner.clone();
+        let grabbed = RUNTIME
+            .spawn(async move { inner.regrab().await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))??;
+        Ok(grabbed)
+    }
+
+    /// Release the keys. Returns false if not started.
+    #[napi]
+    pub async fn stop(&self) -> Result<bool> {
+        let inner = self.inner.clone();
+        RUNTIME
+            .spawn(async move { inner.stop().await })
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))
     }
 }
@@ -0,0 +1,354 @@
+//! Media keys via gnome-settings-daemon: grab them with
+//! `GrabMediaPlayerKeys` and turn `MediaPlayerKeyPressed` into controller
+//! commands, so the keys work even when the desktop doesn't route them to
+//! spotifyd's MPRIS player.
+//!
+//! gsd sends keys to the application that grabbed most recently, so the TUI
+//! re-grabs when it regains focus (`regrab`), and after gsd restarts.
+
+use crate::controller::ControllerInner;
+use crate::bus::open_bus;
+use crate::error::MprisError;
+use crate::types::RepeatMode;
+use futures::StreamExt;
+use std::sync::Arc;
+use tokio::task::AbortHandle;
+use tracing::{debug, info, warn};
+use zbus::proxy;
+
+const APP_NAME: &str = "spotify-tui";
+/// Seek step for the Rewind / FastForward keys
+const SEEK_STEP_MS: i64 = 10_000;
+
+/// Bus names gsd has used for MediaKeys, newest first
+const SERVICES: [&str; 2] = ["org.gnome.SettingsDaemon.MediaKeys", "org.gnome.SettingsDaemon"];
+
+#[proxy(
+    interface = "org.gnome.SettingsDaemon.MediaKeys",
+    default_path = "/org/gnome/SettingsDaemon/MediaKeys"
+)]
+trait MediaKeys {
+    fn grab_media_player_keys(&self, application: &str, time: u32) -> zbus::Result<()>;
+    fn release_media_player_keys(&self, application: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn media_player_key_pressed(&self, application: &str, key: &str) -> zbus::Result<()>;
+}
+
+/// What a media key does
+#[derive(Debug, PartialEq)]
+enum KeyAction {
+    PlayPause,
+    Pause,
+    Next,
+    Previous,
+    Seek(i64),
+    CycleRepeat,
+    ToggleShuffle,
+}
+
+fn key_action(key: &str) -> Option<KeyAction> {
+    Some(match key {
+        // gsd sends "Play" for the play/pause key
+        "Play" => KeyAction::PlayPause,
+        "Pause" | "Stop" => KeyAction::Pause,
+        "Next" => KeyAction::Next,
+        "Previous" => KeyAction::Previous,
+        "Rewind" => KeyAction::Seek(-SEEK_STEP_MS),
+        "FastForward" => KeyAction::Seek(SEEK_STEP_MS),
+        "Repeat" => KeyAction::CycleRepeat,
+        "Shuffle" => KeyAction::ToggleShuffle,
+        _ => return None,
+    })
+}
+
+async fn dispatch(controller: &ControllerInner, action: KeyAction) -> Result<(), MprisError> {
+    match action {
+        KeyAction::PlayPause => controller.play_pause().await.map(|_| ()),
+        KeyAction::Pause => controller.pause().await,
+        KeyAction::Next => controller.next().await,
+        KeyAction::Previous => controller.previous().await,
+        KeyAction::Seek(offset_ms) => controller.seek(offset_ms).await,
+        KeyAction::CycleRepeat => {
+            let repeat = match controller.state().await.repeat {
+                RepeatMode::None => RepeatMode::Playlist,
+                RepeatMode::Playlist => RepeatMode::Track,
+                RepeatMode::Track => RepeatMode::None,
+            };
+            controller.set_repeat(repeat).await
+        }
+        KeyAction::ToggleShuffle => {
+            let shuffle = controller.state().await.shuffle;
+            controller.set_shuffle(!shuffle).await
+        }
+    }
+}
+
+/// Run key presses meant for us until gsd goes away
+async fn listen(
+    proxy: MediaKeysProxy<'static>,
+    mut presses: MediaPlayerKeyPressedStream<'static>,
+    controller: Arc<ControllerInner>,
+) {
+    let mut owners = proxy.inner().receive_owner_changed().await.ok();
+
+    loop {
+        tokio::select! {
+            press = presses.next() => {
+                let Some(press) = press else { break };
+                let Ok(args) = press.args() else { continue };
+                if args.application != APP_NAME {
+                    continue;
+                }
+                let Some(action) = key_action(args.key) else {
+                    debug!("Ignoring media key {:?}", args.key);
+                    continue;
+                };
+                debug!("Media key {:?}", args.key);
+                if let Err(e) = dispatch(&controller, action).await {
+                    warn!("Media key {:?} failed: {}", args.key, e);
+                }
+            }
+            Some(owner) = async { owners.as_mut()?.next().await } => {
+                // gsd restarted and forgot our grab
+                if owner.is_some() {
+                    info!("gnome-settings-daemon restarted, grabbing media keys again");
+                    if let Err(e) = proxy.grab_media_player_keys(APP_NAME, 0).await {
+                        warn!("Could not grab media keys: {}", e);
+                    }
+                }
+            }
+        }
+    }
+    warn!("Media key listener stopped");
+}
+
+struct Running {
+    proxy: MediaKeysProxy<'static>,
+    listener: AbortHandle,
+}
+
+pub struct MediaKeysInner {
+    controller: Arc<ControllerInner>,
+    /// Bus to use instead of the session bus
+    bus_address: Option<String>,
+    running: tokio::sync::Mutex<Option<Running>>,
+}
+
+impl MediaKeysInner {
+    pub fn new(controller: Arc<ControllerInner>) -> Self {
+        Self {
+            controller,
+            bus_address: None,
+            running: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// Use the D-Bus daemon at `address` instead of the session bus
+    pub fn with_bus_address(mut self, address: impl Into<String>) -> Self {
+        self.bus_address = Some(address.into());
+        self
+    }
+
+    /// Grab the media keys and start handling them. Fails if no
+    /// gnome-settings-daemon is running.
+    pub async fn start(&self) -> Result<(), MprisError> {
+        let mut running = self.running.lock().await;
+        if running.is_some() {
+            return Ok(());
+        }
+
+        let conn = open_bus(self.bus_address.as_deref()).await?;
+        let mut last_error = None;
+        for service in SERVICES {
+            let proxy = MediaKeysProxy::builder(&conn).destination(service)?.build().await?;
+            // Subscribe first so no press after the grab is missed
+            let presses = proxy.receive_media_player_key_pressed().await?;
+            match proxy.grab_media_player_keys(APP_NAME, 0).await {
+                Ok(()) => {
+                    info!("Grabbed media keys from {}", service);
+                    let controller = self.controller.clone();
+                    let listener = tokio::spawn(listen(proxy.clone(), presses, controller));
+                    *running = Some(Running {
+                        proxy,
+                        listener: listener.abort_handle(),
+                    });
+                    return Ok(());
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.map_or(MprisError::NotConnected, MprisError::from))
+    }
+
+    /// Grab again so keys come to us, e.g. when the TUI regains focus.
+    /// Returns false if not started.
+    pub async fn regrab(&self) -> Result<bool, MprisError> {
+        let running = self.running.lock().await;
+        let Some(ref running) = *running else {
+            return Ok(false);
+        };
+        running.proxy.grab_media_player_keys(APP_NAME, 0).await?;
+        Ok(true)
+    }
+
+    /// Release the keys and stop listening. Returns false if not started.
+    pub async fn stop(&self) -> bool {
+        let Some(running) = self.running.lock().await.take() else {
+            return false;
+        };
+        running.listener.abort();
+        if let Err(e) = running.proxy.release_media_player_keys(APP_NAME).await {
+            warn!("Could not release media keys: {}", e);
+        }
+        info!("Released media keys");
+        true
+    }
+}
+
+impl Drop for MediaKeysInner {
+    fn drop(&mut self) {
+        if let Some(running) = self.running.get_mut().take() {
+            running.listener.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_player::{connected_controller, DbusDaemon, MockOptions, MockPlayer};
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use zbus::{interface, Connection};
+    use zbus::object_server::SignalContext;
+
+    const PATH: &str = "/org/gnome/SettingsDaemon/MediaKeys";
+
+    /// Stand-in for gsd's MediaKeys, recording grabs and releases
+    #[derive(Default)]
+    struct MockMediaKeys {
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[interface(name = "org.gnome.SettingsDaemon.MediaKeys")]
+    impl MockMediaKeys {
+        fn grab_media_player_keys(&self, application: String, _time: u32) {
+            self.calls.lock().unwrap().push(format!("Grab({})", application));
+        }
+
+        fn release_media_player_keys(&self, application: String) {
+            self.calls.lock().unwrap().push(format!("Release({})", application));
+        }
+
+        #[zbus(signal)]
+        async fn media_player_key_pressed(
+            ctxt: &SignalContext<'_>,
+            application: &str,
+            key: &str,
+        ) -> zbus::Result<()>;
+    }
+
+    async fn mock_gsd(bus: &DbusDaemon) -> (Connection, Arc<Mutex<Vec<String>>>) {
+        let gsd = MockMediaKeys::default();
+        let calls = gsd.calls.clone();
+        let conn = zbus::connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .name(SERVICES[0])
+            .unwrap()
+            .serve_at(PATH, gsd)
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        (conn, calls)
+    }
+
+    async fn press(gsd: &Connection, application: &str, key: &str) {
+        let iface = gsd
+            .object_server()
+            .interface::<_, MockMediaKeys>(PATH)
+            .await
+            .unwrap();
+        MockMediaKeys::media_player_key_pressed(iface.signal_context(), application, key)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn key_presses_drive_the_player() {
+        let bus = DbusDaemon::start();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let (gsd, gsd_calls) = mock_gsd(&bus).await;
+            let controller = connected_controller(&bus).await;
+            let keys = MediaKeysInner::new(controller).with_bus_address(&bus.address);
+
+            keys.start().await.unwrap();
+            press(&gsd, APP_NAME, "Play").await;
+            press(&gsd, "rhythmbox", "Next").await;
+            press(&gsd, APP_NAME, "FastForward").await;
+            press(&gsd, APP_NAME, "Eject").await;
+            press(&gsd, APP_NAME, "Shuffle").await;
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            assert_eq!(player.calls(), ["PlayPause", "Seek(10000000)", "Shuffle=true"]);
+
+            assert!(keys.regrab().await.unwrap());
+            assert!(keys.stop().await);
+            assert!(!keys.stop().await);
+            assert!(!keys.regrab().await.unwrap());
+            assert_eq!(
+                *gsd_calls.lock().unwrap(),
+                ["Grab(spotify-tui)", "Grab(spotify-tui)", "Release(spotify-tui)"]
+            );
+        });
+    }
+
+    #[test]
+    fn grabs_again_after_settings_daemon_restarts() {
+        let bus = DbusDaemon::start();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let (gsd, gsd_calls) = mock_gsd(&bus).await;
+            let controller = connected_controller(&bus).await;
+            let keys = MediaKeysInner::new(controller).with_bus_address(&bus.address);
+            keys.start().await.unwrap();
+            assert_eq!(*gsd_calls.lock().unwrap(), ["Grab(spotify-tui)"]);
+
+            gsd.close().await.unwrap();
+            let (gsd, gsd_calls) = mock_gsd(&bus).await;
+            for _ in 0..50 {
+                if !gsd_calls.lock().unwrap().is_empty() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            assert_eq!(*gsd_calls.lock().unwrap(), ["Grab(spotify-tui)"]);
+
+            // Presses from the new instance still reach the player
+            press(&gsd, APP_NAME, "Next").await;
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            assert_eq!(player.calls(), ["Next"]);
+        });
+    }
+
+    #[test]
+    fn start_fails_without_settings_daemon() {
+        let bus = DbusDaemon::start();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let controller = Arc::new(ControllerInner::new().await.unwrap());
+            let keys = MediaKeysInner::new(controller).with_bus_address(&bus.address);
+            assert!(keys.start().await.is_err());
+            assert!(!keys.regrab().await.unwrap());
+        });
+    }
+
+    #[test]
+    fn keys_map_to_actions() {
+        assert_eq!(key_action("Play"), Some(KeyAction::PlayPause));
+        assert_eq!(key_action("Stop"), Some(KeyAction::Pause));
+        assert_eq!(key_action("Rewind"), Some(KeyAction::Seek(-SEEK_STEP_MS)));
+        assert_eq!(key_action("Repeat"), Some(KeyAction::CycleRepeat));
+        assert_eq!(key_action("Eject"), None);
+    }
+}
@@ -0,0 +1,504 @@
+//! In-process stand-in for spotifyd's D-Bus side, for tests that need a
+//! player without audio or a Spotify account. Serves `org.mpris.MediaPlayer2`,
+//! `org.mpris.MediaPlayer2.Player` and `rs.spotifyd.Controls` on a private
+//! `dbus-daemon --session`, with scriptable state and injectable faults.
+
+use crate::controller::ControllerInner;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use zbus::object_server::SignalContext;
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+use zbus::{interface, Connection};
+
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const CONTROLS_PATH: &str = "/rs/spotifyd/Controls";
+
+// ─────────────────────────────────────────────────────────────
+// Private bus
+// ─────────────────────────────────────────────────────────────
+
+/// A `dbus-daemon --session` of our own, killed on drop
+pub struct DbusDaemon {
+    child: Child,
+    pub address: String,
+}
+
+impl DbusDaemon {
+    /// Panics if dbus-daemon can't be started: D-Bus tests must not pass by
+    /// skipping themselves
+    pub fn start() -> Self {
+        let mut child = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("dbus-daemon is required for the D-Bus tests (install the dbus package)");
+        let mut address = String::new();
+        std::io::BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut address)
+            .expect("dbus-daemon didn't print its address");
+        Self {
+            child,
+            address: address.trim().to_string(),
+        }
+    }
+}
+
+impl Drop for DbusDaemon {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A controller connected to the player already running on `bus`
+pub async fn connected_controller(bus: &DbusDaemon) -> Arc<ControllerInner> {
+    let controller = ControllerInner::new()
+        .await
+        .unwrap()
+        .with_bus_address(bus.address.clone());
+    controller.connect().await.unwrap();
+    Arc::new(controller)
+}
+
+// ─────────────────────────────────────────────────────────────
+// Scriptable state
+// ─────────────────────────────────────────────────────────────
+
+#[derive(Clone, Debug)]
+pub struct MockTrack {
+    pub id: String,
+    pub title: String,
+    pub artists: Vec<String>,
+    pub album: String,
+    pub length_us: i64,
+    pub art_url: Option<String>,
+}
+
+impl MockTrack {
+    pub fn new(id: &str, title: &str, artist: &str) -> Self {
+        Self {
+            id: format!("/org/spotify/track/{}", id),
+            title: title.to_string(),
+            artists: vec![artist.to_string()],
+            album: "Mock Album".to_string(),
+            length_us: 180_000_000,
+            art_url: None,
+        }
+    }
+
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        let owned = |v: Value<'_>| OwnedValue::try_from(v).unwrap();
+        let mut map = HashMap::new();
+        map.insert(
+            "mpris:trackid".to_string(),
+            owned(ObjectPath::try_from(self.id.as_str()).unwrap().into()),
+        );
+        map.insert("mpris:length".to_string(), owned(self.length_us.into()));
+        map.insert("xesam:title".to_string(), owned(self.title.as_str().into()));
+        map.insert("xesam:artist".to_string(), owned(self.artists.clone().into()));
+        map.insert("xesam:album".to_string(), owned(self.album.as_str().into()));
+        if let Some(ref url) = self.art_url {
+            map.insert("mpris:artUrl".to_string(), owned(url.as_str().into()));
+        }
+        map
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MockState {
+    /// "Playing", "Paused" or "Stopped"
+    pub playback_status: String,
+    pub track: Option<MockTrack>,
+    pub position_us: i64,
+    pub volume: f64,
+    pub shuffle: bool,
+    /// "None", "Track" or "Playlist"
+    pub loop_status: String,
+    pub can_quit: bool,
+}
+
+impl Default for MockState {
+    fn default() -> Self {
+        Self {
+            playback_status: "Paused".to_string(),
+            track: Some(MockTrack::new("1", "First Song", "Mock Artist")),
+            position_us: 0,
+            volume: 0.5,
+            shuffle: false,
+            loop_status: "None".to_string(),
+            can_quit: true,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    state: MockState,
+    /// Method and property-setter calls, in order
+    calls: Vec<String>,
+    /// Calls to these names fail with the given message
+    failures: HashMap<String, String>,
+    /// Applied to every call before it's answered
+    delay: Duration,
+}
+
+type SharedRef = Arc<Mutex<Shared>>;
+
+/// Record a call, then apply the delay and any injected failure
+async fn enter(shared: &SharedRef, call: &str) -> zbus::fdo::Result<()> {
+    let (delay, failure) = {
+        let mut shared = shared.lock().unwrap();
+        shared.calls.push(call.to_string());
+        (shared.delay, shared.failures.get(call).cloned())
+    };
+    if !delay.is_zero() {
+        tokio::time::sleep(delay).await;
+    }
+    match failure {
+        Some(message) => Err(zbus::fdo::Error::Failed(message)),
+        None => Ok(()),
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// Interfaces
+// ─────────────────────────────────────────────────────────────
+
+struct Root {
+    shared: SharedRef,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    async fn quit(&self) -> zbus::fdo::Result<()> {
+        enter(&self.shared, "Quit").await
+    }
+
+    async fn raise(&self) -> zbus::fdo::Result<()> {
+        enter(&self.shared, "Raise").await
+    }
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        self.shared.lock().unwrap().state.can_quit
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        "spotifyd".to_string()
+    }
+}
+
+struct Player {
+    shared: SharedRef,
+}
+
+impl Player {
+    fn set_status(&self, status: &str) {
+        self.shared.lock().unwrap().state.playback_status = status.to_string();
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    async fn play(&self, #[zbus(signal_context)] ctxt: SignalContext<'_>) -> zbus::fdo::Result<()> {
+        enter(&self.shared, "Play").await?;
+        self.set_status("Playing");
+        self.playback_status_changed(&ctxt).await?;
+        Ok(())
+    }
+
+    async fn pause(&self, #[zbus(signal_context)] ctxt: SignalContext<'_>) -> zbus::fdo::Result<()> {
+        enter(&self.shared, "Pause").await?;
+        self.set_status("Paused");
+        self.playback_status_changed(&ctxt).await?;
+        Ok(())
+    }
+
+    async fn play_pause(
+        &self,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> zbus::fdo::Result<()> {
+        enter(&self.shared, "PlayPause").await?;
+        let playing = self.shared.lock().unwrap().state.playback_status == "Playing";
+        self.set_status(if playing { "Paused" } else { "Playing" });
+        self.playback_status_changed(&ctxt).await?;
+        Ok(())
+    }
+
+    async fn stop(&self, #[zbus(signal_context)] ctxt: SignalContext<'_>) -> zbus::fdo::Result<()> {
+        enter(&self.shared, "Stop").await?;
+        self.set_status("Stopped");
+        self.playback_status_changed(&ctxt).await?;
+        Ok(())
+    }
+
+    async fn next(&self) -> zbus::fdo::Result<()> {
+        enter(&self.shared, "Next").await
+    }
+
+    async fn previous(&self) -> zbus::fdo::Result<()> {
+        enter(&self.shared, "Previous").await
+    }
+
+    async fn seek(
+        &self,
+        offset: i64,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> zbus::fdo::Result<()> {
+        enter(&self.shared, &format!("Seek({})", offset)).await?;
+        let position = {
+            let mut shared = self.shared.lock().unwrap();
+            shared.state.position_us += offset;
+            shared.state.position_us
+        };
+        Self::seeked(&ctxt, position).await?;
+        Ok(())
+    }
+
+    async fn set_position(
+        &self,
+        track_id: ObjectPath<'_>,
+        position: i64,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> zbus::fdo::Result<()> {
+        enter(&self.shared, &format!("SetPosition({}, {})", track_id, position)).await?;
+        self.shared.lock().unwrap().state.position_us = position;
+        Self::seeked(&ctxt, position).await?;
+        Ok(())
+    }
+
+    #[zbus(signal)]
+    async fn seeked(ctxt: &SignalContext<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        self.shared.lock().unwrap().state.playback_status.clone()
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        let shared = self.shared.lock().unwrap();
+        shared
+            .state
+            .track
+            .as_ref()
+            .map(MockTrack::metadata)
+            .unwrap_or_default()
+    }
+
+    #[zbus(property)]
+    fn position(&self) -> i64 {
+        self.shared.lock().unwrap().state.position_us
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.shared.lock().unwrap().state.volume
+    }
+
+    #[zbus(property)]
+    async fn set_volume(&mut self, volume: f64) -> zbus::fdo::Result<()> {
+        enter(&self.shared, &format!("Volume={}", volume)).await?;
+        self.shared.lock().unwrap().state.volume = volume;
+        Ok(())
+    }
+
+    #[zbus(property)]
+    fn shuffle(&self) -> bool {
+        self.shared.lock().unwrap().state.shuffle
+    }
+
+    #[zbus(property)]
+    async fn set_shuffle(&mut self, shuffle: bool) -> zbus::fdo::Result<()> {
+        enter(&self.shared, &format!("Shuffle={}", shuffle)).await?;
+        self.shared.lock().unwrap().state.shuffle = shuffle;
+        Ok(())
+    }
+
+    #[zbus(property)]
+    fn loop_status(&self) -> String {
+        self.shared.lock().unwrap().state.loop_status.clone()
+    }
+
+    #[zbus(property)]
+    async fn set_loop_status(&mut self, status: String) -> zbus::fdo::Result<()> {
+        enter(&self.shared, &format!("LoopStatus={}", status)).await?;
+        self.shared.lock().unwrap().state.loop_status = status;
+        Ok(())
+    }
+}
+
+struct Controls {
+    shared: SharedRef,
+    /// MPRIS name to claim on TransferPlayback, if not claimed at startup
+    pending_mpris_name: Option<String>,
+}
+
+#[interface(name = "rs.spotifyd.Controls")]
+impl Controls {
+    async fn transfer_playback(
+        &mut self,
+        #[zbus(connection)] conn: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        enter(&self.shared, "TransferPlayback").await?;
+        // Like spotifyd 0.4, only expose MPRIS once this device is active
+        if let Some(name) = self.pending_mpris_name.take() {
+            conn.request_name(name).await?;
+        }
+        Ok(())
+    }
+
+    async fn volume_up(&self) -> zbus::fdo::Result<()> {
+        enter(&self.shared, "VolumeUp").await
+    }
+
+    async fn volume_down(&self) -> zbus::fdo::Result<()> {
+        enter(&self.shared, "VolumeDown").await
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// Handle
+// ─────────────────────────────────────────────────────────────
+
+pub struct MockOptions {
+    /// Suffix for `rs.spotifyd.instance<N>` and `org.mpris.MediaPlayer2.spotifyd.instance<N>`
+    pub instance: u32,
+    /// Claim the MPRIS name at startup; otherwise only after TransferPlayback
+    pub mpris_at_start: bool,
+    pub state: MockState,
+}
+
+impl Default for MockOptions {
+    fn default() -> Self {
+        Self {
+            instance: 1,
+            mpris_at_start: true,
+            state: MockState::default(),
+        }
+    }
+}
+
+/// A running mock spotifyd. Dropping it (or `vanish`) disconnects it from
+/// the bus, like spotifyd exiting.
+pub struct MockPlayer {
+    conn: Connection,
+    shared: SharedRef,
+}
+
+impl MockPlayer {
+    pub async fn start(bus: &DbusDaemon, options: MockOptions) -> Self {
+        let shared = Arc::new(Mutex::new(Shared {
+            state: options.state,
+            ..Default::default()
+        }));
+        let mpris_name = format!("org.mpris.MediaPlayer2.spotifyd.instance{}", options.instance);
+        let controls_name = format!("rs.spotifyd.instance{}", options.instance);
+
+        let mut builder = zbus::connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .name(controls_name)
+            .unwrap()
+            .serve_at(MPRIS_PATH, Root { shared: shared.clone() })
+            .unwrap()
+            .serve_at(MPRIS_PATH, Player { shared: shared.clone() })
+            .unwrap()
+            .serve_at(
+                CONTROLS_PATH,
+                Controls {
+                    shared: shared.clone(),
+                    pending_mpris_name: (!options.mpris_at_start).then(|| mpris_name.clone()),
+                },
+            )
+            .unwrap();
+        if options.mpris_at_start {
+            builder = builder.name(mpris_name).unwrap();
+        }
+
+        Self {
+            conn: builder.build().await.unwrap(),
+            shared,
+        }
+    }
+
+    /// Calls received so far, e.g. "PlayPause", "Seek(10000000)", "Volume=0.6"
+    pub fn calls(&self) -> Vec<String> {
+        self.shared.lock().unwrap().calls.clone()
+    }
+
+    pub fn state(&self) -> MockState {
+        self.shared.lock().unwrap().state.clone()
+    }
+
+    /// Make calls to `call` (a method name or e.g. "Volume=0.6") fail
+    pub fn fail(&self, call: &str, message: &str) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.failures.insert(call.to_string(), message.to_string());
+    }
+
+    pub fn clear_faults(&self) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.failures.clear();
+        shared.delay = Duration::ZERO;
+    }
+
+    /// Hold every reply back by `delay`
+    pub fn set_delay(&self, delay: Duration) {
+        self.shared.lock().unwrap().delay = delay;
+    }
+
+    async fn player_context(&self) -> zbus::InterfaceRef<Player> {
+        self.conn
+            .object_server()
+            .interface::<_, Player>(MPRIS_PATH)
+            .await
+            .unwrap()
+    }
+
+    /// Change playback status and emit PropertiesChanged, like a phone
+    /// pressing play via Spotify Connect
+    pub async fn set_playback_status(&self, status: &str) {
+        let iface = self.player_context().await;
+        iface.get().await.set_status(status);
+        iface
+            .get()
+            .await
+            .playback_status_changed(iface.signal_context())
+            .await
+            .unwrap();
+    }
+
+    /// Change the volume without going through D-Bus, like a phone turning
+    /// it down via Spotify Connect
+    pub fn set_volume(&self, volume: f64) {
+        self.shared.lock().unwrap().state.volume = volume;
+    }
+
+    /// Switch tracks and emit PropertiesChanged for Metadata
+    pub async fn set_track(&self, track: Option<MockTrack>) {
+        self.shared.lock().unwrap().state.track = track;
+        let iface = self.player_context().await;
+        iface
+            .get()
+            .await
+            .metadata_changed(iface.signal_context())
+            .await
+            .unwrap();
+    }
+
+    /// Disconnect from the bus, releasing all names
+    pub async fn vanish(self) {
+        let _ = self.conn.close().await;
+    }
+}
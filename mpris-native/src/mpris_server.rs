@@ -0,0 +1,636 @@
+//! Our own MPRIS player, `org.mpris.MediaPlayer2.spotify_tui`, so desktop
+//! widgets and `playerctl` see the TUI's state (with the metadata it gets
+//! from the Web API) instead of spotifyd's bare player.
+//!
+//! Properties are read from `ControllerInner`'s `PlaybackState` and commands
+//! are forwarded through it, so spotifyd stays the only thing making sound.
+//! The TUI can layer extra metadata on top with `set_extra_metadata`.
+
+use crate::controller::{ControllerInner, OWN_PLAYER_NAME};
+use crate::bus::open_bus;
+use crate::error::MprisError;
+use crate::types::{MprisExtraMetadata, PlaybackState, RepeatMode};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio::task::AbortHandle;
+use tracing::{info, warn};
+use zbus::object_server::{InterfaceRef, SignalContext};
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+use zbus::{interface, Connection};
+
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+/// Track id for "nothing playing", from the MPRIS spec
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+fn failed(err: MprisError) -> zbus::fdo::Error {
+    zbus::fdo::Error::Failed(err.to_string())
+}
+
+fn owned(value: Value<'_>) -> OwnedValue {
+    // Only fails for file descriptors, which metadata never contains
+    OwnedValue::try_from(value).expect("metadata value without fds")
+}
+
+/// MPRIS Metadata for `state`, with `extra` applied if it's for this track
+fn metadata(state: &PlaybackState, extra: &MprisExtraMetadata) -> HashMap<String, OwnedValue> {
+    let mut map = HashMap::new();
+    let Some(ref track) = state.track else {
+        map.insert(
+            "mpris:trackid".to_string(),
+            owned(ObjectPath::from_static_str_unchecked(NO_TRACK).into()),
+        );
+        return map;
+    };
+
+    let track_id = ObjectPath::try_from(track.uri.as_str())
+        .unwrap_or_else(|_| ObjectPath::from_static_str_unchecked(NO_TRACK));
+    map.insert("mpris:trackid".to_string(), owned(track_id.into()));
+    map.insert("mpris:length".to_string(), owned((state.duration_ms * 1000).into()));
+    map.insert("xesam:title".to_string(), owned(track.title.as_str().into()));
+    map.insert("xesam:artist".to_string(), owned(vec![track.artist.as_str()].into()));
+    map.insert("xesam:album".to_string(), owned(track.album.as_str().into()));
+
+    let applies = extra.track_uri.as_ref().is_none_or(|uri| *uri == track.uri);
+    let art_url = match extra.art_path {
+        Some(ref path) if applies => Some(format!("file://{}", path)),
+        _ => track.art_url.clone(),
+    };
+    if let Some(url) = art_url {
+        map.insert("mpris:artUrl".to_string(), owned(url.into()));
+    }
+    if applies {
+        if let Some(ref line) = extra.lyrics_line {
+            map.insert("xesam:asText".to_string(), owned(line.as_str().into()));
+        }
+        if let Some(ref url) = extra.url {
+            map.insert("xesam:url".to_string(), owned(url.as_str().into()));
+        }
+    }
+    map
+}
+
+fn loop_status(repeat: &RepeatMode) -> &'static str {
+    match repeat {
+        RepeatMode::None => "None",
+        RepeatMode::Playlist => "Playlist",
+        RepeatMode::Track => "Track",
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// Interfaces
+// ─────────────────────────────────────────────────────────────
+
+struct Root;
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    /// The TUI lives in a terminal we can't raise
+    fn raise(&self) {}
+
+    /// Quitting is up to the user, not the desktop
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        "spotify-tui".to_string()
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+struct Player {
+    controller: Arc<ControllerInner>,
+    extra: Arc<Mutex<MprisExtraMetadata>>,
+}
+
+impl Player {
+    /// Tell clients where playback is now, after a jump
+    async fn announce_position(&self, ctxt: &SignalContext<'_>) -> zbus::fdo::Result<()> {
+        let position_ms = self.controller.position_ms().await.map_err(failed)?;
+        Self::seeked(ctxt, position_ms * 1000).await?;
+        Ok(())
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    async fn play(&self) -> zbus::fdo::Result<()> {
+        self.controller.play().await.map_err(failed)
+    }
+
+    async fn pause(&self) -> zbus::fdo::Result<()> {
+        self.controller.pause().await.map_err(failed)
+    }
+
+    async fn play_pause(&self) -> zbus::fdo::Result<()> {
+        self.controller.play_pause().await.map(|_| ()).map_err(failed)
+    }
+
+    /// spotifyd can't really stop, so this pauses
+    async fn stop(&self) -> zbus::fdo::Result<()> {
+        self.controller.pause().await.map_err(failed)
+    }
+
+    async fn next(&self) -> zbus::fdo::Result<()> {
+        self.controller.next().await.map_err(failed)
+    }
+
+    async fn previous(&self) -> zbus::fdo::Result<()> {
+        self.controller.previous().await.map_err(failed)
+    }
+
+    async fn seek(
+        &self,
+        offset: i64,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> zbus::fdo::Result<()> {
+        self.controller.seek(offset / 1000).await.map_err(failed)?;
+        self.announce_position(&ctxt).await
+    }
+
+    async fn set_position(
+        &self,
+        track_id: ObjectPath<'_>,
+        position: i64,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> zbus::fdo::Result<()> {
+        // Per the spec, requests for a track that's no longer current are ignored
+        let state = self.controller.state().await;
+        if state.track.is_none_or(|t| t.uri != track_id.as_str()) {
+            return Ok(());
+        }
+        self.controller.set_position(position / 1000).await.map_err(failed)?;
+        self.announce_position(&ctxt).await
+    }
+
+    fn open_uri(&self, _uri: String) -> zbus::fdo::Result<()> {
+        Err(zbus::fdo::Error::NotSupported("OpenUri is not supported".to_string()))
+    }
+
+    #[zbus(signal)]
+    async fn seeked(ctxt: &SignalContext<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    async fn playback_status(&self) -> String {
+        let state = self.controller.state().await;
+        match (state.track.is_some(), state.is_playing) {
+            (false, _) => "Stopped",
+            (true, true) => "Playing",
+            (true, false) => "Paused",
+        }
+        .to_string()
+    }
+
+    #[zbus(property)]
+    async fn loop_status(&self) -> String {
+        loop_status(&self.controller.state().await.repeat).to_string()
+    }
+
+    #[zbus(property)]
+    async fn set_loop_status(&mut self, status: String) -> zbus::fdo::Result<()> {
+        let repeat = match status.as_str() {
+            "None" => RepeatMode::None,
+            "Playlist" => RepeatMode::Playlist,
+            "Track" => RepeatMode::Track,
+            _ => {
+                let message = format!("unknown LoopStatus {:?}", status);
+                return Err(zbus::fdo::Error::InvalidArgs(message));
+            }
+        };
+        self.controller.set_repeat(repeat).await.map_err(failed)
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    async fn shuffle(&self) -> bool {
+        self.controller.state().await.shuffle
+    }
+
+    #[zbus(property)]
+    async fn set_shuffle(&mut self, shuffle: bool) -> zbus::fdo::Result<()> {
+        self.controller.set_shuffle(shuffle).await.map_err(failed)
+    }
+
+    #[zbus(property)]
+    async fn metadata(&self) -> HashMap<String, OwnedValue> {
+        let state = self.controller.state().await;
+        let extra = self.extra.lock().unwrap().clone();
+        metadata(&state, &extra)
+    }
+
+    #[zbus(property)]
+    async fn volume(&self) -> f64 {
+        self.controller.state().await.volume
+    }
+
+    #[zbus(property)]
+    async fn set_volume(&mut self, volume: f64) -> zbus::fdo::Result<()> {
+        self.controller.set_volume(volume.clamp(0.0, 1.0)).await.map_err(failed)
+    }
+
+    /// Asked of spotifyd each time, since positions aren't signalled
+    #[zbus(property(emits_changed_signal = "false"))]
+    async fn position(&self) -> i64 {
+        match self.controller.position_ms().await {
+            Ok(position_ms) => position_ms * 1000,
+            Err(_) => self.controller.state().await.position_ms * 1000,
+        }
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        true
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+/// Emit PropertiesChanged for whatever differs between `old` and `new`
+async fn announce_changes(
+    iface: &InterfaceRef<Player>,
+    old: &PlaybackState,
+    new: &PlaybackState,
+) -> zbus::Result<()> {
+    let ctxt = iface.signal_context();
+    let player = iface.get().await;
+    if old.is_playing != new.is_playing || old.track.is_some() != new.track.is_some() {
+        player.playback_status_changed(ctxt).await?;
+    }
+    if old.track != new.track || old.duration_ms != new.duration_ms {
+        player.metadata_changed(ctxt).await?;
+    }
+    if old.volume != new.volume {
+        player.volume_changed(ctxt).await?;
+    }
+    if old.shuffle != new.shuffle {
+        player.shuffle_changed(ctxt).await?;
+    }
+    if old.repeat != new.repeat {
+        player.loop_status_changed(ctxt).await?;
+    }
+    Ok(())
+}
+
+/// Mirror controller state changes as PropertiesChanged signals
+async fn follow_state(
+    iface: InterfaceRef<Player>,
+    controller: Arc<ControllerInner>,
+    mut rx: broadcast::Receiver<PlaybackState>,
+    mut last: PlaybackState,
+) {
+    loop {
+        let state = match rx.recv().await {
+            Ok(state) => state,
+            // Missed some; compare against the latest instead
+            Err(broadcast::error::RecvError::Lagged(_)) => controller.state().await,
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        if let Err(e) = announce_changes(&iface, &last, &state).await {
+            warn!("Could not emit MPRIS PropertiesChanged: {}", e);
+        }
+        last = state;
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// Server
+// ─────────────────────────────────────────────────────────────
+
+struct Running {
+    conn: Connection,
+    name: String,
+    follow_task: AbortHandle,
+}
+
+pub struct MprisServerInner {
+    controller: Arc<ControllerInner>,
+    extra: Arc<Mutex<MprisExtraMetadata>>,
+    /// Bus to serve on instead of the session bus
+    bus_address: Option<String>,
+    running: tokio::sync::Mutex<Option<Running>>,
+}
+
+impl MprisServerInner {
+    pub fn new(controller: Arc<ControllerInner>) -> Self {
+        Self {
+            controller,
+            extra: Arc::new(Mutex::new(MprisExtraMetadata::default())),
+            bus_address: None,
+            running: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// Use the D-Bus daemon at `address` instead of the session bus
+    pub fn with_bus_address(mut self, address: impl Into<String>) -> Self {
+        self.bus_address = Some(address.into());
+        self
+    }
+
+    /// Publish the player. Returns the bus name claimed: `spotify_tui`, or
+    /// `spotify_tui.instance<pid>` if another TUI already has it. If already
+    /// running, the name in use.
+    pub async fn start(&self) -> Result<String, MprisError> {
+        let mut running = self.running.lock().await;
+        if let Some(ref running) = *running {
+            return Ok(running.name.clone());
+        }
+
+        let conn = open_bus(self.bus_address.as_deref()).await?;
+        let player = Player {
+            controller: self.controller.clone(),
+            extra: self.extra.clone(),
+        };
+        let iface = {
+            let server = conn.object_server();
+            server.at(MPRIS_PATH, Root).await?;
+            server.at(MPRIS_PATH, player).await?;
+            server.interface::<_, Player>(MPRIS_PATH).await?
+        };
+
+        // Subscribe before reading the baseline so no change falls in between
+        let rx = self.controller.subscribe_state_changes();
+        let last = self.controller.state().await;
+        let follow_task = tokio::spawn(follow_state(iface, self.controller.clone(), rx, last));
+
+        let name = match conn.request_name(OWN_PLAYER_NAME).await {
+            Ok(_) => OWN_PLAYER_NAME.to_string(),
+            Err(zbus::Error::NameTaken) => {
+                let name = format!("{}.instance{}", OWN_PLAYER_NAME, std::process::id());
+                if let Err(e) = conn.request_name(name.as_str()).await {
+                    follow_task.abort();
+                    return Err(e.into());
+                }
+                name
+            }
+            Err(e) => {
+                follow_task.abort();
+                return Err(e.into());
+            }
+        };
+        info!("MPRIS server published as {}", name);
+
+        *running = Some(Running {
+            conn,
+            name: name.clone(),
+            follow_task: follow_task.abort_handle(),
+        });
+        Ok(name)
+    }
+
+    /// Withdraw the player from the bus. Returns false if it wasn't running.
+    pub async fn stop(&self) -> bool {
+        let Some(running) = self.running.lock().await.take() else {
+            return false;
+        };
+        running.follow_task.abort();
+        if let Err(e) = running.conn.release_name(running.name.as_str()).await {
+            warn!("Could not release {}: {}", running.name, e);
+        }
+        info!("MPRIS server stopped");
+        true
+    }
+
+    /// Bus name while running
+    pub async fn name(&self) -> Option<String> {
+        self.running.lock().await.as_ref().map(|r| r.name.clone())
+    }
+
+    /// Replace the extra metadata layered over spotifyd's and let clients know
+    pub async fn set_extra_metadata(&self, extra: MprisExtraMetadata) -> Result<(), MprisError> {
+        *self.extra.lock().unwrap() = extra;
+        let running = self.running.lock().await;
+        if let Some(ref running) = *running {
+            let iface = running
+                .conn
+                .object_server()
+                .interface::<_, Player>(MPRIS_PATH)
+                .await?;
+            iface.get().await.metadata_changed(iface.signal_context()).await?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MprisServerInner {
+    fn drop(&mut self) {
+        if let Some(running) = self.running.get_mut().take() {
+            running.follow_task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_player::{connected_controller, DbusDaemon, MockOptions, MockPlayer, MockTrack};
+    use futures::StreamExt;
+    use std::time::Duration;
+    use zbus::proxy;
+    use zbus::zvariant::Str;
+
+    #[proxy(
+        interface = "org.mpris.MediaPlayer2.Player",
+        default_service = "org.mpris.MediaPlayer2.spotify_tui",
+        default_path = "/org/mpris/MediaPlayer2"
+    )]
+    trait OurPlayer {
+        fn play_pause(&self) -> zbus::Result<()>;
+        fn next(&self) -> zbus::Result<()>;
+        fn seek(&self, offset: i64) -> zbus::Result<()>;
+
+        #[zbus(property)]
+        fn playback_status(&self) -> zbus::Result<String>;
+
+        #[zbus(property)]
+        fn metadata(&self) -> zbus::Result<HashMap<String, OwnedValue>>;
+
+        #[zbus(property)]
+        fn volume(&self) -> zbus::Result<f64>;
+
+        #[zbus(property)]
+        fn set_volume(&self, volume: f64) -> zbus::Result<()>;
+
+        #[zbus(property)]
+        fn set_loop_status(&self, status: &str) -> zbus::Result<()>;
+    }
+
+    /// Proxy for our player; `cached` ones can receive property changes
+    async fn connect_client(bus: &DbusDaemon, cached: bool) -> OurPlayerProxy<'static> {
+        let conn = zbus::connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        let cache = match cached {
+            true => zbus::CacheProperties::Yes,
+            false => zbus::CacheProperties::No,
+        };
+        OurPlayerProxy::builder(&conn)
+            .cache_properties(cache)
+            .build()
+            .await
+            .unwrap()
+    }
+
+    async fn server(bus: &DbusDaemon) -> MprisServerInner {
+        let controller = connected_controller(bus).await;
+        let server = MprisServerInner::new(controller).with_bus_address(&bus.address);
+        assert_eq!(server.start().await.unwrap(), OWN_PLAYER_NAME);
+        server
+    }
+
+    fn text(metadata: &HashMap<String, OwnedValue>, key: &str) -> Option<String> {
+        metadata.get(key).and_then(|v| v.downcast_ref::<Str>().ok()).map(|s| s.to_string())
+    }
+
+    #[test]
+    fn commands_are_forwarded_to_spotifyd() {
+        let bus = DbusDaemon::start();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let _server = server(&bus).await;
+            let client = connect_client(&bus, false).await;
+
+            client.play_pause().await.unwrap();
+            client.next().await.unwrap();
+            client.seek(5_000_000).await.unwrap();
+            client.set_volume(0.25).await.unwrap();
+            client.set_loop_status("Track").await.unwrap();
+            assert!(client.set_loop_status("Sometimes").await.is_err());
+
+            assert_eq!(
+                player.calls(),
+                ["PlayPause", "Next", "Seek(5000000)", "Volume=0.25", "LoopStatus=Track"]
+            );
+            assert_eq!(client.volume().await.unwrap(), 0.25);
+        });
+    }
+
+    #[test]
+    fn mirrors_state_and_extra_metadata() {
+        let bus = DbusDaemon::start();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let server = server(&bus).await;
+            let client = connect_client(&bus, false).await;
+
+            let metadata = client.metadata().await.unwrap();
+            assert_eq!(text(&metadata, "xesam:title").as_deref(), Some("First Song"));
+            assert_eq!(client.playback_status().await.unwrap(), "Paused");
+
+            let watcher = connect_client(&bus, true).await;
+            let mut changes = watcher.receive_playback_status_changed().await;
+            player.set_playback_status("Playing").await;
+            let change = tokio::time::timeout(Duration::from_secs(2), async {
+                loop {
+                    let status = changes.next().await.unwrap().get().await.unwrap();
+                    if status == "Playing" {
+                        return status;
+                    }
+                }
+            })
+            .await;
+            assert!(change.is_ok(), "no PlaybackStatus change");
+
+            server
+                .set_extra_metadata(MprisExtraMetadata {
+                    track_uri: Some("/org/spotify/track/1".to_string()),
+                    art_path: Some("/tmp/art/1.jpg".to_string()),
+                    lyrics_line: Some("la la la".to_string()),
+                    url: None,
+                })
+                .await
+                .unwrap();
+            let metadata = client.metadata().await.unwrap();
+            assert_eq!(text(&metadata, "mpris:artUrl").as_deref(), Some("file:///tmp/art/1.jpg"));
+            assert_eq!(text(&metadata, "xesam:asText").as_deref(), Some("la la la"));
+
+            // Extras for the previous track don't leak onto the next one
+            player.set_track(Some(MockTrack::new("2", "Second Song", "Artist"))).await;
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let metadata = client.metadata().await.unwrap();
+            assert_eq!(text(&metadata, "xesam:title").as_deref(), Some("Second Song"));
+            assert!(text(&metadata, "xesam:asText").is_none());
+        });
+    }
+
+    #[test]
+    fn second_instance_gets_a_unique_name() {
+        let bus = DbusDaemon::start();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let _player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let first = server(&bus).await;
+
+            let controller = Arc::new(ControllerInner::new().await.unwrap());
+            let second = MprisServerInner::new(controller).with_bus_address(&bus.address);
+            let name = second.start().await.unwrap();
+            assert!(name.starts_with(&format!("{}.instance", OWN_PLAYER_NAME)), "{}", name);
+
+            assert!(first.stop().await);
+            assert!(!first.stop().await);
+            assert_eq!(first.name().await, None);
+        });
+    }
+}
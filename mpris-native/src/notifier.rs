@@ -0,0 +1,456 @@
+//! "Now playing" desktop notifications, sent through
+//! `org.freedesktop.Notifications` whenever the controller reports a new
+//! track. Each notification replaces the previous one, bursts of track
+//! changes (skipping through a playlist) collapse into one, and nothing is
+//! shown while do-not-disturb is on.
+
+use crate::controller::{track_changed, ControllerInner};
+use crate::bus::open_bus;
+use crate::error::MprisError;
+use crate::types::{NotifierOptions, PlaybackState, TrackInfo};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::AbortHandle;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+use zbus::zvariant::Value;
+use zbus::proxy;
+
+const APP_NAME: &str = "spotify-tui";
+const DEFAULT_MIN_INTERVAL: Duration = Duration::from_secs(2);
+/// Let the notification server pick how long to show it
+const DEFAULT_TIMEOUT_MS: i32 = -1;
+/// Hint values for "urgency"
+const URGENCY_LOW: u8 = 0;
+
+#[proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: HashMap<&str, Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+}
+
+/// Album art cache: ~/.spotify-tui/art
+fn default_art_dir() -> Option<PathBuf> {
+    crate::state_file::spotify_tui_dir().map(|dir| dir.join("art"))
+}
+
+/// Notification bodies may contain markup, so escape what the server would parse
+fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// file:// URL of the track's art: a local `artUrl`, or `<art_dir>/<track id>.jpg|png`
+fn cached_art(track: &TrackInfo, art_dir: Option<&Path>) -> Option<String> {
+    if let Some(ref url) = track.art_url {
+        if url.starts_with("file://") {
+            return Some(url.clone());
+        }
+    }
+    let art_dir = art_dir?;
+    let id = track.uri.rsplit(['/', ':']).next().filter(|id| !id.is_empty())?;
+    ["jpg", "png"]
+        .iter()
+        .map(|ext| art_dir.join(format!("{}.{}", id, ext)))
+        .find(|path| path.is_file())
+        .map(|path| format!("file://{}", path.display()))
+}
+
+/// Summary and body for a track
+fn describe(track: &TrackInfo) -> (String, String) {
+    let body = match (track.artist.is_empty(), track.album.is_empty()) {
+        (false, false) => format!("{} — {}", track.artist, track.album),
+        (false, true) => track.artist.clone(),
+        (true, false) => track.album.clone(),
+        (true, true) => String::new(),
+    };
+    (track.title.clone(), escape_markup(&body))
+}
+
+// ─────────────────────────────────────────────────────────────
+// Notification loop
+// ─────────────────────────────────────────────────────────────
+
+struct Sender {
+    proxy: NotificationsProxy<'static>,
+    timeout_ms: i32,
+    art_dir: Option<PathBuf>,
+    /// ID of our last notification, replaced by the next one
+    last_id: u32,
+}
+
+impl Sender {
+    async fn send(&mut self, track: &TrackInfo) {
+        let (summary, body) = describe(track);
+        let mut hints = HashMap::new();
+        hints.insert("urgency", Value::from(URGENCY_LOW));
+        let art = cached_art(track, self.art_dir.as_deref());
+        if let Some(ref art) = art {
+            hints.insert("image-path", Value::from(art.as_str()));
+        }
+        let result = self
+            .proxy
+            .notify(
+                APP_NAME,
+                self.last_id,
+                "",
+                &summary,
+                &body,
+                &[],
+                hints,
+                self.timeout_ms,
+            )
+            .await;
+        match result {
+            Ok(id) => {
+                debug!("Notified {:?} as #{}", summary, id);
+                self.last_id = id;
+            }
+            // No notification server is not worth more than a log line
+            Err(e) => warn!("Notify failed: {}", e),
+        }
+    }
+}
+
+async fn run(
+    mut sender: Sender,
+    controller: Arc<ControllerInner>,
+    mut rx: broadcast::Receiver<PlaybackState>,
+    mut current: Option<TrackInfo>,
+    min_interval: Duration,
+    do_not_disturb: Arc<AtomicBool>,
+) {
+    let mut pending: Option<TrackInfo> = None;
+    let mut last_sent: Option<Instant> = None;
+
+    loop {
+        let ready_at = last_sent.map_or_else(Instant::now, |at| at + min_interval);
+        tokio::select! {
+            received = rx.recv() => {
+                let state = match received {
+                    Ok(state) => state,
+                    Err(broadcast::error::RecvError::Lagged(_)) => controller.state().await,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if track_changed(current.as_ref(), state.track.as_ref()) {
+                    current = state.track.clone();
+                    // Only the latest track of a burst is worth showing
+                    pending = state.track;
+                }
+            }
+            _ = tokio::time::sleep_until(ready_at), if pending.is_some() => {
+                let track = pending.take().expect("checked by the select guard");
+                if do_not_disturb.load(Ordering::Relaxed) {
+                    debug!("Do not disturb: skipping notification for {:?}", track.title);
+                    continue;
+                }
+                sender.send(&track).await;
+                last_sent = Some(Instant::now());
+            }
+        }
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// Notifier
+// ─────────────────────────────────────────────────────────────
+
+pub struct NotifierInner {
+    controller: Arc<ControllerInner>,
+    options: NotifierOptions,
+    do_not_disturb: Arc<AtomicBool>,
+    /// Bus to notify on instead of the session bus
+    bus_address: Option<String>,
+    task: Mutex<Option<AbortHandle>>,
+}
+
+impl NotifierInner {
+    pub fn new(controller: Arc<ControllerInner>, options: NotifierOptions) -> Self {
+        let do_not_disturb = options.do_not_disturb.unwrap_or(false);
+        Self {
+            controller,
+            options,
+            do_not_disturb: Arc::new(AtomicBool::new(do_not_disturb)),
+            bus_address: None,
+            task: Mutex::new(None),
+        }
+    }
+
+    /// Use the D-Bus daemon at `address` instead of the session bus
+    pub fn with_bus_address(mut self, address: impl Into<String>) -> Self {
+        self.bus_address = Some(address.into());
+        self
+    }
+
+    /// Start notifying on track changes. The track playing now isn't announced.
+    pub async fn start(&self) -> Result<(), MprisError> {
+        if self.is_running() {
+            return Ok(());
+        }
+
+        let conn = open_bus(self.bus_address.as_deref()).await?;
+        let proxy = NotificationsProxy::new(&conn).await?;
+        let sender = Sender {
+            proxy,
+            timeout_ms: self.options.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS),
+            art_dir: self
+                .options
+                .art_cache_dir
+                .as_ref()
+                .map(PathBuf::from)
+                .or_else(default_art_dir),
+            last_id: 0,
+        };
+        let min_interval = self
+            .options
+            .min_interval_ms
+            .map(|ms| Duration::from_millis(ms.into()))
+            .unwrap_or(DEFAULT_MIN_INTERVAL);
+
+        let rx = self.controller.subscribe_state_changes();
+        let current = self.controller.state().await.track;
+        let task = tokio::spawn(run(
+            sender,
+            self.controller.clone(),
+            rx,
+            current,
+            min_interval,
+            self.do_not_disturb.clone(),
+        ));
+
+        let mut slot = self.task.lock().unwrap();
+        if slot.is_some() {
+            // Lost a race with a concurrent start()
+            task.abort();
+        } else {
+            *slot = Some(task.abort_handle());
+            info!("Track change notifications enabled");
+        }
+        Ok(())
+    }
+
+    /// Stop notifying. Returns false if it wasn't running.
+    pub fn stop(&self) -> bool {
+        let Some(task) = self.task.lock().unwrap().take() else {
+            return false;
+        };
+        task.abort();
+        info!("Track change notifications disabled");
+        true
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.task.lock().unwrap().is_some()
+    }
+
+    /// Suppress notifications (including one already waiting) until turned off
+    pub fn set_do_not_disturb(&self, enabled: bool) {
+        self.do_not_disturb.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn do_not_disturb(&self) -> bool {
+        self.do_not_disturb.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for NotifierInner {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_player::{connected_controller, DbusDaemon, MockOptions, MockPlayer, MockTrack};
+    use zbus::{interface, Connection};
+    use zbus::zvariant::OwnedValue;
+
+    #[derive(Debug, Clone)]
+    struct Notification {
+        replaces_id: u32,
+        summary: String,
+        body: String,
+        image: Option<String>,
+    }
+
+    /// Stand-in notification server, recording what it's asked to show
+    #[derive(Default)]
+    struct MockNotifications {
+        received: Arc<Mutex<Vec<Notification>>>,
+    }
+
+    #[interface(name = "org.freedesktop.Notifications")]
+    impl MockNotifications {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &self,
+            _app_name: String,
+            replaces_id: u32,
+            _app_icon: String,
+            summary: String,
+            body: String,
+            _actions: Vec<String>,
+            hints: HashMap<String, OwnedValue>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            let image = hints
+                .get("image-path")
+                .and_then(|v| v.downcast_ref::<String>().ok());
+            let mut received = self.received.lock().unwrap();
+            received.push(Notification {
+                replaces_id,
+                summary,
+                body,
+                image,
+            });
+            // Replacements keep their ID, new notifications get the next one
+            if replaces_id != 0 {
+                replaces_id
+            } else {
+                received.len() as u32
+            }
+        }
+    }
+
+    async fn notification_server(bus: &DbusDaemon) -> (Connection, Arc<Mutex<Vec<Notification>>>) {
+        let server = MockNotifications::default();
+        let received = server.received.clone();
+        let conn = zbus::connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .name("org.freedesktop.Notifications")
+            .unwrap()
+            .serve_at("/org/freedesktop/Notifications", server)
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        (conn, received)
+    }
+
+    async fn notifier(bus: &DbusDaemon, options: NotifierOptions) -> NotifierInner {
+        let notifier = NotifierInner::new(connected_controller(bus).await, options)
+            .with_bus_address(&bus.address);
+        notifier.start().await.unwrap();
+        notifier
+    }
+
+    async fn settle() {
+        tokio::time::sleep(Duration::from_millis(150)).await;
+    }
+
+    #[test]
+    fn notifies_new_tracks_replacing_the_last_notification() {
+        let bus = DbusDaemon::start();
+        let art = tempfile::tempdir().unwrap();
+        std::fs::write(art.path().join("2.jpg"), b"jpeg").unwrap();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let (_server, received) = notification_server(&bus).await;
+            let options = NotifierOptions {
+                min_interval_ms: Some(0),
+                art_cache_dir: Some(art.path().to_string_lossy().into_owned()),
+                ..Default::default()
+            };
+            let _notifier = notifier(&bus, options).await;
+
+            // Pausing isn't a track change
+            player.set_playback_status("Playing").await;
+            settle().await;
+            assert!(received.lock().unwrap().is_empty());
+
+            let mut track = MockTrack::new("2", "Rock & Roll", "Band");
+            track.album = "<Live>".to_string();
+            player.set_track(Some(track)).await;
+            settle().await;
+            player.set_track(Some(MockTrack::new("3", "Encore", "Band"))).await;
+            settle().await;
+
+            let received = received.lock().unwrap().clone();
+            assert_eq!(received.len(), 2, "{:?}", received);
+            assert_eq!(received[0].summary, "Rock & Roll");
+            assert_eq!(received[0].body, "Band — &lt;Live&gt;");
+            assert_eq!(received[0].replaces_id, 0);
+            let expected = format!("file://{}", art.path().join("2.jpg").display());
+            assert_eq!(received[0].image.as_deref(), Some(expected.as_str()));
+            assert_eq!(received[1].summary, "Encore");
+            assert_eq!(received[1].replaces_id, 1);
+            assert_eq!(received[1].image, None);
+        });
+    }
+
+    #[test]
+    fn bursts_are_rate_limited_and_do_not_disturb_is_respected() {
+        let bus = DbusDaemon::start();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let (_server, received) = notification_server(&bus).await;
+            let options = NotifierOptions {
+                min_interval_ms: Some(500),
+                ..Default::default()
+            };
+            let notifier = notifier(&bus, options).await;
+
+            player.set_track(Some(MockTrack::new("2", "Two", "A"))).await;
+            settle().await;
+            player.set_track(Some(MockTrack::new("3", "Three", "A"))).await;
+            player.set_track(Some(MockTrack::new("4", "Four", "A"))).await;
+            settle().await;
+            assert_eq!(received.lock().unwrap().len(), 1);
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            let summaries: Vec<_> =
+                received.lock().unwrap().iter().map(|n| n.summary.clone()).collect();
+            assert_eq!(summaries, ["Two", "Four"]);
+
+            notifier.set_do_not_disturb(true);
+            player.set_track(Some(MockTrack::new("5", "Five", "A"))).await;
+            tokio::time::sleep(Duration::from_millis(700)).await;
+            assert_eq!(received.lock().unwrap().len(), 2);
+
+            notifier.set_do_not_disturb(false);
+            assert!(notifier.stop());
+            player.set_track(Some(MockTrack::new("6", "Six", "A"))).await;
+            settle().await;
+            assert_eq!(received.lock().unwrap().len(), 2);
+        });
+    }
+
+    #[test]
+    fn art_lookup_prefers_local_art_urls() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("abc.png"), b"png").unwrap();
+        let mut track = TrackInfo {
+            title: "T".to_string(),
+            artist: String::new(),
+            album: String::new(),
+            art_url: Some("https://i.scdn.co/image/x".to_string()),
+            uri: "/org/spotify/track/abc".to_string(),
+        };
+        let expected = format!("file://{}", dir.path().join("abc.png").display());
+        assert_eq!(cached_art(&track, Some(dir.path())), Some(expected));
+        assert_eq!(cached_art(&track, None), None);
+
+        track.art_url = Some("file:///tmp/cover.jpg".to_string());
+        assert_eq!(cached_art(&track, Some(dir.path())).as_deref(), Some("file:///tmp/cover.jpg"));
+        assert_eq!(describe(&track), ("T".to_string(), String::new()));
+    }
+}
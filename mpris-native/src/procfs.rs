@@ -0,0 +1,339 @@
+use crate::types::SpotifydProcessInfo;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::Path;
+
+/// Process name we look for in /proc
+const SPOTIFYD_NAME: &str = "spotifyd";
+
+/// Fields we care about from /proc/<pid>/stat
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcStat {
+    pub comm: String,
+    pub state: char,
+    /// Start time in clock ticks since boot
+    pub start_ticks: u64,
+    /// Resident set size in pages
+    pub rss_pages: u64,
+}
+
+/// A process found while scanning /proc
+#[derive(Debug, Clone)]
+pub struct ProcessEntry {
+    pub pid: u32,
+    pub uid: u32,
+    pub stat: ProcStat,
+    pub exe: Option<String>,
+    pub cmdline: Vec<String>,
+}
+
+/// A specific process instance: PID plus start time and executable, so a
+/// recycled PID is never mistaken for the process we were tracking
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessIdentity {
+    pub pid: u32,
+    pub start_ticks: u64,
+    pub exe: Option<String>,
+}
+
+fn same_exe(a: &str, b: &str) -> bool {
+    // An upgraded binary shows up as "<path> (deleted)" but is still the same process
+    a.trim_end_matches(" (deleted)") == b.trim_end_matches(" (deleted)")
+}
+
+impl ProcessIdentity {
+    /// Snapshot the identity of whatever is currently running under `pid`
+    pub fn capture(pid: u32) -> Option<Self> {
+        let stat = read_stat(pid)?;
+        Some(Self {
+            pid,
+            start_ticks: stat.start_ticks,
+            exe: read_exe(pid),
+        })
+    }
+
+    /// Whether `pid` still refers to this process and it hasn't exited.
+    /// A PID that now belongs to something else counts as gone.
+    pub fn is_alive(&self) -> bool {
+        let Some(stat) = read_stat(self.pid) else {
+            return false;
+        };
+        if stat.state == 'Z' || stat.start_ticks != self.start_ticks {
+            return false;
+        }
+        match (&self.exe, read_exe(self.pid)) {
+            (Some(ours), Some(current)) => same_exe(ours, &current),
+            _ => true,
+        }
+    }
+
+    /// Send a signal, but only if the PID still refers to this process.
+    ///
+    /// Uses a pidfd where available so the process can't be swapped out
+    /// between the identity check and the signal. Returns `Ok(false)` if the
+    /// process is gone.
+    pub fn signal(&self, sig: libc::c_int) -> std::io::Result<bool> {
+        let raw = unsafe { libc::syscall(libc::SYS_pidfd_open, self.pid as libc::pid_t, 0) };
+        if raw >= 0 {
+            let pidfd = unsafe { OwnedFd::from_raw_fd(raw as libc::c_int) };
+            // The pidfd pins the process; verify it's the one we expect
+            if !self.is_alive() {
+                return Ok(false);
+            }
+            let res = unsafe {
+                libc::syscall(
+                    libc::SYS_pidfd_send_signal,
+                    pidfd.as_raw_fd(),
+                    sig,
+                    std::ptr::null::<libc::siginfo_t>(),
+                    0,
+                )
+            };
+            return match res {
+                0 => Ok(true),
+                _ => gone_or_error(std::io::Error::last_os_error()),
+            };
+        }
+
+        let err = std::io::Error::last_os_error();
+        if err.raw_os_error() == Some(libc::ESRCH) {
+            return Ok(false);
+        }
+
+        // Kernels without pidfd support (< 5.3): best-effort check then kill
+        if !self.is_alive() {
+            return Ok(false);
+        }
+        match unsafe { libc::kill(self.pid as libc::pid_t, sig) } {
+            0 => Ok(true),
+            _ => gone_or_error(std::io::Error::last_os_error()),
+        }
+    }
+}
+
+fn gone_or_error(err: std::io::Error) -> std::io::Result<bool> {
+    if err.raw_os_error() == Some(libc::ESRCH) {
+        Ok(false)
+    } else {
+        Err(err)
+    }
+}
+
+/// Parse the contents of /proc/<pid>/stat.
+///
+/// The comm field is wrapped in parentheses and may itself contain spaces or
+/// parentheses, so everything after the *last* ')' is split on whitespace.
+pub fn parse_stat(contents: &str) -> Option<ProcStat> {
+    let open = contents.find('(')?;
+    let close = contents.rfind(')')?;
+    let comm = contents.get(open + 1..close)?.to_string();
+    let rest: Vec<&str> = contents.get(close + 1..)?.split_whitespace().collect();
+
+    // rest[0] is field 3 (state); starttime is field 22, rss is field 24
+    let state = rest.first()?.chars().next()?;
+    let start_ticks = rest.get(19)?.parse().ok()?;
+    let rss_pages = rest.get(21)?.parse().ok()?;
+
+    Some(ProcStat {
+        comm,
+        state,
+        start_ticks,
+        rss_pages,
+    })
+}
+
+pub fn read_stat(pid: u32) -> Option<ProcStat> {
+    let contents = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    parse_stat(&contents)
+}
+
+/// Real UID from /proc/<pid>/status
+fn read_uid(pid: u32) -> Option<u32> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))
+        .and_then(|uids| uids.split_whitespace().next())
+        .and_then(|uid| uid.parse().ok())
+}
+
+pub fn read_cmdline(pid: u32) -> Vec<String> {
+    std::fs::read(format!("/proc/{}/cmdline", pid))
+        .map(|bytes| {
+            bytes
+                .split(|b| *b == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Resolved executable path; unreadable for processes we don't own
+fn read_exe(pid: u32) -> Option<String> {
+    std::fs::read_link(format!("/proc/{}/exe", pid))
+        .ok()
+        .map(|p| p.to_string_lossy().into_owned())
+}
+
+pub fn read_process(pid: u32) -> Option<ProcessEntry> {
+    let stat = read_stat(pid)?;
+    let uid = read_uid(pid)?;
+    Some(ProcessEntry {
+        pid,
+        uid,
+        stat,
+        exe: read_exe(pid),
+        cmdline: read_cmdline(pid),
+    })
+}
+
+fn file_name(path: &str) -> Option<&str> {
+    Path::new(path).file_name().and_then(|n| n.to_str())
+}
+
+impl ProcessEntry {
+    /// Whether this looks like a spotifyd process, by comm, exe or argv[0]
+    pub fn is_spotifyd(&self) -> bool {
+        if self.stat.comm == SPOTIFYD_NAME {
+            return true;
+        }
+        // A deleted binary shows up as "/path/spotifyd (deleted)"
+        if let Some(exe) = &self.exe {
+            let exe = exe.trim_end_matches(" (deleted)");
+            if file_name(exe) == Some(SPOTIFYD_NAME) {
+                return true;
+            }
+        }
+        self.cmdline
+            .first()
+            .and_then(|argv0| file_name(argv0))
+            .is_some_and(|name| name == SPOTIFYD_NAME)
+    }
+
+    pub fn is_zombie(&self) -> bool {
+        self.stat.state == 'Z'
+    }
+
+    pub fn identity(&self) -> ProcessIdentity {
+        ProcessIdentity {
+            pid: self.pid,
+            start_ticks: self.stat.start_ticks,
+            exe: self.exe.clone(),
+        }
+    }
+
+    /// Time since the process started
+    pub fn uptime_ms(&self) -> Option<i64> {
+        let uptime_secs = system_uptime_secs()?;
+        let started_secs = self.stat.start_ticks as f64 / clock_ticks_per_sec();
+        Some(((uptime_secs - started_secs).max(0.0) * 1000.0) as i64)
+    }
+
+    pub fn rss_bytes(&self) -> i64 {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as u64;
+        (self.stat.rss_pages * page_size) as i64
+    }
+
+    pub fn to_info(&self) -> SpotifydProcessInfo {
+        SpotifydProcessInfo {
+            pid: self.pid,
+            binary_path: self.exe.clone().or_else(|| self.cmdline.first().cloned()),
+            args: self.cmdline.iter().skip(1).cloned().collect(),
+            uptime_ms: self.uptime_ms(),
+            rss_bytes: self.rss_bytes(),
+        }
+    }
+}
+
+fn system_uptime_secs() -> Option<f64> {
+    std::fs::read_to_string("/proc/uptime")
+        .ok()?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+fn clock_ticks_per_sec() -> f64 {
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 {
+        ticks as f64
+    } else {
+        100.0
+    }
+}
+
+/// Iterate over all numeric entries in /proc
+fn all_pids() -> Vec<u32> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.file_name().to_str().and_then(|n| n.parse().ok()))
+        .collect()
+}
+
+pub fn current_uid() -> u32 {
+    unsafe { libc::getuid() }
+}
+
+/// All live spotifyd processes regardless of owner, oldest first
+pub fn scan_spotifyd_processes() -> Vec<ProcessEntry> {
+    let mut found: Vec<ProcessEntry> = all_pids()
+        .into_iter()
+        .filter_map(read_process)
+        .filter(|p| !p.is_zombie() && p.is_spotifyd())
+        .collect();
+    found.sort_by_key(|p| p.stat.start_ticks);
+    found
+}
+
+/// All live spotifyd processes owned by the current user, oldest first
+pub fn find_spotifyd_processes() -> Vec<ProcessEntry> {
+    let uid = current_uid();
+    scan_spotifyd_processes()
+        .into_iter()
+        .filter(|p| p.uid == uid)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_stat_handles_parens_and_spaces_in_comm() {
+        let line = "4242 (spot (d) x) S 1 4242 4242 0 -1 4194560 100 0 0 0 5 3 0 0 20 0 4 0 \
+                    987654 123456789 2048 18446744073709551615 1 1 0 0 0 0 0 4096 0 0 0 0 17 3 0 0 0 0 0";
+        let stat = parse_stat(line).unwrap();
+        assert_eq!(stat.comm, "spot (d) x");
+        assert_eq!(stat.state, 'S');
+        assert_eq!(stat.start_ticks, 987654);
+        assert_eq!(stat.rss_pages, 2048);
+    }
+
+    #[test]
+    fn read_process_sees_current_process() {
+        let me = read_process(std::process::id()).unwrap();
+        assert_eq!(me.uid, current_uid());
+        assert!(!me.is_zombie());
+        assert!(me.exe.is_some());
+        assert!(me.uptime_ms().is_some());
+    }
+
+    #[test]
+    fn identity_mismatch_counts_as_gone() {
+        let me = ProcessIdentity::capture(std::process::id()).unwrap();
+        assert!(me.is_alive());
+        // Signal 0 only checks deliverability
+        assert!(me.signal(0).unwrap());
+
+        let recycled = ProcessIdentity {
+            start_ticks: me.start_ticks + 1,
+            ..me
+        };
+        assert!(!recycled.is_alive());
+        assert!(!recycled.signal(0).unwrap());
+    }
+}
@@ -0,0 +1,495 @@
+//! Sleep timer: pause after a while, when the current track ends, after a
+//! number of tracks or once the next album starts - optionally fading the
+//! volume out first.
+//!
+//! The timer runs as a task on the controller's runtime and shows up in
+//! `PlaybackState::sleep_timer`, with `ends_at_ms` for a countdown once the
+//! end is known. Track ends are worked out from the track length and the
+//! live position, re-checked periodically and whenever playback changes.
+
+use crate::controller::ControllerInner;
+use crate::error::MprisError;
+use crate::types::{
+    FadeCurve, PlaybackState, SleepTimerMode, SleepTimerOptions, SleepTimerState,
+};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use tokio::task::AbortHandle;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+/// How often the end of a track is recomputed from the live position
+const RESYNC_INTERVAL: Duration = Duration::from_secs(5);
+/// Pause this long before a track's end so the next one doesn't start
+const END_MARGIN: Duration = Duration::from_millis(750);
+/// `ends_at_ms` changes smaller than this aren't worth a state update
+const ENDS_AT_TOLERANCE_MS: i64 = 1000;
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Validated sleep timer options
+pub(crate) struct Plan {
+    mode: SleepTimerMode,
+    until: Until,
+    fade: Duration,
+}
+
+enum Until {
+    Elapsed(Duration),
+    /// This many track ends
+    Tracks(u32),
+    AlbumChange,
+}
+
+pub(crate) fn plan(options: &SleepTimerOptions) -> Result<Plan, MprisError> {
+    let invalid = |message: &str| MprisError::InvalidArgument(message.to_string());
+    let until = match options.mode {
+        SleepTimerMode::Duration => match options.duration_ms {
+            Some(ms) if ms > 0 => Until::Elapsed(Duration::from_millis(ms.into())),
+            _ => return Err(invalid("durationMs is required for Duration")),
+        },
+        SleepTimerMode::EndOfTrack => Until::Tracks(1),
+        SleepTimerMode::EndOfAlbum => Until::AlbumChange,
+        SleepTimerMode::AfterTracks => match options.tracks {
+            Some(tracks) if tracks > 0 => Until::Tracks(tracks),
+            _ => return Err(invalid("tracks must be at least 1 for AfterTracks")),
+        },
+    };
+    let mut fade = Duration::from_millis(options.fade_out_ms.unwrap_or(0).into());
+    match until {
+        Until::Elapsed(duration) => fade = fade.min(duration),
+        // The next album is already playing by the time we know; a fade
+        // would only play more of it
+        Until::AlbumChange => fade = Duration::ZERO,
+        Until::Tracks(_) => {}
+    }
+    Ok(Plan {
+        mode: options.mode,
+        until,
+        fade,
+    })
+}
+
+fn track_uri(state: &PlaybackState) -> Option<&str> {
+    state.track.as_ref().map(|t| t.uri.as_str())
+}
+
+fn album(state: &PlaybackState) -> Option<&str> {
+    state.track.as_ref().map(|t| t.album.as_str())
+}
+
+/// The timer's entry in the playback state
+struct Shown<'a> {
+    controller: &'a ControllerInner,
+    state: SleepTimerState,
+}
+
+impl Shown<'_> {
+    async fn update(&mut self, change: impl FnOnce(&mut SleepTimerState)) {
+        let before = self.state.clone();
+        change(&mut self.state);
+        let ends_at_moved = match (before.ends_at_ms, self.state.ends_at_ms) {
+            (Some(a), Some(b)) => (a - b).abs() >= ENDS_AT_TOLERANCE_MS,
+            (a, b) => a.is_some() != b.is_some(),
+        };
+        let mut other = self.state.clone();
+        other.ends_at_ms = before.ends_at_ms;
+        if ends_at_moved || other != before {
+            self.controller.publish_sleep_timer(Some(self.state.clone())).await;
+        }
+    }
+}
+
+/// Next state from `rx`, or None once the controller is gone
+async fn next_state(
+    controller: &ControllerInner,
+    rx: &mut broadcast::Receiver<PlaybackState>,
+) -> Option<PlaybackState> {
+    match rx.recv().await {
+        Ok(state) => Some(state),
+        Err(broadcast::error::RecvError::Lagged(_)) => Some(controller.state().await),
+        Err(broadcast::error::RecvError::Closed) => None,
+    }
+}
+
+/// When to fire within the last track: None if it isn't playing or its
+/// end is unknown, Err(()) if it's already due
+async fn last_track_deadline(
+    controller: &ControllerInner,
+    state: &PlaybackState,
+    lead: Duration,
+) -> Result<Option<(Instant, i64)>, ()> {
+    if !state.is_playing || state.duration_ms <= 0 {
+        return Ok(None);
+    }
+    let Ok(position_ms) = controller.position_ms().await else {
+        return Ok(None);
+    };
+    let remaining = Duration::from_millis((state.duration_ms - position_ms).max(0) as u64);
+    if remaining <= lead {
+        return Err(());
+    }
+    Ok(Some((Instant::now() + remaining - lead, now_ms() + remaining.as_millis() as i64)))
+}
+
+/// Wait until `tracks` tracks have ended (or been skipped), counting from
+/// `state`, the playback state when the timer started
+async fn wait_for_tracks(
+    shown: &mut Shown<'_>,
+    mut rx: broadcast::Receiver<PlaybackState>,
+    mut state: PlaybackState,
+    tracks: u32,
+    lead: Duration,
+) {
+    let controller = shown.controller;
+    let mut current = track_uri(&state).map(str::to_string);
+    let mut left = tracks;
+
+    let mut sync_due = true;
+    let mut fire_at = None;
+    let mut resync_at = Instant::now();
+    loop {
+        if sync_due {
+            sync_due = false;
+            resync_at = Instant::now() + RESYNC_INTERVAL;
+            fire_at = None;
+            let mut ends_at_ms = None;
+            if left == 1 {
+                match last_track_deadline(controller, &state, lead).await {
+                    Err(()) => return,
+                    Ok(Some((at, ends_at))) => {
+                        fire_at = Some(at);
+                        ends_at_ms = Some(ends_at);
+                    }
+                    Ok(None) => {}
+                }
+            }
+            shown.update(|s| s.ends_at_ms = ends_at_ms).await;
+        }
+
+        let wake = fire_at.map_or(resync_at, |at: Instant| at.min(resync_at));
+        tokio::select! {
+            _ = tokio::time::sleep_until(wake) => {
+                if fire_at.is_some_and(|at| at <= Instant::now()) {
+                    return;
+                }
+                sync_due = true;
+            }
+            received = next_state(controller, &mut rx) => {
+                let Some(new) = received else { return };
+                if track_uri(&new) != current.as_deref() {
+                    current = track_uri(&new).map(str::to_string);
+                    left -= 1;
+                    if left == 0 {
+                        return;
+                    }
+                    shown.update(|s| s.tracks_remaining = Some(left)).await;
+                    sync_due = true;
+                } else if new.is_playing != state.is_playing {
+                    sync_due = true;
+                }
+                state = new;
+            }
+        }
+    }
+}
+
+/// Wait until a track from a different album than `state`'s starts
+async fn wait_for_album_change(
+    controller: &ControllerInner,
+    mut rx: broadcast::Receiver<PlaybackState>,
+    state: PlaybackState,
+) {
+    let Some(album_now) = album(&state).map(str::to_string) else {
+        return;
+    };
+    while let Some(state) = next_state(controller, &mut rx).await {
+        if album(&state).is_some_and(|a| a != album_now) {
+            return;
+        }
+    }
+}
+
+/// Fade the volume out over `fade`, remembering where it started
+async fn fade_out(shown: &mut Shown<'_>, fade: Duration, restore: &Mutex<Option<f64>>) {
+    let controller = shown.controller;
+    *restore.lock().unwrap() = Some(controller.state().await.volume);
+    shown.update(|s| s.fading = true).await;
+
+    let fade_ms = fade.as_millis() as u32;
+    match controller.fade_to(0.0, fade_ms, FadeCurve::EaseIn).await {
+        Ok(true) => {}
+        // Someone turned it up or down meanwhile; pause all the same
+        Ok(false) => info!("Sleep timer fade was interrupted"),
+        Err(e) => warn!("Sleep timer fade failed: {}", e),
+    }
+}
+
+/// Playback as the timer started: changes from `rx` on top of `state`
+struct Start {
+    rx: broadcast::Receiver<PlaybackState>,
+    state: PlaybackState,
+}
+
+async fn run(
+    controller: Arc<ControllerInner>,
+    plan: Plan,
+    shown: SleepTimerState,
+    start: Start,
+    restore: Arc<Mutex<Option<f64>>>,
+) {
+    let mut shown = Shown {
+        controller: &controller,
+        state: shown,
+    };
+
+    match plan.until {
+        Until::Elapsed(duration) => tokio::time::sleep(duration - plan.fade).await,
+        Until::Tracks(tracks) => {
+            let lead = plan.fade + END_MARGIN;
+            wait_for_tracks(&mut shown, start.rx, start.state, tracks, lead).await
+        }
+        Until::AlbumChange => wait_for_album_change(&controller, start.rx, start.state).await,
+    }
+
+    if !plan.fade.is_zero() {
+        fade_out(&mut shown, plan.fade, &restore).await;
+    }
+    info!("Sleep timer fired, pausing");
+    if let Err(e) = controller.pause().await {
+        warn!("Sleep timer could not pause: {}", e);
+    }
+    // Back to where it was for whenever playback resumes
+    let volume = restore.lock().unwrap().take();
+    if let Some(volume) = volume {
+        if let Err(e) = controller.set_volume(volume).await {
+            warn!("Could not restore volume after fade: {}", e);
+        }
+    }
+    controller.publish_sleep_timer(None).await;
+}
+
+/// Handle on a running sleep timer
+pub(crate) struct SleepTimer {
+    task: AbortHandle,
+    /// Volume before the fade began, while fading
+    restore: Arc<Mutex<Option<f64>>>,
+}
+
+impl SleepTimer {
+    /// Spawn the timer; also returns its initial state
+    pub(crate) async fn start(
+        controller: Arc<ControllerInner>,
+        plan: Plan,
+    ) -> (Self, SleepTimerState) {
+        // Subscribe before the snapshot so no track change is missed
+        let rx = controller.subscribe_state_changes();
+        let start = Start {
+            rx,
+            state: controller.state().await,
+        };
+        let state = SleepTimerState {
+            mode: plan.mode,
+            ends_at_ms: match plan.until {
+                Until::Elapsed(duration) => Some(now_ms() + duration.as_millis() as i64),
+                _ => None,
+            },
+            tracks_remaining: match plan.until {
+                Until::Tracks(tracks) => Some(tracks),
+                _ => None,
+            },
+            fade_out_ms: plan.fade.as_millis() as u32,
+            fading: false,
+        };
+        controller.publish_sleep_timer(Some(state.clone())).await;
+
+        let restore = Arc::new(Mutex::new(None));
+        let task = tokio::spawn(run(controller, plan, state.clone(), start, restore.clone()));
+        let timer = Self {
+            task: task.abort_handle(),
+            restore,
+        };
+        (timer, state)
+    }
+
+    /// Stop the timer. Returns the volume to restore if it was fading.
+    pub(crate) fn cancel(self) -> Option<f64> {
+        self.task.abort();
+        self.restore.lock().unwrap().take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_player::{
+        connected_controller, DbusDaemon, MockOptions, MockPlayer, MockState, MockTrack,
+    };
+
+    fn options(mode: SleepTimerMode) -> SleepTimerOptions {
+        SleepTimerOptions {
+            mode,
+            duration_ms: None,
+            tracks: None,
+            fade_out_ms: None,
+        }
+    }
+
+    fn playing_near_end() -> MockOptions {
+        MockOptions {
+            state: MockState {
+                playback_status: "Playing".to_string(),
+                // 180s track, 1s left
+                position_us: 179_000_000,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn duration_timer_fades_out_pauses_and_restores_volume() {
+        let bus = DbusDaemon::start();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let player = MockPlayer::start(&bus, playing_near_end()).await;
+            let controller = connected_controller(&bus).await;
+
+            let started = controller
+                .start_sleep_timer(SleepTimerOptions {
+                    duration_ms: Some(400),
+                    fade_out_ms: Some(200),
+                    ..options(SleepTimerMode::Duration)
+                })
+                .await
+                .unwrap();
+            assert!(started.ends_at_ms.unwrap() > now_ms());
+            assert_eq!(controller.state().await.sleep_timer, Some(started));
+
+            tokio::time::sleep(Duration::from_millis(700)).await;
+            let calls = player.calls();
+            assert!(calls.first().unwrap().starts_with("Volume=0."), "{:?}", calls);
+            assert!(calls.contains(&"Volume=0".to_string()), "{:?}", calls);
+            assert_eq!(&calls[calls.len() - 2..], ["Pause", "Volume=0.5"]);
+            let state = controller.state().await;
+            assert_eq!(state.sleep_timer, None);
+            assert_eq!(state.volume, 0.5);
+        });
+    }
+
+    #[test]
+    fn end_of_track_pauses_just_before_the_end() {
+        let bus = DbusDaemon::start();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let player = MockPlayer::start(&bus, playing_near_end()).await;
+            let controller = connected_controller(&bus).await;
+
+            controller.start_sleep_timer(options(SleepTimerMode::EndOfTrack)).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let timer = controller.state().await.sleep_timer.unwrap();
+            let left = timer.ends_at_ms.unwrap() - now_ms();
+            assert!((500..=1000).contains(&left), "{}", left);
+            assert!(player.calls().is_empty());
+
+            // 1s left less the 750ms margin
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            assert_eq!(player.calls(), ["Pause"]);
+            assert_eq!(controller.state().await.sleep_timer, None);
+        });
+    }
+
+    #[test]
+    fn after_tracks_counts_track_changes() {
+        let bus = DbusDaemon::start();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let controller = connected_controller(&bus).await;
+
+            let timer = SleepTimerOptions {
+                tracks: Some(2),
+                ..options(SleepTimerMode::AfterTracks)
+            };
+            controller.start_sleep_timer(timer).await.unwrap();
+
+            player.set_track(Some(MockTrack::new("2", "Two", "A"))).await;
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let timer = controller.state().await.sleep_timer.unwrap();
+            assert_eq!(timer.tracks_remaining, Some(1));
+            assert!(player.calls().is_empty());
+
+            player.set_track(Some(MockTrack::new("3", "Three", "A"))).await;
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert_eq!(player.calls(), ["Pause"]);
+        });
+    }
+
+    #[test]
+    fn end_of_album_waits_for_another_album() {
+        let bus = DbusDaemon::start();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let controller = connected_controller(&bus).await;
+            controller.start_sleep_timer(options(SleepTimerMode::EndOfAlbum)).await.unwrap();
+
+            player.set_track(Some(MockTrack::new("2", "Same Album", "A"))).await;
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert!(player.calls().is_empty());
+
+            let mut other = MockTrack::new("3", "Elsewhere", "B");
+            other.album = "Other Album".to_string();
+            player.set_track(Some(other)).await;
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert_eq!(player.calls(), ["Pause"]);
+        });
+    }
+
+    #[test]
+    fn cancel_restores_volume_mid_fade() {
+        let bus = DbusDaemon::start();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let player = MockPlayer::start(&bus, MockOptions::default()).await;
+            let controller = connected_controller(&bus).await;
+
+            let timer = SleepTimerOptions {
+                duration_ms: Some(1000),
+                fade_out_ms: Some(1000),
+                ..options(SleepTimerMode::Duration)
+            };
+            let started = controller.start_sleep_timer(timer).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(350)).await;
+            let shown = controller.state().await.sleep_timer.unwrap();
+            assert!(shown.fading);
+            // Still counting down to the end it started with
+            assert_eq!(shown.ends_at_ms, started.ends_at_ms);
+
+            assert!(controller.cancel_sleep_timer().await);
+            assert!(!controller.cancel_sleep_timer().await);
+            let calls = player.calls();
+            assert_eq!(calls.last().map(String::as_str), Some("Volume=0.5"));
+            assert!(!calls.contains(&"Pause".to_string()));
+            assert_eq!(controller.state().await.sleep_timer, None);
+        });
+    }
+
+    #[test]
+    fn options_are_validated() {
+        assert!(plan(&options(SleepTimerMode::Duration)).is_err());
+        assert!(plan(&options(SleepTimerMode::AfterTracks)).is_err());
+        let fade_longer_than_timer = SleepTimerOptions {
+            duration_ms: Some(1000),
+            fade_out_ms: Some(5000),
+            ..options(SleepTimerMode::Duration)
+        };
+        assert_eq!(plan(&fade_longer_than_timer).unwrap().fade, Duration::from_secs(1));
+        let album_fade = SleepTimerOptions {
+            fade_out_ms: Some(5000),
+            ..options(SleepTimerMode::EndOfAlbum)
+        };
+        assert_eq!(plan(&album_fade).unwrap().fade, Duration::ZERO);
+    }
+}
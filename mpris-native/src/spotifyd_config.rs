@@ -0,0 +1,533 @@
+use crate::error::MprisError;
+use crate::state_file::spotify_tui_dir;
+use crate::types::SpotifydConfig;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+/// Config file we generate when the user doesn't supply their own
+const GENERATED_CONFIG_FILE: &str = "spotifyd.conf";
+
+/// Bitrates spotifyd accepts
+const VALID_BITRATES: [u32; 3] = [96, 160, 320];
+
+/// Buses spotifyd can register its D-Bus interfaces on
+const VALID_DBUS_TYPES: [&str; 2] = ["session", "system"];
+
+/// spotifyd's TOML config layout; all settings live under `[global]`
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SpotifydToml {
+    pub global: GlobalSection,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GlobalSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bitrate: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backend: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume_normalisation: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initial_volume: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub autoplay: Option<bool>,
+}
+
+impl SpotifydToml {
+    /// Build and validate the config file contents from our settings
+    pub fn from_config(config: &SpotifydConfig) -> Result<Self, MprisError> {
+        if let Some(bitrate) = config.bitrate {
+            if !VALID_BITRATES.contains(&bitrate) {
+                return Err(MprisError::InvalidConfig(format!(
+                    "bitrate must be one of {:?}, got {}",
+                    VALID_BITRATES, bitrate
+                )));
+            }
+        }
+        if let Some(volume) = config.initial_volume {
+            if volume > 100 {
+                return Err(MprisError::InvalidConfig(format!(
+                    "initial_volume must be 0-100, got {}",
+                    volume
+                )));
+            }
+        }
+
+        Ok(Self {
+            global: GlobalSection {
+                username: config.username.clone(),
+                password: config.password.clone(),
+                device_name: config.device_name.clone(),
+                bitrate: config.bitrate,
+                backend: config.backend.clone(),
+                device_type: config.device_type.clone(),
+                cache_path: config.cache_path.clone(),
+                volume_normalisation: config.volume_normalisation,
+                initial_volume: config.initial_volume,
+                autoplay: config.autoplay,
+            },
+        })
+    }
+
+    pub fn render(&self) -> Result<String, MprisError> {
+        toml::to_string(self).map_err(|e| MprisError::InvalidConfig(e.to_string()))
+    }
+}
+
+/// Check every setting up front, so a bad config is rejected before a
+/// running spotifyd is stopped to apply it
+pub fn validate(config: &SpotifydConfig) -> Result<(), MprisError> {
+    SpotifydToml::from_config(config)?;
+    validate_flags(config)?;
+    validate_env(config)
+}
+
+/// Check the settings that are only ever passed as flags
+fn validate_flags(config: &SpotifydConfig) -> Result<(), MprisError> {
+    if let Some(ref dbus_type) = config.dbus_type {
+        if !VALID_DBUS_TYPES.contains(&dbus_type.as_str()) {
+            return Err(MprisError::InvalidConfig(format!(
+                "dbus_type must be one of {:?}, got {:?}",
+                VALID_DBUS_TYPES, dbus_type
+            )));
+        }
+    }
+    if let Some(port) = config.zeroconf_port {
+        if port > u16::MAX as u32 {
+            return Err(MprisError::InvalidConfig(format!(
+                "zeroconf_port must be a valid port, got {}",
+                port
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn push_opt(args: &mut Vec<String>, flag: &str, value: Option<impl ToString>) {
+    if let Some(value) = value {
+        args.push(flag.to_string());
+        args.push(value.to_string());
+    }
+}
+
+/// Build spotifyd's command line (without argv[0]).
+///
+/// Settings already rendered into a generated config file aren't repeated;
+/// with a user-supplied config file they are passed as flags instead, which
+/// spotifyd lets override the file. `volume_normalisation` and `autoplay`
+/// are switches with no "off" form, so `Some(false)` can't override a `true`
+/// in that file. `extra_args` always come last.
+pub fn command_line_args(config: &SpotifydConfig, config_path: Option<&Path>) -> Vec<String> {
+    let mut args = vec!["--no-daemon".to_string()];
+
+    push_opt(&mut args, "--config-path", config_path.map(|p| p.display()));
+    push_opt(&mut args, "--device-name", config.device_name.as_ref());
+
+    if config.config_path.is_some() {
+        push_opt(&mut args, "--bitrate", config.bitrate);
+        push_opt(&mut args, "--backend", config.backend.as_ref());
+        push_opt(&mut args, "--device-type", config.device_type.as_ref());
+        push_opt(&mut args, "--cache-path", config.cache_path.as_ref());
+        push_opt(&mut args, "--initial-volume", config.initial_volume);
+        if config.volume_normalisation == Some(true) {
+            args.push("--volume-normalisation".to_string());
+        }
+        if config.autoplay == Some(true) {
+            args.push("--autoplay".to_string());
+        }
+    }
+
+    push_opt(&mut args, "--device", config.device.as_ref());
+    push_opt(&mut args, "--mixer", config.mixer.as_ref());
+    push_opt(&mut args, "--volume-controller", config.volume_controller.as_ref());
+    push_opt(&mut args, "--zeroconf-port", config.zeroconf_port);
+    push_opt(&mut args, "--use-mpris", config.use_mpris);
+    push_opt(&mut args, "--dbus-type", config.dbus_type.as_ref());
+
+    if let Some(ref extra) = config.extra_args {
+        args.extend(extra.iter().cloned());
+    }
+
+    args
+}
+
+fn validate_env_name(name: &str) -> Result<(), MprisError> {
+    if name.is_empty() || name.contains('=') || name.contains('\0') {
+        return Err(MprisError::InvalidConfig(format!(
+            "invalid environment variable name {:?}",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Check the names in `env` and `env_remove`, and the values in `env`
+fn validate_env(config: &SpotifydConfig) -> Result<(), MprisError> {
+    let removed = config.env_remove.as_deref().unwrap_or_default();
+    let overrides = config.env.as_ref();
+
+    for name in removed.iter().chain(overrides.into_iter().flat_map(|env| env.keys())) {
+        validate_env_name(name)?;
+    }
+    if let Some((name, _)) = overrides
+        .into_iter()
+        .flatten()
+        .find(|(_, value)| value.contains('\0'))
+    {
+        return Err(MprisError::InvalidConfig(format!(
+            "environment variable {} contains a NUL byte",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Environment spotifyd is started with: `base` (normally our own
+/// environment) minus `env_remove`, then with `env` applied on top.
+pub fn environment(
+    config: &SpotifydConfig,
+    base: impl IntoIterator<Item = (OsString, OsString)>,
+) -> Result<Vec<(OsString, OsString)>, MprisError> {
+    validate_env(config)?;
+    let removed = config.env_remove.as_deref().unwrap_or_default();
+    let overrides = config.env.as_ref();
+
+    let mut vars: Vec<(OsString, OsString)> = base
+        .into_iter()
+        .filter(|(name, _)| {
+            !removed.iter().any(|r| name.as_os_str() == r.as_str())
+                && !overrides.is_some_and(|env| name.to_str().is_some_and(|n| env.contains_key(n)))
+        })
+        .collect();
+
+    if let Some(env) = overrides {
+        let mut sorted: Vec<_> = env.iter().collect();
+        sorted.sort();
+        vars.extend(sorted.into_iter().map(|(k, v)| (k.into(), v.into())));
+    }
+
+    Ok(vars)
+}
+
+/// Where the generated config lives (~/.spotify-tui/spotifyd.conf)
+pub fn generated_config_path() -> Option<PathBuf> {
+    spotify_tui_dir().map(|dir| dir.join(GENERATED_CONFIG_FILE))
+}
+
+/// Whether any setting has to go into a config file of ours. The device
+/// name doesn't count: it is always passed as a flag.
+pub fn needs_generated_config(config: &SpotifydConfig) -> bool {
+    config.username.is_some()
+        || config.password.is_some()
+        || config.bitrate.is_some()
+        || config.backend.is_some()
+        || config.device_type.is_some()
+        || config.cache_path.is_some()
+        || config.volume_normalisation.is_some()
+        || config.initial_volume.is_some()
+        || config.autoplay.is_some()
+}
+
+/// The config file spotifyd reads when not given `--config-path`
+pub fn user_config_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    config_home
+        .map(|dir| dir.join("spotifyd").join("spotifyd.conf"))
+        .into_iter()
+        .chain([PathBuf::from("/etc/spotifyd.conf")])
+        .find(|path| path.is_file())
+}
+
+/// `base` (the user's own config file, if any) with our settings laid over
+/// it, so whatever we don't set still comes from there
+fn overlay_config(config: &SpotifydConfig, base: Option<&Path>) -> Result<String, MprisError> {
+    let ours = SpotifydToml::from_config(config)?;
+    let Some(base) = base else {
+        return ours.render();
+    };
+
+    let invalid = |e: &dyn std::fmt::Display| {
+        MprisError::InvalidConfig(format!("{}: {}", base.display(), e))
+    };
+    let mut table: toml::Table = toml::from_str(&std::fs::read_to_string(base)?)
+        .map_err(|e| invalid(&e))?;
+    let ours = toml::Table::try_from(ours.global).map_err(|e| invalid(&e))?;
+
+    // spotifyd reads both sections, [spotifyd] winning, so set ours in each
+    let mut sections: Vec<&str> = ["global", "spotifyd"]
+        .into_iter()
+        .filter(|name| table.contains_key(*name))
+        .collect();
+    if sections.is_empty() {
+        sections.push("global");
+    }
+    for name in sections {
+        let section = table
+            .entry(name)
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| invalid(&format!("[{}] is not a table", name)))?;
+        section.extend(ours.clone());
+    }
+    toml::to_string(&table).map_err(|e| invalid(&e))
+}
+
+/// Render our settings over `base` to `path`. The file may contain a
+/// password, so it is created owner-only and replaced atomically.
+pub fn write_config(
+    config: &SpotifydConfig,
+    path: &Path,
+    base: Option<&Path>,
+) -> Result<(), MprisError> {
+    let contents = overlay_config(config, base)?;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let tmp = path.with_extension("conf.tmp");
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn full_config() -> SpotifydConfig {
+        SpotifydConfig {
+            username: Some("user".to_string()),
+            password: Some("p\"a\\ss".to_string()),
+            device_name: Some("My \"Living\" Room".to_string()),
+            bitrate: Some(320),
+            backend: Some("pulseaudio".to_string()),
+            device_type: Some("speaker".to_string()),
+            cache_path: Some("/home/user/.cache/spotifyd".to_string()),
+            volume_normalisation: Some(true),
+            initial_volume: Some(75),
+            autoplay: Some(false),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rendered_config_round_trips() {
+        let expected = SpotifydToml::from_config(&full_config()).unwrap();
+        let rendered = expected.render().unwrap();
+
+        assert!(rendered.starts_with("[global]"));
+        let parsed: SpotifydToml = toml::from_str(&rendered).unwrap();
+        assert_eq!(parsed, expected);
+        assert_eq!(parsed.global.device_name.as_deref(), Some("My \"Living\" Room"));
+        assert_eq!(parsed.global.password.as_deref(), Some("p\"a\\ss"));
+    }
+
+    #[test]
+    fn unset_fields_are_omitted() {
+        let config = SpotifydConfig {
+            device_name: Some("spotify-tui".to_string()),
+            ..Default::default()
+        };
+        let rendered = SpotifydToml::from_config(&config).unwrap().render().unwrap();
+
+        assert_eq!(rendered.trim(), "[global]\ndevice_name = \"spotify-tui\"");
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let bad_bitrate = SpotifydConfig {
+            bitrate: Some(128),
+            ..Default::default()
+        };
+        assert!(matches!(
+            SpotifydToml::from_config(&bad_bitrate),
+            Err(MprisError::InvalidConfig(_))
+        ));
+
+        let bad_volume = SpotifydConfig {
+            initial_volume: Some(101),
+            ..Default::default()
+        };
+        assert!(matches!(
+            SpotifydToml::from_config(&bad_volume),
+            Err(MprisError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn written_config_is_private_and_parses() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("spotifyd.conf");
+
+        write_config(&full_config(), &path, None).unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let parsed: SpotifydToml = toml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(parsed, SpotifydToml::from_config(&full_config()).unwrap());
+    }
+
+    #[test]
+    fn overlays_the_users_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("user.conf");
+        std::fs::write(
+            &base,
+            "[global]\nbackend = \"alsa\"\nbitrate = 160\n\
+             on_song_change_hook = \"notify\"\n\n[spotifyd]\nbitrate = 96\n",
+        )
+        .unwrap();
+        let path = dir.path().join("spotifyd.conf");
+        let config = SpotifydConfig {
+            bitrate: Some(320),
+            ..Default::default()
+        };
+
+        write_config(&config, &path, Some(&base)).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        let written: toml::Table = toml::from_str(&written).unwrap();
+        assert_eq!(written["global"]["backend"].as_str(), Some("alsa"));
+        assert_eq!(written["global"]["on_song_change_hook"].as_str(), Some("notify"));
+        assert_eq!(written["global"]["bitrate"].as_integer(), Some(320));
+        assert_eq!(written["spotifyd"]["bitrate"].as_integer(), Some(320));
+
+        std::fs::write(&base, "global = 1").unwrap();
+        assert!(matches!(
+            write_config(&config, &path, Some(&base)),
+            Err(MprisError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn only_typed_settings_need_a_generated_config() {
+        let name_only = SpotifydConfig {
+            device_name: Some("spotify-tui".to_string()),
+            mixer: Some("PCM".to_string()),
+            ..Default::default()
+        };
+        assert!(!needs_generated_config(&name_only));
+        assert!(needs_generated_config(&full_config()));
+    }
+
+    #[test]
+    fn command_line_depends_on_config_source() {
+        let mut config = SpotifydConfig {
+            mixer: Some("PCM".to_string()),
+            zeroconf_port: Some(5354),
+            use_mpris: Some(true),
+            extra_args: Some(vec!["--verbose".to_string()]),
+            ..full_config()
+        };
+
+        // Generated config already carries bitrate etc.; only flag-only options are passed
+        let generated = command_line_args(&config, Some(Path::new("/tmp/gen.conf")));
+        assert_eq!(
+            generated,
+            [
+                "--no-daemon", "--config-path", "/tmp/gen.conf",
+                "--device-name", "My \"Living\" Room",
+                "--mixer", "PCM", "--zeroconf-port", "5354", "--use-mpris", "true",
+                "--verbose",
+            ]
+        );
+
+        // A user-supplied config gets the typed settings as overriding flags
+        config.config_path = Some("/home/user/spotifyd.conf".to_string());
+        let user = command_line_args(&config, Some(Path::new("/home/user/spotifyd.conf")));
+        assert!(user.windows(2).any(|w| w == ["--bitrate", "320"]));
+        assert!(user.contains(&"--volume-normalisation".to_string()));
+        assert!(!user.contains(&"--autoplay".to_string()));
+        assert_eq!(user.last().map(String::as_str), Some("--verbose"));
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        let bad = [
+            SpotifydConfig {
+                dbus_type: Some("user".to_string()),
+                ..Default::default()
+            },
+            SpotifydConfig {
+                zeroconf_port: Some(70000),
+                ..Default::default()
+            },
+            SpotifydConfig {
+                bitrate: Some(123),
+                ..Default::default()
+            },
+            SpotifydConfig {
+                env_remove: Some(vec!["A=B".to_string()]),
+                ..Default::default()
+            },
+        ];
+        for config in bad {
+            assert!(matches!(validate(&config), Err(MprisError::InvalidConfig(_))), "{:?}", config);
+        }
+        assert!(validate(&SpotifydConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn environment_applies_removals_then_overrides() {
+        let base = [("PATH", "/usr/bin"), ("RUST_LOG", "debug"), ("http_proxy", "http://proxy:3128")]
+            .map(|(k, v)| (OsString::from(k), OsString::from(v)));
+        let config = SpotifydConfig {
+            env: Some(HashMap::from([
+                ("RUST_LOG".to_string(), "info".to_string()),
+                ("PULSE_SERVER".to_string(), "unix:/run/pulse".to_string()),
+            ])),
+            env_remove: Some(vec!["http_proxy".to_string(), "NOT_SET".to_string()]),
+            ..Default::default()
+        };
+
+        let vars = environment(&config, base).unwrap();
+        let vars: Vec<(&str, &str)> = vars
+            .iter()
+            .map(|(k, v)| (k.to_str().unwrap(), v.to_str().unwrap()))
+            .collect();
+        assert_eq!(
+            vars,
+            [("PATH", "/usr/bin"), ("PULSE_SERVER", "unix:/run/pulse"), ("RUST_LOG", "info")]
+        );
+    }
+
+    #[test]
+    fn environment_rejects_invalid_names() {
+        for name in ["", "A=B", "A\0B"] {
+            let config = SpotifydConfig {
+                env_remove: Some(vec![name.to_string()]),
+                ..Default::default()
+            };
+            assert!(matches!(
+                environment(&config, Vec::new()),
+                Err(MprisError::InvalidConfig(_))
+            ));
+        }
+    }
+}
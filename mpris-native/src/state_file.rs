@@ -0,0 +1,274 @@
+use crate::procfs::{self, ProcessIdentity};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Supervisor state persisted across TUI restarts, so a spotifyd we spawned
+/// (which outlives us thanks to the double fork) is still recognised as ours
+const STATE_FILE: &str = "spotifyd-state.json";
+/// Lock file guarding read-modify-write of the state file between TUI instances
+const LOCK_FILE: &str = "spotifyd-state.lock";
+
+/// A spotifyd process we spawned, identified by PID and start time so a
+/// recycled PID isn't mistaken for it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpawnedDaemon {
+    pub pid: u32,
+    pub start_ticks: u64,
+    pub binary_path: String,
+    pub args: Vec<String>,
+    pub device_name: Option<String>,
+    /// Unix timestamp (seconds) when we spawned it
+    pub spawned_at: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SupervisorState {
+    #[serde(default)]
+    spawned: Vec<SpawnedDaemon>,
+}
+
+impl SpawnedDaemon {
+    /// Whether the recorded process is still the one running under this PID
+    pub fn is_live(&self) -> bool {
+        procfs::read_stat(self.pid)
+            .is_some_and(|stat| stat.state != 'Z' && stat.start_ticks == self.start_ticks)
+    }
+
+    pub fn matches(&self, process: &ProcessIdentity) -> bool {
+        self.pid == process.pid && self.start_ticks == process.start_ticks
+    }
+}
+
+/// ~/.spotify-tui, where all persistent native-module state lives
+pub fn spotify_tui_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".spotify-tui"))
+}
+
+/// Advisory flock on the lock file, released on drop
+struct StateLock {
+    _file: File,
+}
+
+impl StateLock {
+    fn acquire(dir: &Path, exclusive: bool) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join(LOCK_FILE))?;
+        let op = if exclusive { libc::LOCK_EX } else { libc::LOCK_SH };
+        loop {
+            if unsafe { libc::flock(file.as_raw_fd(), op) } == 0 {
+                break;
+            }
+            let err = std::io::Error::last_os_error();
+            if err.kind() != std::io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+        Ok(Self { _file: file })
+    }
+}
+
+fn read_state(dir: &Path) -> SupervisorState {
+    std::fs::read(dir.join(STATE_FILE))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+fn write_state(dir: &Path, state: &SupervisorState) -> std::io::Result<()> {
+    let path = dir.join(STATE_FILE);
+
+    // Write to a temp file and rename so readers never see a partial file
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(state)?)?;
+    std::fs::rename(&tmp, &path)
+}
+
+/// Read-modify-write the state under an exclusive lock, dropping dead entries
+fn update_state(dir: &Path, f: impl FnOnce(&mut SupervisorState)) -> std::io::Result<()> {
+    let _lock = StateLock::acquire(dir, true)?;
+    let mut state = read_state(dir);
+    state.spawned.retain(|d| d.is_live());
+    f(&mut state);
+    write_state(dir, &state)
+}
+
+fn load_spawned_in(dir: &Path) -> Vec<SpawnedDaemon> {
+    let _lock = match StateLock::acquire(dir, false) {
+        Ok(lock) => lock,
+        Err(e) => {
+            warn!("Failed to lock supervisor state: {}", e);
+            return Vec::new();
+        }
+    };
+    read_state(dir)
+        .spawned
+        .into_iter()
+        .filter(|d| d.is_live())
+        .collect()
+}
+
+/// Load records for spawned processes that are still alive
+pub fn load_spawned() -> Vec<SpawnedDaemon> {
+    spotify_tui_dir()
+        .map(|dir| load_spawned_in(&dir))
+        .unwrap_or_default()
+}
+
+/// Find the record for a process, if we spawned it
+pub fn find_spawned(process: &ProcessIdentity) -> Option<SpawnedDaemon> {
+    load_spawned().into_iter().find(|d| d.matches(process))
+}
+
+fn record_spawn_in(dir: &Path, daemon: SpawnedDaemon) -> std::io::Result<()> {
+    update_state(dir, |state| {
+        state.spawned.retain(|d| d.pid != daemon.pid);
+        state.spawned.push(daemon);
+    })
+}
+
+/// Record a process we just spawned
+pub fn record_spawn(daemon: SpawnedDaemon) {
+    let Some(dir) = spotify_tui_dir() else {
+        return;
+    };
+    if let Err(e) = record_spawn_in(&dir, daemon) {
+        warn!("Failed to persist spotifyd spawn record: {}", e);
+    }
+}
+
+fn remove_spawn_in(dir: &Path, process: &ProcessIdentity) -> std::io::Result<()> {
+    update_state(dir, |state| state.spawned.retain(|d| !d.matches(process)))
+}
+
+/// Forget a process we spawned, e.g. after stopping it
+pub fn remove_spawn(process: &ProcessIdentity) {
+    let Some(dir) = spotify_tui_dir() else {
+        return;
+    };
+    if let Err(e) = remove_spawn_in(&dir, process) {
+        warn!("Failed to update supervisor state: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::{Child, Command};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    /// A live process to record, killed on drop
+    struct Sleeper(Child);
+
+    impl Sleeper {
+        fn start() -> Self {
+            Self(Command::new("sleep").arg("30").spawn().unwrap())
+        }
+
+        fn identity(&self) -> ProcessIdentity {
+            ProcessIdentity::capture(self.0.id()).unwrap()
+        }
+
+        fn record(&self) -> SpawnedDaemon {
+            let identity = self.identity();
+            SpawnedDaemon {
+                pid: identity.pid,
+                start_ticks: identity.start_ticks,
+                binary_path: "/usr/bin/sleep".to_string(),
+                args: vec!["30".to_string()],
+                device_name: Some("spotify-tui".to_string()),
+                spawned_at: 1,
+            }
+        }
+    }
+
+    impl Drop for Sleeper {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    #[test]
+    fn records_persist_until_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (Sleeper::start(), Sleeper::start());
+        record_spawn_in(dir.path(), a.record()).unwrap();
+        record_spawn_in(dir.path(), b.record()).unwrap();
+
+        // A fresh read, as a later TUI session would do
+        assert_eq!(load_spawned_in(dir.path()), [a.record(), b.record()]);
+        remove_spawn_in(dir.path(), &a.identity()).unwrap();
+        assert_eq!(load_spawned_in(dir.path()), [b.record()]);
+    }
+
+    #[test]
+    fn dead_and_recycled_entries_are_reclaimed() {
+        let dir = tempfile::tempdir().unwrap();
+        let live = Sleeper::start();
+        let dead = {
+            let gone = Sleeper::start();
+            gone.record()
+        };
+        // Same PID as a live process, different start time: a recycled PID
+        let recycled = SpawnedDaemon {
+            start_ticks: live.record().start_ticks + 1,
+            ..live.record()
+        };
+        let state = SupervisorState {
+            spawned: vec![dead.clone(), recycled],
+        };
+        write_state(dir.path(), &state).unwrap();
+        assert_eq!(load_spawned_in(dir.path()), []);
+
+        // The next write drops them from the file too
+        record_spawn_in(dir.path(), live.record()).unwrap();
+        assert_eq!(read_state(dir.path()).spawned, [live.record()]);
+    }
+
+    #[test]
+    fn matches_only_the_same_process_instance() {
+        let sleeper = Sleeper::start();
+        let record = sleeper.record();
+        let identity = sleeper.identity();
+        assert!(record.matches(&identity));
+        assert!(!record.matches(&ProcessIdentity {
+            start_ticks: identity.start_ticks + 1,
+            ..identity.clone()
+        }));
+        assert!(!record.matches(&ProcessIdentity {
+            pid: identity.pid + 1,
+            ..identity
+        }));
+    }
+
+    #[test]
+    fn writers_wait_for_the_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let sleeper = Sleeper::start();
+        let held = StateLock::acquire(dir.path(), false).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let path = dir.path().to_path_buf();
+        let record = sleeper.record();
+        let writer = std::thread::spawn(move || {
+            record_spawn_in(&path, record).unwrap();
+            tx.send(()).unwrap();
+        });
+        // flock locks belong to the open file, so a second open in this
+        // process still blocks behind the shared lock
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+        drop(held);
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        writer.join().unwrap();
+        assert_eq!(load_spawned_in(dir.path()), [sleeper.record()]);
+    }
+}
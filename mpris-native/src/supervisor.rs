@@ -1,49 +1,31 @@
+use crate::auth;
+use crate::binary;
+use crate::bus::open_bus;
+use crate::controller::ControllerInner;
 use crate::error::MprisError;
-use crate::types::{SpotifydConfig, SpotifydStartResult, SpotifydStatus};
-use std::os::unix::process::CommandExt;
-use std::path::PathBuf;
-use std::process::Stdio;
-use std::time::Duration;
+use crate::installer::{self, InstallRequest, InstallSource};
+use crate::procfs::{self, ProcessIdentity};
+use crate::spotifyd_config;
+use crate::state_file::{self, SpawnedDaemon};
+use crate::types::{
+    AuthProgress, AuthenticateOptions, AuthenticateResult, InstallOptions, InstallProgress,
+    InstallResult, InstallStage, SkippedProcess, SpotifydAuthState, SpotifydCandidate,
+    SpotifydConfig, SpotifydDetection, SpotifydProcessInfo, SpotifydRestartResult,
+    SpotifydStartResult, SpotifydStartStage, SpotifydStatus, SpotifydStopPolicy,
+    SpotifydStopResult, SpotifydStopStage,
+};
+use futures::StreamExt;
+use std::ffi::{CString, OsString};
+use std::io::Read;
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::{watch, RwLock};
 use tracing::{debug, error, info, instrument, warn};
-use zbus::Connection;
-
-/// Find the spotifyd binary path
-/// Priority: 1. Custom config path 2. Environment variable 3. Downloaded binary 4. System PATH
-fn find_spotifyd_binary(config: &SpotifydConfig) -> String {
-    // 1. Check for custom path in config
-    if let Some(ref path) = config.config_path {
-        if std::path::Path::new(path).exists() {
-            return path.clone();
-        }
-    }
-
-    // 2. Check for environment variable
-    if let Ok(path) = std::env::var("SPOTIFY_TUI_SPOTIFYD_PATH") {
-        if std::path::Path::new(&path).exists() {
-            return path;
-        }
-    }
-
-    // 3. Check for downloaded binary at ~/.spotify-tui/bin/spotifyd
-    if let Some(home) = std::env::var_os("HOME") {
-        let downloaded_path: PathBuf = [
-            home.to_string_lossy().as_ref(),
-            ".spotify-tui",
-            "bin",
-            "spotifyd",
-        ]
-        .iter()
-        .collect();
-
-        if downloaded_path.exists() {
-            return downloaded_path.to_string_lossy().to_string();
-        }
-    }
-
-    // 4. Fall back to system PATH
-    "spotifyd".to_string()
-}
+use zbus::{proxy, Connection};
 
 /// Check if a process with given PID is alive (not a zombie)
 fn is_pid_alive(pid: u32) -> bool {
@@ -63,79 +45,664 @@ fn is_pid_alive(pid: u32) -> bool {
     }
 }
 
-/// Kill a process by PID using SIGTERM, then SIGKILL if needed
-async fn kill_pid(pid: u32) -> bool {
-    info!("Killing spotifyd process with PID {}", pid);
+/// Root MPRIS interface, used to ask spotifyd to quit
+#[proxy(
+    interface = "org.mpris.MediaPlayer2",
+    default_path = "/org/mpris/MediaPlayer2"
+)]
+trait MediaPlayer2 {
+    fn quit(&self) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn can_quit(&self) -> zbus::Result<bool>;
+}
+
+#[proxy(
+    interface = "org.mpris.MediaPlayer2.Player",
+    default_path = "/org/mpris/MediaPlayer2"
+)]
+trait MprisPlayer {
+    fn pause(&self) -> zbus::Result<()>;
+}
+
+/// Where spotifyd (via libdbus/zbus) finds the session bus
+const BUS_ADDRESS_ENV: &str = "DBUS_SESSION_BUS_ADDRESS";
+
+/// Limit for each MPRIS call made while stopping
+const STOP_CALL_TIMEOUT: Duration = Duration::from_secs(1);
+const DEFAULT_QUIT_TIMEOUT: Duration = Duration::from_secs(3);
+const DEFAULT_TERM_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long to wait for the kernel to reap a SIGKILLed process
+const KILL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Resolved stop policy
+struct StopSequence {
+    pause: bool,
+    mpris_quit: bool,
+    quit_timeout: Duration,
+    term_timeout: Duration,
+}
+
+impl StopSequence {
+    fn new(policy: Option<&SpotifydStopPolicy>) -> Self {
+        let policy = policy.cloned().unwrap_or_default();
+        let ms = |v: Option<u32>, default| {
+            v.map(|ms| Duration::from_millis(ms as u64))
+                .unwrap_or(default)
+        };
+        Self {
+            pause: policy.pause.unwrap_or(true),
+            mpris_quit: policy.mpris_quit.unwrap_or(true),
+            quit_timeout: ms(policy.quit_timeout_ms, DEFAULT_QUIT_TIMEOUT),
+            term_timeout: ms(policy.term_timeout_ms, DEFAULT_TERM_TIMEOUT),
+        }
+    }
+
+    /// SIGTERM then SIGKILL, for processes that aren't ours to talk to
+    fn signals_only() -> Self {
+        Self {
+            pause: false,
+            mpris_quit: false,
+            quit_timeout: Duration::ZERO,
+            term_timeout: DEFAULT_TERM_TIMEOUT,
+        }
+    }
+}
+
+/// Poll until the process is gone or the timeout passes
+async fn wait_for_exit(process: &ProcessIdentity, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while process.is_alive() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(EXIT_POLL_INTERVAL).await;
+    }
+    true
+}
+
+/// MPRIS name owned by `pid`, on a fresh session connection
+async fn mpris_name_of(pid: u32, bus: Option<&str>) -> Option<(Connection, String)> {
+    let conn = open_bus(bus).await.ok()?;
+    let dbus = zbus::fdo::DBusProxy::new(&conn).await.ok()?;
+    let names = dbus.list_names().await.ok()?;
+    for name in names.iter() {
+        if name.as_str().starts_with("org.mpris.MediaPlayer2.spotifyd")
+            && name_owned_by(&dbus, name.as_str(), pid).await
+        {
+            return Some((conn, name.to_string()));
+        }
+    }
+    None
+}
+
+/// Result of an MPRIS call, or None if it failed or timed out
+async fn stop_call<T>(call: impl std::future::Future<Output = zbus::Result<T>>) -> Option<T> {
+    tokio::time::timeout(STOP_CALL_TIMEOUT, call).await.ok()?.ok()
+}
+
+/// Pause playback, then call Quit if spotifyd says it supports it.
+/// Returns (paused, quit requested).
+async fn mpris_shutdown(pid: u32, sequence: &StopSequence, bus: Option<&str>) -> (bool, bool) {
+    let Some((conn, name)) = mpris_name_of(pid, bus).await else {
+        debug!("spotifyd {} has no MPRIS name, skipping to signals", pid);
+        return (false, false);
+    };
+    let mut paused = false;
+    if sequence.pause {
+        if let Ok(player) = MprisPlayerProxy::builder(&conn).destination(name.clone()) {
+            if let Ok(player) = player.build().await {
+                paused = stop_call(player.pause()).await.is_some();
+            }
+        }
+    }
 
-    // Try SIGTERM first
-    unsafe {
-        if libc::kill(pid as i32, libc::SIGTERM) == 0 {
-            // Wait up to 2 seconds for graceful shutdown
-            for _ in 0..20 {
-                tokio::time::sleep(Duration::from_millis(100)).await;
-                if !is_pid_alive(pid) {
-                    info!("spotifyd {} terminated gracefully", pid);
-                    return true;
+    let mut quit = false;
+    if sequence.mpris_quit {
+        if let Ok(root) = MediaPlayer2Proxy::builder(&conn).destination(name) {
+            if let Ok(root) = root.build().await {
+                if stop_call(root.can_quit()).await == Some(true) {
+                    quit = stop_call(root.quit()).await.is_some();
+                } else {
+                    debug!("spotifyd {} doesn't support MPRIS Quit", pid);
                 }
             }
+        }
+    }
+    (paused, quit)
+}
+
+/// Stop a process step by step: MPRIS pause and Quit, SIGTERM, SIGKILL.
+/// The process identity is re-verified before every signal, so a recycled
+/// PID is never hit.
+async fn terminate(
+    process: &ProcessIdentity,
+    sequence: &StopSequence,
+    bus: Option<&str>,
+) -> SpotifydStopResult {
+    let started = Instant::now();
+    info!("Stopping spotifyd process with PID {}", process.pid);
+
+    let mut paused = false;
+    if sequence.pause || sequence.mpris_quit {
+        let (did_pause, quit) = mpris_shutdown(process.pid, sequence, bus).await;
+        paused = did_pause;
+        if quit && wait_for_exit(process, sequence.quit_timeout).await {
+            return stop_result(process, started, paused, Ok(SpotifydStopStage::MprisQuit));
+        }
+    }
+    let outcome = escalate_signals(process, sequence.term_timeout).await;
+    stop_result(process, started, paused, outcome)
+}
 
-            // Force kill with SIGKILL
-            warn!("spotifyd {} didn't terminate, sending SIGKILL", pid);
-            if libc::kill(pid as i32, libc::SIGKILL) == 0 {
-                tokio::time::sleep(Duration::from_millis(100)).await;
-                return !is_pid_alive(pid);
+/// SIGTERM, then SIGKILL once `term_timeout` passes
+async fn escalate_signals(
+    process: &ProcessIdentity,
+    term_timeout: Duration,
+) -> Result<SpotifydStopStage, String> {
+    let pid = process.pid;
+    match process.signal(libc::SIGTERM) {
+        Ok(true) => {}
+        Ok(false) => return Ok(SpotifydStopStage::AlreadyExited),
+        Err(e) => return Err(format!("failed to send SIGTERM: {}", e)),
+    }
+    if wait_for_exit(process, term_timeout).await {
+        return Ok(SpotifydStopStage::Sigterm);
+    }
+
+    warn!("spotifyd {} didn't terminate, sending SIGKILL", pid);
+    match process.signal(libc::SIGKILL) {
+        Ok(true) if wait_for_exit(process, KILL_TIMEOUT).await => Ok(SpotifydStopStage::Sigkill),
+        Ok(true) => Err("survived SIGKILL".to_string()),
+        // Exited between the timeout and the signal
+        Ok(false) => Ok(SpotifydStopStage::Sigterm),
+        Err(e) => Err(format!("failed to send SIGKILL: {}", e)),
+    }
+}
+
+fn stop_result(
+    process: &ProcessIdentity,
+    started: Instant,
+    paused: bool,
+    outcome: Result<SpotifydStopStage, String>,
+) -> SpotifydStopResult {
+    let message = match outcome {
+        Ok(SpotifydStopStage::AlreadyExited) => "spotifyd had already exited".to_string(),
+        Ok(SpotifydStopStage::MprisQuit) => "spotifyd quit via MPRIS".to_string(),
+        Ok(SpotifydStopStage::Sigterm) => "spotifyd exited after SIGTERM".to_string(),
+        Ok(SpotifydStopStage::Sigkill) => "spotifyd killed with SIGKILL".to_string(),
+        Err(ref e) => format!("Failed to stop spotifyd {}: {}", process.pid, e),
+    };
+    match outcome {
+        Ok(_) => info!("{} (PID {})", message, process.pid),
+        Err(_) => warn!("{}", message),
+    }
+    SpotifydStopResult {
+        stopped: outcome.is_ok(),
+        pid: Some(process.pid),
+        stage: outcome.ok(),
+        paused,
+        elapsed_ms: started.elapsed().as_millis() as u32,
+        message,
+    }
+}
+
+/// Kill a process using SIGTERM, then SIGKILL if needed. Returns true once
+/// the process is gone.
+async fn kill_process(process: &ProcessIdentity) -> bool {
+    terminate(process, &StopSequence::signals_only(), None)
+        .await
+        .stopped
+}
+
+// Frame tags written to the spawn status pipe. Each frame is one tag byte
+// followed by a native-endian i32 payload.
+const SPAWN_MSG_PID: u8 = b'P';
+const SPAWN_MSG_FORK_ERRNO: u8 = b'F';
+const SPAWN_MSG_EXEC_ERRNO: u8 = b'E';
+const SPAWN_MSG_CHDIR_ERRNO: u8 = b'D';
+
+/// Write one status frame to the spawn pipe (async-signal-safe)
+unsafe fn write_spawn_msg(fd: libc::c_int, tag: u8, value: i32) {
+    let mut buf = [0u8; 5];
+    buf[0] = tag;
+    buf[1..].copy_from_slice(&value.to_ne_bytes());
+    libc::write(fd, buf.as_ptr() as *const libc::c_void, buf.len());
+}
+
+fn last_errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap_or(0)
+}
+
+/// Environment, working directory and output capture for a spawned process
+#[derive(Debug, Default)]
+struct SpawnOptions {
+    env: Vec<(OsString, OsString)>,
+    working_dir: Option<String>,
+    /// File stdout/stderr are redirected to (truncated), instead of /dev/null
+    log_file: Option<PathBuf>,
+}
+
+/// Open (and truncate) the file a spawned process logs to. It is opened for
+/// appending so `cap_log` can cut it back while the process writes to it.
+fn open_log_file(path: &Path) -> std::io::Result<std::fs::File> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let file = std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .mode(0o600)
+        .open(path)?;
+    file.set_len(0)?;
+    Ok(file)
+}
+
+/// Spawn a fully detached process via double fork and return the grandchild PID.
+///
+/// The grandchild PID and any fork/chdir/exec failure are reported back over
+/// a CLOEXEC pipe: a successful exec closes the write end without sending
+/// anything, so reading to EOF tells us whether the binary actually started.
+fn spawn_detached(binary: &str, args: &[String], options: &SpawnOptions) -> Result<u32, MprisError> {
+    // Everything the children need is allocated up front - only
+    // async-signal-safe calls are allowed between fork and exec.
+    let to_cstring = |s: &[u8]| {
+        CString::new(s).map_err(|_| {
+            MprisError::ProcessSpawn(format!(
+                "argument contains NUL byte: {:?}",
+                String::from_utf8_lossy(s)
+            ))
+        })
+    };
+    let c_binary = to_cstring(binary.as_bytes())?;
+    let c_args = std::iter::once(Ok(c_binary.clone()))
+        .chain(args.iter().map(|a| to_cstring(a.as_bytes())))
+        .collect::<Result<Vec<_>, _>>()?;
+    let c_argv: Vec<*const libc::c_char> = c_args
+        .iter()
+        .map(|s| s.as_ptr())
+        .chain(std::iter::once(std::ptr::null()))
+        .collect();
+
+    let c_env = options
+        .env
+        .iter()
+        .map(|(name, value)| {
+            let mut entry = name.as_bytes().to_vec();
+            entry.push(b'=');
+            entry.extend_from_slice(value.as_bytes());
+            to_cstring(&entry)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let c_envp: Vec<*const libc::c_char> = c_env
+        .iter()
+        .map(|s| s.as_ptr())
+        .chain(std::iter::once(std::ptr::null()))
+        .collect();
+
+    let c_working_dir = options
+        .working_dir
+        .as_deref()
+        .map(|dir| to_cstring(dir.as_bytes()))
+        .transpose()?;
+
+    // Opened CLOEXEC; the grandchild dup2s it onto stdout/stderr before exec
+    let log_file = options.log_file.as_deref().and_then(|path| match open_log_file(path) {
+        Ok(file) => Some(file),
+        Err(e) => {
+            warn!("Failed to open spotifyd log {}: {}", path.display(), e);
+            None
+        }
+    });
+    let log_fd = log_file.as_ref().map_or(-1, |file| file.as_raw_fd());
+
+    let mut fds = [0 as libc::c_int; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(MprisError::Io(std::io::Error::last_os_error()));
+    }
+    let (read_fd, write_fd) = (fds[0], fds[1]);
+
+    // First fork
+    let pid = unsafe { libc::fork() };
+
+    if pid < 0 {
+        let err = std::io::Error::last_os_error();
+        unsafe {
+            libc::close(read_fd);
+            libc::close(write_fd);
+        }
+        return Err(MprisError::ProcessSpawn(format!("Fork failed: {}", err)));
+    }
+
+    if pid == 0 {
+        // First child - start a new session, fork the grandchild, report its PID and exit
+        unsafe {
+            libc::close(read_fd);
+            libc::setsid();
+
+            let pid2 = libc::fork();
+            if pid2 < 0 {
+                write_spawn_msg(write_fd, SPAWN_MSG_FORK_ERRNO, last_errno());
+                libc::_exit(1);
             }
+
+            if pid2 > 0 {
+                write_spawn_msg(write_fd, SPAWN_MSG_PID, pid2);
+                libc::_exit(0);
+            }
+
+            // Grandchild - this becomes the actual spotifyd process
+            // Redirect stdin to /dev/null, stdout and stderr to the log (or /dev/null)
+            let dev_null = libc::open(c"/dev/null".as_ptr(), libc::O_RDWR);
+            let output = if log_fd >= 0 { log_fd } else { dev_null };
+            if dev_null >= 0 {
+                libc::dup2(dev_null, libc::STDIN_FILENO);
+            }
+            if output >= 0 {
+                libc::dup2(output, libc::STDOUT_FILENO);
+                libc::dup2(output, libc::STDERR_FILENO);
+            }
+            if dev_null > libc::STDERR_FILENO {
+                libc::close(dev_null);
+            }
+
+            if let Some(ref dir) = c_working_dir {
+                if libc::chdir(dir.as_ptr()) != 0 {
+                    write_spawn_msg(write_fd, SPAWN_MSG_CHDIR_ERRNO, last_errno());
+                    libc::_exit(127);
+                }
+            }
+
+            libc::execvpe(c_binary.as_ptr(), c_argv.as_ptr(), c_envp.as_ptr());
+
+            // If exec returns, it failed - tell the parent why
+            write_spawn_msg(write_fd, SPAWN_MSG_EXEC_ERRNO, last_errno());
+            libc::_exit(127);
         }
     }
-    false
+
+    // Parent - close our write end so EOF arrives once both children are done with it
+    unsafe { libc::close(write_fd) };
+    drop(log_file);
+
+    // Reap the first child, which exits right after forking
+    let mut status: libc::c_int = 0;
+    while unsafe { libc::waitpid(pid, &mut status, 0) } < 0 && last_errno() == libc::EINTR {}
+
+    let mut reader = unsafe { std::fs::File::from_raw_fd(read_fd) };
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+
+    let mut grandchild_pid = None;
+    for frame in buf.chunks_exact(5) {
+        let value = i32::from_ne_bytes([frame[1], frame[2], frame[3], frame[4]]);
+        match frame[0] {
+            SPAWN_MSG_PID => grandchild_pid = Some(value as u32),
+            SPAWN_MSG_FORK_ERRNO => {
+                return Err(MprisError::ProcessSpawn(format!(
+                    "Second fork failed: {}",
+                    std::io::Error::from_raw_os_error(value)
+                )));
+            }
+            SPAWN_MSG_CHDIR_ERRNO => {
+                return Err(MprisError::ProcessSpawn(format!(
+                    "Failed to enter working directory {}: {}",
+                    options.working_dir.as_deref().unwrap_or_default(),
+                    std::io::Error::from_raw_os_error(value)
+                )));
+            }
+            SPAWN_MSG_EXEC_ERRNO => {
+                return Err(MprisError::ExecFailed {
+                    path: binary.to_string(),
+                    source: std::io::Error::from_raw_os_error(value),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    grandchild_pid.ok_or_else(|| MprisError::ProcessSpawn("Failed to get grandchild PID".to_string()))
+}
+
+/// Outcome of clearing stale spotifyd processes before a fresh start
+#[derive(Default)]
+struct KillReport {
+    killed: Vec<u32>,
+    skipped: Vec<SkippedProcess>,
+}
+
+/// How long a fresh spotifyd gets to register on D-Bus before we stop waiting
+const READY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Extra time allowed for MPRIS once the controls interface is registered
+const MPRIS_GRACE: Duration = Duration::from_millis(1000);
+/// How often to check whether a starting spotifyd has exited
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Outcome of spawning a fresh spotifyd
+pub struct FreshStart {
+    pub pid: u32,
+    /// Furthest readiness stage reached before returning
+    pub stage: SpotifydStartStage,
+    /// Time from spawn until spotifyd became usable, if it did
+    pub ready_after: Option<Duration>,
+}
+
+/// Map a D-Bus name to the startup stage its registration represents
+fn registration_stage(name: &str) -> Option<SpotifydStartStage> {
+    if name.starts_with("org.mpris.MediaPlayer2.spotifyd") {
+        Some(SpotifydStartStage::MprisRegistered)
+    } else if name.starts_with("rs.spotifyd.instance") {
+        Some(SpotifydStartStage::ControlsRegistered)
+    } else {
+        None
+    }
+}
+
+/// Check that a well-known name belongs to the given process.
+/// Errs on the side of accepting if the bus can't tell us.
+async fn name_owned_by(dbus: &zbus::fdo::DBusProxy<'_>, name: &str, pid: u32) -> bool {
+    let Ok(bus_name) = zbus::names::BusName::try_from(name) else {
+        return false;
+    };
+    match dbus.get_connection_unix_process_id(bus_name).await {
+        Ok(owner_pid) => owner_pid == pid,
+        Err(_) => true,
+    }
 }
 
-/// Find ALL spotifyd PIDs via pgrep
-async fn find_all_spotifyd_pids() -> Vec<u32> {
-    let output = tokio::process::Command::new("pgrep")
-        .arg("-x")
-        .arg("spotifyd")
-        .output()
-        .await;
+/// Next `NameOwnerChanged` signal, or pending forever if there is no stream
+async fn next_owner_change(
+    stream: &mut Option<zbus::fdo::NameOwnerChangedStream<'_>>,
+) -> Option<zbus::fdo::NameOwnerChanged> {
+    match stream {
+        Some(stream) => stream.next().await,
+        None => std::future::pending().await,
+    }
+}
 
-    match output {
-        Ok(out) if out.status.success() => {
-            let stdout = String::from_utf8_lossy(&out.stdout);
-            stdout
-                .lines()
-                .filter_map(|line| line.trim().parse::<u32>().ok())
-                .collect()
+/// Furthest D-Bus registration stage `pid` has reached, if any
+async fn registered_stage(pid: u32, bus: Option<&str>) -> Option<SpotifydStartStage> {
+    let conn = open_bus(bus).await.ok()?;
+    let dbus = zbus::fdo::DBusProxy::new(&conn).await.ok()?;
+    let names = dbus.list_names().await.ok()?;
+
+    let mut reached = None;
+    for name in names.iter() {
+        if let Some(stage) = registration_stage(name.as_str()) {
+            if Some(stage) > reached && name_owned_by(&dbus, name.as_str(), pid).await {
+                reached = Some(stage);
+            }
         }
-        _ => Vec::new(),
+    }
+    reached
+}
+
+/// Status for a running spotifyd
+fn running_status(
+    pid: u32,
+    argv: Vec<String>,
+    (auth_state, auth_error): (SpotifydAuthState, Option<String>),
+) -> SpotifydStatus {
+    SpotifydStatus {
+        running: true,
+        pid: Some(pid),
+        authenticated: auth_state == SpotifydAuthState::Authenticated,
+        auth_state,
+        auth_error,
+        argv,
     }
 }
 
-/// Kill ALL existing spotifyd processes
-async fn kill_all_spotifyd() -> usize {
-    let pids = find_all_spotifyd_pids().await;
-    let count = pids.len();
-    
-    if count > 0 {
-        info!("Killing {} existing spotifyd process(es)", count);
-        for pid in pids {
-            kill_pid(pid).await;
+/// Update the auth fields of the published status, if it still describes `pid`
+fn publish_auth(
+    status_tx: &watch::Sender<SpotifydStatus>,
+    pid: u32,
+    (auth_state, auth_error): (SpotifydAuthState, Option<String>),
+) {
+    status_tx.send_if_modified(|status| {
+        if status.pid != Some(pid)
+            || (status.auth_state == auth_state && status.auth_error == auth_error)
+        {
+            return false;
+        }
+        status.authenticated = auth_state == SpotifydAuthState::Authenticated;
+        status.auth_state = auth_state;
+        status.auth_error = auth_error;
+        true
+    });
+}
+
+/// How often the auth watcher re-checks spotifyd's log and liveness
+const AUTH_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Size at which the captured spotifyd log is emptied
+const LOG_MAX_BYTES: u64 = 1024 * 1024;
+/// How often the captured log's size is checked
+const LOG_CAP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Empty `log` whenever it grows past `max_bytes`, for as long as `process`
+/// runs. spotifyd writes with O_APPEND, so it carries on from the start.
+async fn cap_log(process: ProcessIdentity, log: PathBuf, max_bytes: u64, interval: Duration) {
+    let mut poll = tokio::time::interval(interval);
+    while process.is_alive() {
+        poll.tick().await;
+        let too_big = std::fs::metadata(&log).is_ok_and(|meta| meta.len() > max_bytes);
+        if too_big {
+            let emptied = std::fs::OpenOptions::new()
+                .write(true)
+                .open(&log)
+                .and_then(|file| file.set_len(0));
+            match emptied {
+                Ok(()) => debug!("Emptied spotifyd log {} past {} bytes", log.display(), max_bytes),
+                Err(e) => warn!("Could not empty spotifyd log {}: {}", log.display(), e),
+            }
+        }
+    }
+}
+
+/// Follow a spotifyd that hasn't logged in yet until its D-Bus names appear
+/// or its log reports a failed login, publishing the result. Ends quietly if
+/// the process exits.
+async fn watch_auth(
+    status_tx: Arc<watch::Sender<SpotifydStatus>>,
+    process: ProcessIdentity,
+    log: Option<PathBuf>,
+    bus: Option<String>,
+) {
+    let pid = process.pid;
+
+    let conn = open_bus(bus.as_deref()).await.ok();
+    let dbus = match conn.as_ref() {
+        Some(conn) => zbus::fdo::DBusProxy::new(conn).await.ok(),
+        None => None,
+    };
+    let mut owner_changes = match dbus.as_ref() {
+        Some(dbus) => dbus.receive_name_owner_changed().await.ok(),
+        None => None,
+    };
+
+    // Subscribed first, so a registration right now can't slip through
+    if registered_stage(pid, bus.as_deref()).await.is_some() {
+        publish_auth(&status_tx, pid, (SpotifydAuthState::Authenticated, None));
+        return;
+    }
+
+    let mut poll = tokio::time::interval(AUTH_POLL_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = poll.tick() => {
+                if !process.is_alive() {
+                    return;
+                }
+                if let Some(line) = log.as_deref().and_then(auth::read_auth_failure) {
+                    warn!("spotifyd {} failed to log in: {}", pid, line);
+                    publish_auth(&status_tx, pid, (SpotifydAuthState::Failed, Some(line)));
+                    return;
+                }
+            }
+            signal = next_owner_change(&mut owner_changes) => {
+                let Some(signal) = signal else {
+                    owner_changes = None;
+                    continue;
+                };
+                let Ok(args) = signal.args() else { continue };
+                if args.new_owner().is_none() {
+                    continue;
+                }
+                let name = args.name().as_str();
+                if registration_stage(name).is_none() {
+                    continue;
+                }
+                let owned = match dbus.as_ref() {
+                    Some(dbus) => name_owned_by(dbus, name, pid).await,
+                    None => true,
+                };
+                if owned {
+                    info!("spotifyd {} logged in", pid);
+                    publish_auth(&status_tx, pid, (SpotifydAuthState::Authenticated, None));
+                    return;
+                }
+            }
         }
-        // Wait a bit for all processes to fully terminate
-        tokio::time::sleep(Duration::from_millis(300)).await;
     }
-    
-    count
+}
+
+/// Install directory from the option, defaulting to ~/.spotify-tui/bin
+fn install_dir(dir: Option<&str>) -> Result<PathBuf, MprisError> {
+    match dir {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => installer::default_install_dir()
+            .ok_or_else(|| MprisError::InvalidConfig("HOME not set".to_string())),
+    }
+}
+
+/// A running `authenticate`
+struct AuthFlow {
+    cancel: Arc<tokio::sync::Notify>,
+    cancelled: bool,
 }
 
 pub struct SupervisorInner {
-    /// Child process handle (only if we spawned it)
-    spawned_child_pid: RwLock<Option<u32>>,
-    /// Adopted process PID (existing process we didn't spawn)
-    adopted_pid: RwLock<Option<u32>>,
+    /// Process we spawned, if any
+    spawned_child: RwLock<Option<ProcessIdentity>>,
+    /// Existing process we adopted but didn't spawn
+    adopted: RwLock<Option<ProcessIdentity>>,
     /// Status broadcast channel
-    status_tx: watch::Sender<SpotifydStatus>,
-    /// Configuration
-    config: SpotifydConfig,
+    status_tx: Arc<watch::Sender<SpotifydStatus>>,
+    /// Background task waiting for the tracked spotifyd to log in
+    auth_watch: std::sync::Mutex<Option<tokio::task::AbortHandle>>,
+    /// Background task keeping the captured spotifyd log small
+    log_cap: std::sync::Mutex<Option<tokio::task::AbortHandle>>,
+    /// The running `spotifyd authenticate`, if any, until it has exited
+    auth_flow: std::sync::Mutex<Option<AuthFlow>>,
+    /// Held for the whole of an `authenticate`, so a new one waits for a
+    /// cancelled one to exit before touching the credentials cache
+    auth_running: tokio::sync::Mutex<()>,
+    /// Configuration, swapped by restart()
+    config: std::sync::RwLock<Arc<SpotifydConfig>>,
+    /// Controller reconnected to the new spotifyd after a restart
+    linked_controller: std::sync::Mutex<Option<Arc<ControllerInner>>>,
+    /// Bus to look for spotifyd on, and to point spotifyd at, instead of the session bus
+    bus_address: Option<String>,
     /// Lock to prevent concurrent start_or_adopt calls
     start_lock: tokio::sync::Mutex<()>,
 }
@@ -145,14 +712,41 @@ impl SupervisorInner {
         let (status_tx, _) = watch::channel(SpotifydStatus::default());
 
         Self {
-            spawned_child_pid: RwLock::new(None),
-            adopted_pid: RwLock::new(None),
-            status_tx,
-            config,
+            spawned_child: RwLock::new(None),
+            adopted: RwLock::new(None),
+            status_tx: Arc::new(status_tx),
+            auth_watch: std::sync::Mutex::new(None),
+            log_cap: std::sync::Mutex::new(None),
+            auth_flow: std::sync::Mutex::new(None),
+            auth_running: tokio::sync::Mutex::new(()),
+            config: std::sync::RwLock::new(Arc::new(config)),
+            linked_controller: std::sync::Mutex::new(None),
+            bus_address: None,
             start_lock: tokio::sync::Mutex::new(()),
         }
     }
 
+    /// Use the D-Bus daemon at `address` instead of the session bus. spotifyd
+    /// is started with DBUS_SESSION_BUS_ADDRESS pointing at it.
+    pub fn with_bus_address(mut self, address: impl Into<String>) -> Self {
+        self.bus_address = Some(address.into());
+        self
+    }
+
+    fn bus(&self) -> Option<&str> {
+        self.bus_address.as_deref()
+    }
+
+    /// Current configuration
+    pub fn config(&self) -> Arc<SpotifydConfig> {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Reconnect `controller` whenever restart() replaces spotifyd
+    pub fn link_controller(&self, controller: Arc<ControllerInner>) {
+        *self.linked_controller.lock().unwrap() = Some(controller);
+    }
+
     // ─────────────────────────────────────────────────────────────
     // Process Discovery
     // ─────────────────────────────────────────────────────────────
@@ -162,7 +756,7 @@ impl SupervisorInner {
     pub async fn find_spotifyd_via_dbus(&self) -> Option<u32> {
         debug!("Looking for spotifyd via D-Bus");
 
-        let conn = match Connection::session().await {
+        let conn = match open_bus(self.bus()).await {
             Ok(c) => c,
             Err(e) => {
                 warn!("Failed to connect to D-Bus session: {}", e);
@@ -207,31 +801,23 @@ impl SupervisorInner {
         None
     }
 
-    /// Find existing spotifyd process via pgrep (fallback)
+    /// Find existing spotifyd process by scanning /proc (fallback)
     #[instrument(skip(self))]
-    pub async fn find_spotifyd_via_pgrep(&self) -> Option<u32> {
-        debug!("Looking for spotifyd via pgrep");
-
-        let output = tokio::process::Command::new("pgrep")
-            .arg("-x")
-            .arg("spotifyd")
-            .output()
-            .await;
-
-        match output {
-            Ok(out) if out.status.success() => {
-                let stdout = String::from_utf8_lossy(&out.stdout);
-                // Take the first PID if multiple found
-                if let Some(pid_str) = stdout.lines().next() {
-                    if let Ok(pid) = pid_str.trim().parse::<u32>() {
-                        info!("Found spotifyd via pgrep with PID {}", pid);
-                        return Some(pid);
-                    }
-                }
-                None
-            }
-            _ => None,
-        }
+    pub fn find_spotifyd_via_proc(&self) -> Option<u32> {
+        debug!("Looking for spotifyd in /proc");
+
+        // Take the oldest if multiple found
+        let pid = procfs::find_spotifyd_processes().first()?.pid;
+        info!("Found spotifyd via /proc with PID {}", pid);
+        Some(pid)
+    }
+
+    /// List all spotifyd processes owned by the current user
+    pub fn list_spotifyd_processes(&self) -> Vec<SpotifydProcessInfo> {
+        procfs::find_spotifyd_processes()
+            .iter()
+            .map(|p| p.to_info())
+            .collect()
     }
 
     /// Find any existing spotifyd process
@@ -241,8 +827,8 @@ impl SupervisorInner {
             return Some(pid);
         }
 
-        // Fall back to pgrep
-        self.find_spotifyd_via_pgrep().await
+        // Fall back to scanning /proc
+        self.find_spotifyd_via_proc()
     }
 
     // ─────────────────────────────────────────────────────────────
@@ -254,7 +840,7 @@ impl SupervisorInner {
     pub async fn check_dbus_responsive(&self) -> bool {
         debug!("Checking if spotifyd D-Bus interface is responsive");
 
-        let conn = match Connection::session().await {
+        let conn = match open_bus(self.bus()).await {
             Ok(c) => c,
             Err(_) => return false,
         };
@@ -280,20 +866,29 @@ impl SupervisorInner {
         has_spotifyd
     }
 
-    /// Get the current tracked PID (spawned or adopted)
-    pub async fn get_tracked_pid(&self) -> Option<u32> {
-        if let Some(pid) = *self.spawned_child_pid.read().await {
-            return Some(pid);
+    /// Get the currently tracked process (spawned or adopted)
+    async fn tracked_process(&self) -> Option<ProcessIdentity> {
+        if let Some(process) = self.spawned_child.read().await.clone() {
+            return Some(process);
         }
-        *self.adopted_pid.read().await
+        self.adopted.read().await.clone()
+    }
+
+    /// Get the current tracked PID (spawned or adopted), if that process is
+    /// still the one running under it
+    pub async fn get_tracked_pid(&self) -> Option<u32> {
+        self.tracked_process()
+            .await
+            .filter(|p| p.is_alive())
+            .map(|p| p.pid)
     }
 
     /// Check if tracked spotifyd is still alive
     #[instrument(skip(self))]
     pub async fn is_alive(&self) -> bool {
-        if let Some(pid) = self.get_tracked_pid().await {
-            let alive = is_pid_alive(pid);
-            debug!("spotifyd PID {} alive: {}", pid, alive);
+        if let Some(process) = self.tracked_process().await {
+            let alive = process.is_alive();
+            debug!("spotifyd PID {} alive: {}", process.pid, alive);
             return alive;
         }
         false
@@ -313,162 +908,551 @@ impl SupervisorInner {
     pub async fn adopt(&self, pid: u32) -> Result<(), MprisError> {
         info!("Adopting existing spotifyd process with PID {}", pid);
 
+        let process = ProcessIdentity::capture(pid).ok_or(MprisError::SpotifydNotRunning)?;
+
         // Clear any previous state
-        *self.spawned_child_pid.write().await = None;
-        *self.adopted_pid.write().await = Some(pid);
+        *self.spawned_child.write().await = None;
+        *self.adopted.write().await = Some(process.clone());
 
-        // Update status
-        self.status_tx
-            .send(SpotifydStatus {
-                running: true,
-                pid: Some(pid),
-                authenticated: true,
-            })
-            .ok();
+        // Not ours, so its output isn't in our log; D-Bus and credentials only
+        let auth = self.assess_auth(pid, None).await;
+        self.publish_running(&process, procfs::read_cmdline(pid), auth, None);
 
         Ok(())
     }
 
-    /// Start a fresh spotifyd process (truly detached, survives parent death)
+    /// Take back ownership of a spotifyd we spawned in an earlier session,
+    /// as recorded in the persisted supervisor state. Returns false if the
+    /// process isn't one of ours.
     #[instrument(skip(self))]
-    pub async fn start_fresh(&self) -> Result<u32, MprisError> {
-        info!("Starting fresh spotifyd process");
+    async fn reclaim(&self, pid: u32) -> bool {
+        let Some(process) = ProcessIdentity::capture(pid) else {
+            return false;
+        };
+        if state_file::find_spawned(&process).is_none() {
+            return false;
+        }
 
-        // Find the spotifyd binary
-        let binary_path = find_spotifyd_binary(&self.config);
-        info!("Using spotifyd binary: {}", binary_path);
+        info!("Reclaiming spotifyd process with PID {} spawned by a previous session", pid);
+
+        *self.adopted.write().await = None;
+        *self.spawned_child.write().await = Some(process.clone());
 
-        // Check if binary exists
-        if !std::path::Path::new(&binary_path).exists() && binary_path != "spotifyd" {
-            return Err(MprisError::ProcessSpawn(format!(
-                "spotifyd binary not found at {}",
-                binary_path
+        let log = auth::log_path();
+        let auth = self.assess_auth(pid, log.as_deref()).await;
+        self.publish_running(&process, procfs::read_cmdline(pid), auth, log);
+
+        true
+    }
+
+    // ─────────────────────────────────────────────────────────────
+    // Installation
+    // ─────────────────────────────────────────────────────────────
+
+    /// Install spotifyd from a URL, a local archive or the pinned release
+    /// for this platform. A new binary that won't report its version is
+    /// rolled back.
+    #[instrument(skip(self, on_progress))]
+    pub async fn install(
+        &self,
+        options: InstallOptions,
+        on_progress: impl Fn(InstallProgress) + Send + Sync + 'static,
+    ) -> Result<InstallResult, MprisError> {
+        let manifest = installer::Manifest::pinned();
+        let install_dir = install_dir(options.install_dir.as_deref())?;
+        let source = match (options.url, options.archive_path) {
+            (Some(_), Some(_)) => {
+                return Err(MprisError::InvalidConfig(
+                    "pass either url or archivePath, not both".to_string(),
+                ))
+            }
+            (Some(url), None) => InstallSource::Url(url),
+            (None, Some(path)) => InstallSource::Archive(PathBuf::from(path)),
+            (None, None) => match manifest.for_current_target() {
+                Some(artifact) => InstallSource::Url(artifact.url.clone()),
+                None => {
+                    return Err(MprisError::InstallFailed(format!(
+                        "no spotifyd {} release for {}",
+                        manifest.version,
+                        installer::current_target()
+                    )))
+                }
+            },
+        };
+        let request = InstallRequest {
+            source,
+            sha256: options.sha256,
+            install_dir: install_dir.clone(),
+        };
+
+        let on_progress = Arc::new(on_progress);
+        let installed = {
+            let on_progress = on_progress.clone();
+            tokio::task::spawn_blocking(move || {
+                installer::install(&manifest, &request, |p| on_progress(p))
+            })
+            .await
+            .map_err(|e| MprisError::InstallFailed(e.to_string()))??
+        };
+
+        on_progress(InstallProgress {
+            stage: InstallStage::Checking,
+            bytes: 0,
+            total_bytes: None,
+            percent: None,
+        });
+        match binary::version(&installed.path).await {
+            Ok(version) => Ok(InstallResult {
+                success: true,
+                message: format!("Installed spotifyd {}", version),
+                path: Some(installed.path.to_string_lossy().into_owned()),
+                version: Some(version),
+                sha256: Some(installed.sha256),
+                previous_path: installed.previous.map(|p| p.to_string_lossy().into_owned()),
+            }),
+            Err(e) => {
+                // Don't leave a binary behind that can't run
+                let restored =
+                    installed.previous.is_some() && installer::rollback(&install_dir).is_ok();
+                if installed.previous.is_none() {
+                    let _ = std::fs::remove_file(&installed.path);
+                }
+                Err(MprisError::InstallFailed(format!(
+                    "installed binary doesn't run ({}){}",
+                    e,
+                    if restored {
+                        "; previous version restored"
+                    } else {
+                        ""
+                    }
+                )))
+            }
+        }
+    }
+
+    /// Put back the binary replaced by the last install
+    pub async fn rollback_install(&self, dir: Option<&str>) -> Result<InstallResult, MprisError> {
+        let dir = install_dir(dir)?;
+        let path = tokio::task::spawn_blocking(move || installer::rollback(&dir))
+            .await
+            .map_err(|e| MprisError::InstallFailed(e.to_string()))??;
+        let version = binary::version(&path).await.ok();
+        Ok(InstallResult {
+            success: true,
+            message: match version {
+                Some(ref v) => format!("Rolled back to spotifyd {}", v),
+                None => "Rolled back to the previous spotifyd".to_string(),
+            },
+            path: Some(path.to_string_lossy().into_owned()),
+            version,
+            ..Default::default()
+        })
+    }
+
+    // ─────────────────────────────────────────────────────────────
+    // Authentication
+    // ─────────────────────────────────────────────────────────────
+
+    /// Work out whether spotifyd `pid` is logged in. `log` is its captured
+    /// output, only available for processes we spawned.
+    async fn assess_auth(&self, pid: u32, log: Option<&Path>) -> (SpotifydAuthState, Option<String>) {
+        let registered = registered_stage(pid, self.bus()).await.is_some();
+        let failure = match registered {
+            true => None,
+            false => log.and_then(auth::read_auth_failure),
+        };
+        auth::assess(&self.config(), registered, failure)
+    }
+
+    /// Publish a running status, and keep watching for the login to finish
+    /// if it hasn't yet
+    fn publish_running(
+        &self,
+        process: &ProcessIdentity,
+        argv: Vec<String>,
+        auth: (SpotifydAuthState, Option<String>),
+        log: Option<PathBuf>,
+    ) {
+        let logged_in = matches!(auth.0, SpotifydAuthState::Authenticated | SpotifydAuthState::Failed);
+        self.status_tx.send_replace(running_status(process.pid, argv, auth));
+
+        let cap = log.clone().map(|log| {
+            tokio::spawn(cap_log(process.clone(), log, LOG_MAX_BYTES, LOG_CAP_INTERVAL))
+                .abort_handle()
+        });
+        if let Some(previous) = std::mem::replace(&mut *self.log_cap.lock().unwrap(), cap) {
+            previous.abort();
+        }
+
+        let watch = (!logged_in).then(|| {
+            tokio::spawn(watch_auth(
+                self.status_tx.clone(),
+                process.clone(),
+                log,
+                self.bus_address.clone(),
+            ))
+            .abort_handle()
+        });
+        let previous = std::mem::replace(&mut *self.auth_watch.lock().unwrap(), watch);
+        if let Some(previous) = previous {
+            previous.abort();
+        }
+    }
+
+    /// Stop the auth watcher and the log size cap
+    fn stop_background_tasks(&self) {
+        if let Some(watch) = self.auth_watch.lock().unwrap().take() {
+            watch.abort();
+        }
+        if let Some(cap) = self.log_cap.lock().unwrap().take() {
+            cap.abort();
+        }
+    }
+
+    /// Log in through `spotifyd authenticate` (OAuth in the browser).
+    /// Only one login runs at a time.
+    #[instrument(skip(self, on_progress))]
+    pub async fn authenticate(
+        &self,
+        options: AuthenticateOptions,
+        on_progress: impl Fn(AuthProgress),
+    ) -> Result<AuthenticateResult, MprisError> {
+        let in_progress = || {
+            let flow = self.auth_flow.lock().unwrap();
+            flow.as_ref().is_some_and(|flow| !flow.cancelled)
+        };
+        if in_progress() {
+            return Err(MprisError::AuthInProgress);
+        }
+        // A cancelled flow may still be unwinding; wait for it to exit
+        let _running = self.auth_running.lock().await;
+        if in_progress() {
+            return Err(MprisError::AuthInProgress);
+        }
+        let cancel = Arc::new(tokio::sync::Notify::new());
+        *self.auth_flow.lock().unwrap() = Some(AuthFlow {
+            cancel: cancel.clone(),
+            cancelled: false,
+        });
+
+        let result = match self.auth_command(&options).await {
+            Ok(command) => auth::run_authenticate(command, &cancel, on_progress).await,
+            Err(e) => Err(e),
+        };
+
+        *self.auth_flow.lock().unwrap() = None;
+        result
+    }
+
+    /// Cancel a running `authenticate`. Returns false if none is running or
+    /// it was already cancelled.
+    pub fn cancel_authenticate(&self) -> bool {
+        let mut flow = self.auth_flow.lock().unwrap();
+        match flow.as_mut() {
+            Some(flow) if !flow.cancelled => {
+                flow.cancelled = true;
+                flow.cancel.notify_one();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    async fn auth_command(&self, options: &AuthenticateOptions) -> Result<auth::AuthCommand, MprisError> {
+        let candidate = binary::resolve(&self.config()).await?;
+        if candidate.features.is_some_and(|f| !f.authenticate) {
+            return Err(MprisError::AuthFailed(format!(
+                "spotifyd {} doesn't support OAuth; 0.4.0 or newer is required",
+                candidate.version.unwrap_or_default()
             )));
         }
+        let binary = candidate.path;
 
-        // Build the command arguments
-        let mut args = vec!["--no-daemon".to_string()];
+        let credentials_path = auth::credentials_path(&self.config()).ok_or_else(|| {
+            MprisError::InvalidConfig("cannot determine spotifyd cache directory".to_string())
+        })?;
 
-        if let Some(ref device_name) = self.config.device_name {
-            args.push("--device-name".to_string());
-            args.push(device_name.clone());
+        // Point spotifyd at the same cache the daemon will read credentials from
+        let mut args = Vec::new();
+        if let Some(ref path) = self.config().config_path {
+            args.extend(["--config-path".to_string(), path.clone()]);
+        }
+        if let Some(ref path) = self.config().cache_path {
+            args.extend(["--cache-path".to_string(), path.clone()]);
+        }
+        args.push("authenticate".to_string());
+        if let Some(port) = options.oauth_port {
+            if port == 0 || port > u16::MAX as u32 {
+                return Err(MprisError::InvalidConfig(format!(
+                    "oauth_port must be a valid port, got {}",
+                    port
+                )));
+            }
+            args.extend(["--oauth-port".to_string(), port.to_string()]);
         }
 
-        let binary_path_clone = binary_path.clone();
-        let args_clone = args.clone();
+        let spawn = self.spawn_options()?;
+        Ok(auth::AuthCommand {
+            binary,
+            args,
+            env: spawn.env,
+            working_dir: spawn.working_dir,
+            credentials_path,
+            timeout: options
+                .timeout_ms
+                .map(|ms| Duration::from_millis(ms as u64))
+                .unwrap_or(auth::DEFAULT_AUTH_TIMEOUT),
+        })
+    }
 
-        // Spawn in a blocking task using double-fork to properly daemonize
-        // This prevents zombie processes by making init (PID 1) the parent
-        let child_pid = tokio::task::spawn_blocking(move || {
-            // First fork
-            let pid = unsafe { libc::fork() };
-            
-            if pid < 0 {
-                return Err(MprisError::ProcessSpawn("Fork failed".to_string()));
-            }
-            
-            if pid > 0 {
-                // Parent process - wait for first child to exit immediately
-                let mut status: libc::c_int = 0;
-                unsafe { libc::waitpid(pid, &mut status, 0) };
-                
-                // Read the grandchild PID from the status
-                // The first child will have written it to a temp file
-                let pid_file = format!("/tmp/spotifyd-{}.pid", pid);
-                let grandchild_pid = std::fs::read_to_string(&pid_file)
-                    .ok()
-                    .and_then(|s| s.trim().parse::<u32>().ok());
-                let _ = std::fs::remove_file(&pid_file);
-                
-                return grandchild_pid.ok_or_else(|| MprisError::ProcessSpawn("Failed to get grandchild PID".to_string()));
-            }
-            
-            // First child - will exit immediately after forking grandchild
-            // Create new session
-            unsafe { libc::setsid() };
-            
-            // Second fork
-            let pid2 = unsafe { libc::fork() };
-            
-            if pid2 < 0 {
-                std::process::exit(1);
+    /// Probe all spotifyd binary candidates
+    pub async fn detect_spotifyd(&self) -> SpotifydDetection {
+        binary::detect(&self.config()).await
+    }
+
+    /// Re-check whether the tracked spotifyd is logged in and publish the result
+    pub async fn refresh_auth(&self) -> SpotifydStatus {
+        if let Some(process) = self.tracked_process().await.filter(|p| p.is_alive()) {
+            let log = match self.spawned_child.read().await.is_some() {
+                true => auth::log_path(),
+                false => None,
+            };
+            let auth = self.assess_auth(process.pid, log.as_deref()).await;
+            publish_auth(&self.status_tx, process.pid, auth);
+        }
+        self.get_status()
+    }
+
+    /// Config file spotifyd is pointed at: the user's own, the one we
+    /// generate, or none when spotifyd's default file will do
+    fn effective_config_path(&self) -> Option<PathBuf> {
+        let config = self.config();
+        match config.config_path {
+            Some(ref path) => Some(PathBuf::from(path)),
+            None if spotifyd_config::needs_generated_config(&config) => {
+                spotifyd_config::generated_config_path()
             }
-            
-            if pid2 > 0 {
-                // First child - write grandchild PID and exit
-                let pid_file = format!("/tmp/spotifyd-{}.pid", std::process::id());
-                let _ = std::fs::write(&pid_file, format!("{}", pid2));
-                std::process::exit(0);
+            None => None,
+        }
+    }
+
+    /// Make sure the config file exists before spawning. Unless the user
+    /// supplied their own, typed settings are laid over spotifyd's default
+    /// config file into one we generate.
+    fn prepare_config(&self) -> Result<(), MprisError> {
+        let config = self.config();
+        spotifyd_config::validate(&config)?;
+
+        if let Some(ref path) = config.config_path {
+            if !std::path::Path::new(path).exists() {
+                return Err(MprisError::InvalidConfig(format!(
+                    "config file not found at {}",
+                    path
+                )));
             }
-            
-            // Grandchild - this becomes the actual spotifyd process
-            // Close all file descriptors and redirect to /dev/null
-            unsafe {
-                // Redirect stdin, stdout, stderr to /dev/null
-                let dev_null = libc::open(b"/dev/null\0".as_ptr() as *const i8, libc::O_RDWR);
-                if dev_null >= 0 {
-                    libc::dup2(dev_null, libc::STDIN_FILENO);
-                    libc::dup2(dev_null, libc::STDOUT_FILENO);
-                    libc::dup2(dev_null, libc::STDERR_FILENO);
-                    if dev_null > libc::STDERR_FILENO {
-                        libc::close(dev_null);
-                    }
-                }
+        } else if spotifyd_config::needs_generated_config(&config) {
+            if let Some(path) = spotifyd_config::generated_config_path() {
+                let base = spotifyd_config::user_config_path();
+                spotifyd_config::write_config(&config, &path, base.as_deref())?;
+                info!("Wrote spotifyd config to {}", path.display());
             }
-            
-            // Convert args to CStrings
-            let c_binary = std::ffi::CString::new(binary_path_clone.as_str()).unwrap();
-            let c_args: Vec<std::ffi::CString> = std::iter::once(c_binary.clone())
-                .chain(args_clone.iter().map(|s| std::ffi::CString::new(s.as_str()).unwrap()))
-                .collect();
-            let c_argv: Vec<*const i8> = c_args.iter()
-                .map(|s| s.as_ptr())
-                .chain(std::iter::once(std::ptr::null()))
-                .collect();
-            
-            // Exec spotifyd
-            unsafe {
-                libc::execvp(c_binary.as_ptr(), c_argv.as_ptr());
-                // If exec returns, it failed
-                libc::_exit(1);
+        }
+        Ok(())
+    }
+
+    /// Environment and working directory spotifyd is launched with
+    fn spawn_options(&self) -> Result<SpawnOptions, MprisError> {
+        let working_dir = self.config().working_dir.clone();
+        if let Some(ref dir) = working_dir {
+            if !std::path::Path::new(dir).is_dir() {
+                return Err(MprisError::InvalidConfig(format!(
+                    "working directory {} does not exist",
+                    dir
+                )));
             }
+        }
+
+        Ok(SpawnOptions {
+            env: spotifyd_config::environment(&self.config(), self.base_environment())?,
+            working_dir,
+            log_file: auth::log_path(),
         })
-        .await
-        .map_err(|e| MprisError::ProcessSpawn(format!("Task join error: {}", e)))??;
+    }
 
-        info!("spotifyd spawned with PID {}", child_pid);
+    /// Our environment, with the bus override applied
+    fn base_environment(&self) -> Vec<(OsString, OsString)> {
+        let mut vars: Vec<_> = std::env::vars_os().collect();
+        if let Some(address) = self.bus() {
+            vars.retain(|(name, _)| name != BUS_ADDRESS_ENV);
+            vars.push((BUS_ADDRESS_ENV.into(), address.into()));
+        }
+        vars
+    }
+
+    /// Arguments spotifyd is launched with
+    fn spawn_args(&self) -> Vec<String> {
+        let config_path = self.effective_config_path();
+        spotifyd_config::command_line_args(&self.config(), config_path.as_deref())
+    }
 
-        // Store the PID
-        *self.spawned_child_pid.write().await = Some(child_pid);
-        *self.adopted_pid.write().await = None;
+    /// The first running spotifyd the kill policy allows us to stop, or why
+    /// the first one found is off limits
+    fn stoppable_spotifyd(&self) -> Result<Option<ProcessIdentity>, SkippedProcess> {
+        let policy = self.config().kill_policy.clone().unwrap_or_default();
+        let uid = procfs::current_uid();
+        let our_args = self.spawn_args();
+        let spawned = state_file::load_spawned();
+
+        let mut first_skipped = None;
+        for process in procfs::scan_spotifyd_processes() {
+            match policy.check(&process, uid, &our_args, &spawned) {
+                Ok(()) => return Ok(Some(process.identity())),
+                Err(reason) => {
+                    first_skipped.get_or_insert(SkippedProcess {
+                        pid: process.pid,
+                        reason,
+                    });
+                }
+            }
+        }
+        first_skipped.map_or(Ok(None), Err)
+    }
 
-        // Wait for spotifyd to initialize
-        tokio::time::sleep(Duration::from_millis(1500)).await;
+    /// Kill existing spotifyd processes that the kill policy allows us to touch
+    #[instrument(skip(self))]
+    async fn kill_stale_spotifyd(&self) -> KillReport {
+        let policy = self.config().kill_policy.clone().unwrap_or_default();
+        let uid = procfs::current_uid();
+        let our_args = self.spawn_args();
+        let spawned = state_file::load_spawned();
+
+        let mut report = KillReport::default();
+        let mut targets = Vec::new();
+
+        for process in procfs::scan_spotifyd_processes() {
+            match policy.check(&process, uid, &our_args, &spawned) {
+                Ok(()) => targets.push(process.identity()),
+                Err(reason) => {
+                    info!("Leaving spotifyd {} alone: {}", process.pid, reason);
+                    report.skipped.push(SkippedProcess {
+                        pid: process.pid,
+                        reason,
+                    });
+                }
+            }
+        }
 
-        // Verify it's still running
-        if !is_pid_alive(child_pid) {
-            *self.spawned_child_pid.write().await = None;
-            return Err(MprisError::ProcessSpawn(
-                "spotifyd exited immediately after starting".to_string(),
-            ));
+        if targets.is_empty() {
+            return report;
         }
 
-        // Update status
+        info!("Killing {} existing spotifyd process(es)", targets.len());
+        for process in targets {
+            if kill_process(&process).await {
+                report.killed.push(process.pid);
+            } else {
+                warn!("spotifyd {} could not be terminated", process.pid);
+                report.skipped.push(SkippedProcess {
+                    pid: process.pid,
+                    reason: "could not be terminated".to_string(),
+                });
+            }
+        }
+
+        // Wait a bit for all processes to fully terminate
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        report
+    }
+
+    /// Start a fresh spotifyd process from `binary`, as picked by
+    /// `binary::resolve` (truly detached, survives parent death)
+    #[instrument(skip(self, binary))]
+    pub async fn start_fresh(&self, binary: SpotifydCandidate) -> Result<FreshStart, MprisError> {
+        info!("Starting fresh spotifyd process");
+
+        let binary_path = binary.path;
+        info!("Using spotifyd binary: {}", binary_path);
+
+        self.prepare_config()?;
+        let args = self.spawn_args();
+        let options = self.spawn_options()?;
+        let argv: Vec<String> = std::iter::once(binary_path.clone())
+            .chain(args.iter().cloned())
+            .collect();
+        info!("spotifyd command line: {:?}", argv);
+
+        let spawned_at = Instant::now();
+
+        // Spawn in a blocking task using double-fork to properly daemonize
+        // This prevents zombie processes by making init (PID 1) the parent
+        let (spawn_binary, spawn_args) = (binary_path.clone(), args.clone());
+        let child_pid = tokio::task::spawn_blocking(move || spawn_detached(&spawn_binary, &spawn_args, &options))
+            .await
+            .map_err(|e| MprisError::ProcessSpawn(format!("Task join error: {}", e)))??;
+
+        info!("spotifyd spawned with PID {}", child_pid);
+
+        // Already gone if we can't even read its /proc entry
+        let process = ProcessIdentity::capture(child_pid).ok_or(MprisError::ExitedImmediately)?;
+
+        // Persist it so the next TUI session knows this daemon is ours
+        state_file::record_spawn(SpawnedDaemon {
+            pid: child_pid,
+            start_ticks: process.start_ticks,
+            binary_path,
+            args,
+            device_name: self.config().device_name.clone(),
+            spawned_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        });
+
+        // Store the process identity
+        *self.spawned_child.write().await = Some(process.clone());
+        *self.adopted.write().await = None;
+
+        // Update status; login happens asynchronously after spawning
+        let log = auth::log_path();
+        let initial_auth = auth::assess(&self.config(), false, None);
+        // send_replace, so get_status() sees it even with no subscribers
         self.status_tx
-            .send(SpotifydStatus {
-                running: true,
-                pid: Some(child_pid),
-                authenticated: true,
-            })
-            .ok();
+            .send_replace(running_status(child_pid, argv.clone(), initial_auth));
 
-        // Wait for D-Bus registration (non-blocking, just informational)
-        match self.wait_for_dbus_registration().await {
-            Ok(()) => info!("spotifyd D-Bus registration confirmed"),
-            Err(_) => warn!("spotifyd D-Bus registration pending (may take a few more seconds)"),
+        let (stage, ready_after) = match self.wait_until_ready(&process, spawned_at, log.as_deref()).await {
+            Ok(readiness) => readiness,
+            Err(e) => {
+                *self.spawned_child.write().await = None;
+                state_file::remove_spawn(&process);
+                self.status_tx.send_replace(SpotifydStatus::default());
+                // A bad login is the most common reason for an early exit
+                if let Some(line) = log.as_deref().and_then(auth::read_auth_failure) {
+                    return Err(MprisError::AuthFailed(line));
+                }
+                return Err(e);
+            }
+        };
+
+        let auth = match stage {
+            SpotifydStartStage::Spawned => {
+                let failure = log.as_deref().and_then(auth::read_auth_failure);
+                auth::assess(&self.config(), false, failure)
+            }
+            _ => (SpotifydAuthState::Authenticated, None),
+        };
+        self.publish_running(&process, argv, auth, log);
+
+        match ready_after {
+            Some(elapsed) => info!("spotifyd ready ({:?}) after {:?}", stage, elapsed),
+            None => warn!("spotifyd D-Bus registration pending (may take a few more seconds)"),
         }
 
-        Ok(child_pid)
+        Ok(FreshStart {
+            pid: child_pid,
+            stage,
+            ready_after,
+        })
     }
 
     /// Start spotifyd or adopt existing instance
@@ -476,23 +1460,32 @@ impl SupervisorInner {
     pub async fn start_or_adopt(&self) -> Result<SpotifydStartResult, MprisError> {
         // Acquire lock to prevent concurrent calls
         let _guard = self.start_lock.lock().await;
-        
+        self.start_or_adopt_locked().await
+    }
+
+    /// start_or_adopt with `start_lock` already held
+    async fn start_or_adopt_locked(&self) -> Result<SpotifydStartResult, MprisError> {
         info!("Starting or adopting spotifyd");
 
         // First, check if we already have a healthy tracked process
-        if let Some(pid) = self.get_tracked_pid().await {
-            if is_pid_alive(pid) && self.check_dbus_responsive().await {
+        if let Some(process) = self.tracked_process().await {
+            let pid = process.pid;
+            if process.is_alive() && self.check_dbus_responsive().await {
                 info!("Already tracking healthy spotifyd with PID {}", pid);
                 return Ok(SpotifydStartResult {
                     success: true,
                     message: "Spotifyd already running".to_string(),
                     pid: Some(pid),
-                    adopted: true,
+                    adopted: self.spawned_child.read().await.is_none(),
+                    stage: Some(SpotifydStartStage::MprisRegistered),
+                    ready_ms: None,
+                    killed: Vec::new(),
+                    skipped: Vec::new(),
                 });
             }
-            // Our tracked process is dead or unhealthy, clear it
-            *self.spawned_child_pid.write().await = None;
-            *self.adopted_pid.write().await = None;
+            // Our tracked process is dead, unhealthy or its PID was recycled - clear it
+            *self.spawned_child.write().await = None;
+            *self.adopted.write().await = None;
         }
 
         // Check for existing spotifyd (not tracked by us)
@@ -501,126 +1494,327 @@ impl SupervisorInner {
 
             // Verify it's actually healthy (responsive)
             if is_pid_alive(pid) && self.check_dbus_responsive().await {
+                if self.reclaim(pid).await {
+                    return Ok(SpotifydStartResult {
+                        success: true,
+                        message: "Reclaimed spotifyd spawned by a previous session".to_string(),
+                        pid: Some(pid),
+                        adopted: false,
+                        stage: Some(SpotifydStartStage::MprisRegistered),
+                        ready_ms: None,
+                        killed: Vec::new(),
+                        skipped: Vec::new(),
+                    });
+                }
+
                 self.adopt(pid).await?;
                 return Ok(SpotifydStartResult {
                     success: true,
                     message: "Adopted existing spotifyd instance (instant start!)".to_string(),
                     pid: Some(pid),
                     adopted: true,
+                    stage: Some(SpotifydStartStage::MprisRegistered),
+                    ready_ms: None,
+                    killed: Vec::new(),
+                    skipped: Vec::new(),
                 });
             }
 
-            // Process exists but not healthy - kill ALL existing spotifyd processes
-            warn!("Existing spotifyd {} is not healthy, killing all instances", pid);
+            // Process exists but not healthy - clear out stale instances below
+            warn!("Existing spotifyd {} is not healthy, replacing it", pid);
         }
 
-        // Kill ALL existing spotifyd processes before starting fresh
-        // This ensures we don't accumulate zombie processes
-        let killed = kill_all_spotifyd().await;
-        if killed > 0 {
-            info!("Killed {} existing spotifyd process(es)", killed);
-        }
-        
-        // Double-check no spotifyd processes remain (paranoid verification)
-        let remaining = find_all_spotifyd_pids().await;
-        if !remaining.is_empty() {
-            warn!("Spotifyd processes still running after kill: {:?}", remaining);
-            // Try killing them again more aggressively
-            for pid in remaining {
-                kill_pid(pid).await;
+        // Without a usable binary, fail before touching any running spotifyd
+        let binary = match binary::resolve(&self.config()).await {
+            Ok(binary) => binary,
+            Err(e) => {
+                error!("{}", e);
+                return Ok(SpotifydStartResult {
+                    success: false,
+                    message: e.to_string(),
+                    pid: None,
+                    adopted: false,
+                    stage: None,
+                    ready_ms: None,
+                    killed: Vec::new(),
+                    skipped: Vec::new(),
+                });
             }
-            tokio::time::sleep(Duration::from_millis(500)).await;
-        }
+        };
+
+        // Kill stale spotifyd processes before starting fresh, as far as the
+        // kill policy allows
+        let report = self.kill_stale_spotifyd().await;
 
         // Start fresh
-        match self.start_fresh().await {
-            Ok(pid) => Ok(SpotifydStartResult {
+        match self.start_fresh(binary).await {
+            Ok(started) => Ok(SpotifydStartResult {
                 success: true,
                 message: "Started fresh spotifyd instance".to_string(),
-                pid: Some(pid),
+                pid: Some(started.pid),
                 adopted: false,
+                stage: Some(started.stage),
+                ready_ms: started.ready_after.map(|d| d.as_millis() as u32),
+                killed: report.killed,
+                skipped: report.skipped,
             }),
             Err(e) => Ok(SpotifydStartResult {
                 success: false,
                 message: format!("Failed to start spotifyd: {}", e),
                 pid: None,
                 adopted: false,
+                stage: None,
+                ready_ms: None,
+                killed: report.killed,
+                skipped: report.skipped,
             }),
         }
     }
 
-    /// Wait for spotifyd D-Bus registration
+    /// Wait for a freshly spawned spotifyd to register on D-Bus.
+    ///
+    /// Watches `NameOwnerChanged` for the controls (`rs.spotifyd.instance*`) and
+    /// MPRIS names while racing against the process exiting. Returns as soon as
+    /// MPRIS appears; once only the controls interface is up we allow a short
+    /// grace period for MPRIS, since spotifyd 0.4 exposes it only after
+    /// playback has been transferred. Times out with `Spawned` rather than an
+    /// error so a slow-to-login daemon is still reported as started.
     #[instrument(skip(self))]
-    async fn wait_for_dbus_registration(&self) -> Result<(), MprisError> {
+    async fn wait_until_ready(
+        &self,
+        process: &ProcessIdentity,
+        spawned_at: Instant,
+        log: Option<&Path>,
+    ) -> Result<(SpotifydStartStage, Option<Duration>), MprisError> {
         debug!("Waiting for spotifyd D-Bus registration");
 
-        let conn = Connection::session()
-            .await
-            .map_err(|_| MprisError::RegistrationTimeout)?;
-
-        for attempt in 1..=30 {
-            let dbus = zbus::fdo::DBusProxy::new(&conn)
-                .await
-                .map_err(|_| MprisError::RegistrationTimeout)?;
-
-            let names = dbus
-                .list_names()
-                .await
-                .map_err(|_| MprisError::RegistrationTimeout)?;
-
-            if names
-                .iter()
-                .any(|n| n.as_str().starts_with("org.mpris.MediaPlayer2.spotifyd"))
-            {
-                info!(
-                    "spotifyd D-Bus registration detected after {} attempts",
-                    attempt
-                );
-                return Ok(());
+        let pid = process.pid;
+
+        let mut stage = SpotifydStartStage::Spawned;
+        let mut ready_after = None;
+        let mut deadline = spawned_at + READY_TIMEOUT;
+
+        let conn = match open_bus(self.bus()).await {
+            Ok(c) => Some(c),
+            Err(e) => {
+                warn!("No D-Bus session for readiness detection: {}", e);
+                None
             }
+        };
+        let dbus = match conn.as_ref() {
+            Some(conn) => zbus::fdo::DBusProxy::new(conn).await.ok(),
+            None => None,
+        };
 
-            tokio::time::sleep(Duration::from_millis(100)).await;
+        // Subscribe before listing so a registration between the two isn't missed
+        let mut owner_changes = match dbus.as_ref() {
+            Some(dbus) => dbus.receive_name_owner_changed().await.ok(),
+            None => None,
+        };
+
+        if let Some(dbus) = dbus.as_ref() {
+            if let Ok(names) = dbus.list_names().await {
+                for name in names.iter() {
+                    if let Some(reached) = registration_stage(name.as_str()) {
+                        if reached > stage && name_owned_by(dbus, name.as_str(), pid).await {
+                            stage = reached;
+                        }
+                    }
+                }
+            }
         }
 
-        error!("spotifyd D-Bus registration timeout after 3 seconds");
-        Err(MprisError::RegistrationTimeout)
+        let mut exit_check = tokio::time::interval(EXIT_POLL_INTERVAL);
+
+        loop {
+            if stage == SpotifydStartStage::MprisRegistered {
+                break;
+            }
+            if stage == SpotifydStartStage::ControlsRegistered && ready_after.is_none() {
+                ready_after = Some(spawned_at.elapsed());
+                deadline = deadline.min(Instant::now() + MPRIS_GRACE);
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline.into()) => break,
+                _ = exit_check.tick() => {
+                    if !process.is_alive() {
+                        error!("spotifyd {} exited during startup", pid);
+                        return Err(MprisError::ExitedImmediately);
+                    }
+                    // No point waiting out the timeout for a login that already failed
+                    if stage == SpotifydStartStage::Spawned
+                        && log.and_then(auth::read_auth_failure).is_some()
+                    {
+                        warn!("spotifyd {} reported an authentication failure", pid);
+                        break;
+                    }
+                }
+                signal = next_owner_change(&mut owner_changes) => {
+                    let Some(signal) = signal else {
+                        // Signal stream ended - fall back to exit polling until the deadline
+                        owner_changes = None;
+                        continue;
+                    };
+                    let Ok(args) = signal.args() else { continue };
+                    if args.new_owner().is_none() {
+                        continue;
+                    }
+                    let name = args.name().as_str();
+                    if let Some(reached) = registration_stage(name) {
+                        if reached > stage {
+                            let owned = match dbus.as_ref() {
+                                Some(dbus) => name_owned_by(dbus, name, pid).await,
+                                None => true,
+                            };
+                            if owned {
+                                info!("spotifyd registered {}", name);
+                                stage = reached;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if stage == SpotifydStartStage::MprisRegistered {
+            ready_after = ready_after.or_else(|| Some(spawned_at.elapsed()));
+        }
+
+        // Final liveness check in case it died right at the deadline
+        if !process.is_alive() {
+            return Err(MprisError::ExitedImmediately);
+        }
+
+        Ok((stage, ready_after))
     }
 
-    /// Stop spotifyd (whether spawned or adopted)
+    /// Stop and start spotifyd under one lock, optionally with new
+    /// configuration, then reconnect the linked controller. If the new
+    /// configuration doesn't come up, the previous one is restored and
+    /// started instead.
+    #[instrument(skip(self, new_config))]
+    pub async fn restart(
+        &self,
+        new_config: Option<SpotifydConfig>,
+    ) -> Result<SpotifydRestartResult, MprisError> {
+        let _guard = self.start_lock.lock().await;
+
+        let previous = self.config();
+        let reconfigured = new_config.is_some();
+        if let Some(config) = new_config {
+            // Reject a bad config before stopping a working spotifyd
+            spotifyd_config::validate(&config)?;
+            *self.config.write().unwrap() = Arc::new(config);
+        }
+        info!("Restarting spotifyd (reconfigured={})", reconfigured);
+
+        let stop = self.stop(true, None).await?;
+        let outcome = self.start_or_adopt_locked().await;
+
+        let (start, rolled_back) = match outcome {
+            Ok(start) if start.success || !reconfigured => (start, false),
+            outcome => {
+                let reason = match outcome {
+                    Ok(start) => start.message,
+                    Err(e) => e.to_string(),
+                };
+                warn!("spotifyd failed to restart with new configuration: {}", reason);
+                *self.config.write().unwrap() = previous;
+                self.stop(true, None).await?;
+                let mut start = self.start_or_adopt_locked().await?;
+                start.message = format!(
+                    "New configuration failed ({}); restored the previous one: {}",
+                    reason, start.message
+                );
+                (start, true)
+            }
+        };
+
+        let controller = self.linked_controller.lock().unwrap().clone();
+        let controller_reconnected = match controller {
+            Some(controller) if start.success => Some(match controller.reconnect().await {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Linked controller failed to reconnect: {}", e);
+                    false
+                }
+            }),
+            Some(_) => Some(false),
+            None => None,
+        };
+
+        Ok(SpotifydRestartResult {
+            success: start.success && !rolled_back,
+            message: start.message.clone(),
+            stop,
+            start,
+            rolled_back,
+            controller_reconnected,
+        })
+    }
+
+    /// Stop spotifyd (whether spawned or adopted), escalating per `policy`
+    /// or the configured stop policy
     #[instrument(skip(self))]
-    pub async fn stop(&self, force: bool) -> Result<(), MprisError> {
+    pub async fn stop(
+        &self,
+        force: bool,
+        policy: Option<SpotifydStopPolicy>,
+    ) -> Result<SpotifydStopResult, MprisError> {
         info!("Stopping spotifyd (force={})", force);
+        let sequence = StopSequence::new(policy.as_ref().or(self.config().stop_policy.as_ref()));
 
-        let pid = self.get_tracked_pid().await;
-
-        if let Some(pid) = pid {
-            if force || self.spawned_child_pid.read().await.is_some() {
-                kill_pid(pid).await;
+        let mut result = SpotifydStopResult {
+            stopped: true,
+            message: "spotifyd not running".to_string(),
+            ..Default::default()
+        };
+        if let Some(process) = self.tracked_process().await {
+            if force || self.spawned_child.read().await.is_some() {
+                result = terminate(&process, &sequence, self.bus()).await;
+                if result.stopped {
+                    state_file::remove_spawn(&process);
+                }
             } else {
-                info!("Not killing adopted process {} without force flag", pid);
+                info!("Not killing adopted process {} without force flag", process.pid);
+                result = SpotifydStopResult {
+                    pid: Some(process.pid),
+                    message: "Left adopted spotifyd running (pass force to stop it)".to_string(),
+                    ..Default::default()
+                };
             }
         } else if force {
-            // Force mode: find and kill any spotifyd
-            if let Some(pid) = self.find_existing_spotifyd().await {
-                kill_pid(pid).await;
+            // Force mode: stop a spotifyd the kill policy lets us touch
+            match self.stoppable_spotifyd() {
+                Ok(Some(process)) => {
+                    result = terminate(&process, &sequence, self.bus()).await;
+                    if result.stopped {
+                        state_file::remove_spawn(&process);
+                    }
+                }
+                Ok(None) => {}
+                Err(skipped) => {
+                    info!("Not stopping spotifyd {}: {}", skipped.pid, skipped.reason);
+                    result = SpotifydStopResult {
+                        pid: Some(skipped.pid),
+                        message: format!("Left spotifyd running: {}", skipped.reason),
+                        ..Default::default()
+                    };
+                }
             }
         }
 
         // Clear state
-        *self.spawned_child_pid.write().await = None;
-        *self.adopted_pid.write().await = None;
+        self.stop_background_tasks();
+        *self.spawned_child.write().await = None;
+        *self.adopted.write().await = None;
 
         // Update status
-        self.status_tx
-            .send(SpotifydStatus {
-                running: false,
-                pid: None,
-                authenticated: false,
-            })
-            .ok();
+        self.status_tx.send_replace(SpotifydStatus::default());
 
-        info!("spotifyd stopped");
-        Ok(())
+        info!("spotifyd stop: {} ({:?})", result.message, result.stage);
+        Ok(result)
     }
 
     /// Get current status
@@ -648,3 +1842,310 @@ impl SupervisorInner {
         self.is_healthy().await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{wait_until, write_archive, write_script, SPAWN_LOCK};
+    use std::io::Write;
+
+    /// Our own environment and working directory, unchanged
+    fn inherit() -> SpawnOptions {
+        SpawnOptions {
+            env: std::env::vars_os().collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn spawn_reports_grandchild_pid_and_passes_args() {
+        let _guard = SPAWN_LOCK.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let args_file = dir.path().join("args");
+        let script = write_script(
+            dir.path(),
+            "spotifyd",
+            &format!("echo \"$$ $@\" > {}\nexec sleep 30", args_file.display()),
+            0o755,
+        );
+
+        let args = vec!["--no-daemon".to_string(), "--device-name".to_string(), "test".to_string()];
+        let pid = spawn_detached(&script, &args, &inherit()).unwrap();
+
+        assert!(wait_until(Duration::from_secs(5), || std::fs::read_to_string(&args_file)
+            .map(|s| s.ends_with('\n'))
+            .unwrap_or(false)));
+        let recorded = std::fs::read_to_string(&args_file).unwrap();
+        assert_eq!(recorded.trim(), format!("{} --no-daemon --device-name test", pid));
+
+        // The daemon runs in its own session, detached from ours
+        assert_ne!(unsafe { libc::getsid(pid as i32) }, unsafe { libc::getsid(0) });
+        assert!(is_pid_alive(pid));
+
+        unsafe { libc::kill(pid as i32, libc::SIGKILL) };
+        assert!(wait_until(Duration::from_secs(5), || !is_pid_alive(pid)));
+    }
+
+    #[test]
+    fn spawn_reports_non_executable_binary() {
+        let _guard = SPAWN_LOCK.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let script = write_script(dir.path(), "spotifyd", "exit 0", 0o644);
+
+        match spawn_detached(&script, &[], &inherit()) {
+            Err(MprisError::ExecFailed { path, source }) => {
+                assert_eq!(path, script);
+                assert_eq!(source.raw_os_error(), Some(libc::EACCES));
+            }
+            other => panic!("expected ExecFailed, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn spawn_reports_missing_binary() {
+        let _guard = SPAWN_LOCK.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("spotifyd").to_string_lossy().to_string();
+
+        match spawn_detached(&missing, &[], &inherit()) {
+            Err(MprisError::ExecFailed { source, .. }) => {
+                assert_eq!(source.raw_os_error(), Some(libc::ENOENT));
+            }
+            other => panic!("expected ExecFailed, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn spawn_succeeds_for_binary_that_exits_immediately() {
+        let _guard = SPAWN_LOCK.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let script = write_script(dir.path(), "spotifyd", "exit 3", 0o755);
+
+        // exec itself succeeds; the early exit is only visible afterwards
+        let pid = spawn_detached(&script, &[], &inherit()).unwrap();
+        assert!(wait_until(Duration::from_secs(5), || !is_pid_alive(pid)));
+    }
+
+    #[test]
+    fn spawn_applies_configured_environment_and_working_dir() {
+        let _guard = SPAWN_LOCK.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let work_dir = dir.path().join("work");
+        std::fs::create_dir(&work_dir).unwrap();
+        let env_file = dir.path().join("env");
+        // Fake spotifyd that dumps its environment and working directory
+        let script = write_script(
+            dir.path(),
+            "spotifyd",
+            &format!(
+                "{{ pwd; env; }} > {0}.tmp && mv {0}.tmp {0}\nexec sleep 30",
+                env_file.display()
+            ),
+            0o755,
+        );
+
+        let inner = SupervisorInner::new(SpotifydConfig {
+            env: Some(std::collections::HashMap::from([
+                ("RUST_LOG".to_string(), "spotifyd=trace".to_string()),
+                ("PULSE_SERVER".to_string(), "unix:/tmp/pulse socket".to_string()),
+            ])),
+            env_remove: Some(vec!["HOME".to_string()]),
+            working_dir: Some(work_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        });
+        let options = inner.spawn_options().unwrap();
+        let pid = spawn_detached(&script, &[], &options).unwrap();
+
+        assert!(wait_until(Duration::from_secs(5), || env_file.exists()));
+        let dump = std::fs::read_to_string(&env_file).unwrap();
+        let mut lines = dump.lines();
+        assert_eq!(lines.next(), Some(work_dir.to_str().unwrap()));
+        let vars: Vec<&str> = lines.collect();
+        assert!(vars.contains(&"RUST_LOG=spotifyd=trace"));
+        assert!(vars.contains(&"PULSE_SERVER=unix:/tmp/pulse socket"));
+        assert!(!vars.iter().any(|v| v.starts_with("HOME=")));
+        // Everything else is still inherited
+        assert!(vars.iter().any(|v| v.starts_with("PATH=")));
+
+        unsafe { libc::kill(pid as i32, libc::SIGKILL) };
+        assert!(wait_until(Duration::from_secs(5), || !is_pid_alive(pid)));
+    }
+
+    #[test]
+    fn config_path_only_when_typed_settings_need_one() {
+        let plain = SupervisorInner::new(SpotifydConfig {
+            device_name: Some("spotify-tui".to_string()),
+            ..Default::default()
+        });
+        let args = plain.spawn_args();
+        assert!(!args.iter().any(|arg| arg == "--config-path"), "{:?}", args);
+        assert!(args.windows(2).any(|w| w == ["--device-name", "spotify-tui"]));
+
+        let typed = SupervisorInner::new(SpotifydConfig {
+            bitrate: Some(320),
+            ..Default::default()
+        });
+        let args = typed.spawn_args();
+        let generated = spotifyd_config::generated_config_path().unwrap();
+        assert!(args
+            .windows(2)
+            .any(|w| w[0] == "--config-path" && w[1] == generated.to_str().unwrap()));
+    }
+
+    #[test]
+    fn spawn_reports_unusable_working_dir() {
+        let _guard = SPAWN_LOCK.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let script = write_script(dir.path(), "spotifyd", "exit 0", 0o755);
+        let options = SpawnOptions {
+            working_dir: Some(dir.path().join("missing").to_string_lossy().to_string()),
+            ..inherit()
+        };
+
+        match spawn_detached(&script, &[], &options) {
+            Err(MprisError::ProcessSpawn(msg)) => assert!(msg.contains("working directory")),
+            other => panic!("expected ProcessSpawn, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn spawn_captures_output_for_auth_failure_detection() {
+        let _guard = SPAWN_LOCK.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("logs").join("spotifyd.log");
+        std::fs::create_dir(dir.path().join("logs")).unwrap();
+        std::fs::write(&log, "Login failed with reason: Bad credentials (previous run)\n").unwrap();
+        let script = write_script(
+            dir.path(),
+            "spotifyd",
+            "echo 'Connecting to AP'\necho 'Login failed with reason: Premium account required' >&2\nexit 1",
+            0o755,
+        );
+
+        let options = SpawnOptions {
+            log_file: Some(log.clone()),
+            ..inherit()
+        };
+        let pid = spawn_detached(&script, &[], &options).unwrap();
+        assert!(wait_until(Duration::from_secs(5), || !is_pid_alive(pid)));
+
+        // The log is truncated per spawn, so only this run's failure shows up
+        let contents = std::fs::read_to_string(&log).unwrap();
+        assert!(contents.starts_with("Connecting to AP"));
+        assert_eq!(
+            auth::read_auth_failure(&log).as_deref(),
+            Some("Login failed with reason: Premium account required")
+        );
+    }
+
+    #[test]
+    fn captured_log_is_capped_while_the_process_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("spotifyd.log");
+        let mut writer = open_log_file(&log).unwrap();
+        let me = ProcessIdentity::capture(std::process::id()).unwrap();
+
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let cap = tokio::spawn(cap_log(me, log.clone(), 100, Duration::from_millis(10)));
+            writer.write_all(&[b'x'; 200]).unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            cap.abort();
+        });
+
+        // The writer's next line lands at the start, not after a hole
+        writer.write_all(b"after\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "after\n");
+    }
+
+    #[test]
+    fn install_checks_new_binary_and_rolls_back_broken_one() {
+        let _guard = SPAWN_LOCK.lock().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        let dir = work.path().join("bin");
+        let good = work.path().join("good.tar.gz");
+        let broken = work.path().join("broken.tar.gz");
+        let good_digest = write_archive(
+            &good,
+            &[("spotifyd", "#!/bin/sh\necho 'spotifyd 0.4.2'", 0o755)],
+        );
+        let broken_digest = write_archive(&broken, &[("spotifyd", "#!/bin/sh\nexit 3", 0o755)]);
+
+        let supervisor = SupervisorInner::new(SpotifydConfig::default());
+        let options = |archive: &Path, digest: &str| InstallOptions {
+            archive_path: Some(archive.to_string_lossy().into_owned()),
+            sha256: Some(digest.to_string()),
+            install_dir: Some(dir.to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let result = runtime
+            .block_on(supervisor.install(options(&good, &good_digest), |_| {}))
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(result.version.as_deref(), Some("0.4.2"));
+
+        let err = runtime
+            .block_on(supervisor.install(options(&broken, &broken_digest), |_| {}))
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("previous version restored"),
+            "{}",
+            err
+        );
+        let restored = runtime.block_on(binary::version(&dir.join("spotifyd")));
+        assert_eq!(restored.as_deref(), Ok("0.4.2"));
+    }
+
+    #[test]
+    fn stop_escalates_to_sigkill_only_when_needed() {
+        let _guard = SPAWN_LOCK.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let polite = write_script(dir.path(), "polite", "while :; do sleep 0.1; done", 0o755);
+        let stubborn = write_script(
+            dir.path(),
+            "stubborn",
+            "trap '' TERM\nwhile :; do sleep 0.1; done",
+            0o755,
+        );
+        // No session bus, and skip straight to signals
+        let sequence = StopSequence::new(Some(&SpotifydStopPolicy {
+            pause: Some(false),
+            mpris_quit: Some(false),
+            term_timeout_ms: Some(300),
+            ..Default::default()
+        }));
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let mut stages = Vec::new();
+        for script in [&polite, &stubborn] {
+            let pid = spawn_detached(script, &[], &inherit()).unwrap();
+            let process = ProcessIdentity::capture(pid).unwrap();
+            // Let the shell install its trap before signalling it
+            std::thread::sleep(Duration::from_millis(100));
+
+            let result = runtime.block_on(terminate(&process, &sequence, None));
+            assert!(result.stopped, "{}", result.message);
+            assert_eq!(result.pid, Some(pid));
+            assert!(!process.is_alive());
+            stages.push(result.stage.unwrap());
+        }
+        assert_eq!(stages, [SpotifydStopStage::Sigterm, SpotifydStopStage::Sigkill]);
+    }
+
+    #[test]
+    fn restart_rejects_bad_config_before_stopping() {
+        let supervisor = SupervisorInner::new(SpotifydConfig {
+            device_name: Some("before".to_string()),
+            ..Default::default()
+        });
+        let result = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(supervisor.restart(Some(SpotifydConfig {
+                dbus_type: Some("bogus".to_string()),
+                ..Default::default()
+            })));
+        assert!(matches!(result, Err(MprisError::InvalidConfig(_))));
+        assert_eq!(supervisor.config().device_name.as_deref(), Some("before"));
+    }
+}
@@ -0,0 +1,49 @@
+//! Helpers shared by unit tests that spawn fake spotifyd scripts
+
+use sha2::{Digest, Sha256};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Serialises spawns so a concurrent fork can't hold a script's write fd
+/// open while another test execs it (ETXTBSY).
+pub static SPAWN_LOCK: Mutex<()> = Mutex::new(());
+
+/// Write an executable (or not, depending on `mode`) shell script
+pub fn write_script(dir: &Path, name: &str, body: &str, mode: u32) -> String {
+    let path = dir.join(name);
+    std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+/// Poll `f` until it returns true or the timeout passes
+pub fn wait_until(timeout: Duration, mut f: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if f() {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    f()
+}
+
+/// Build a .tar.gz holding `files` (path, contents, mode); returns its SHA-256
+pub fn write_archive(path: &Path, files: &[(&str, &str, u32)]) -> String {
+    let file = std::fs::File::create(path).unwrap();
+    let gz = flate2::write::GzEncoder::new(file, flate2::Compression::fast());
+    let mut builder = tar::Builder::new(gz);
+    for (name, contents, mode) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(*mode);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, name, contents.as_bytes())
+            .unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap();
+    format!("{:x}", Sha256::digest(std::fs::read(path).unwrap()))
+}
@@ -1,7 +1,10 @@
 use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[napi(object)]
-#[derive(Clone, Default, Debug)]
+#[derive(Clone, Default, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PlaybackState {
     pub is_playing: bool,
     pub position_ms: i64,
@@ -10,10 +13,12 @@ pub struct PlaybackState {
     pub shuffle: bool,
     pub repeat: RepeatMode,
     pub track: Option<TrackInfo>,
+    /// Running sleep timer, if any
+    pub sleep_timer: Option<SleepTimerState>,
 }
 
 #[napi(string_enum)]
-#[derive(Default, Debug)]
+#[derive(Default, Debug, PartialEq, Serialize, Deserialize)]
 pub enum RepeatMode {
     #[default]
     None,
@@ -22,7 +27,8 @@ pub enum RepeatMode {
 }
 
 #[napi(object)]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TrackInfo {
     pub title: String,
     pub artist: String,
@@ -31,6 +37,152 @@ pub struct TrackInfo {
     pub uri: String,
 }
 
+/// Shape of a volume fade over time
+#[napi(string_enum)]
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum FadeCurve {
+    #[default]
+    Linear,
+    /// Slow start, fast finish
+    EaseIn,
+    /// Fast start, slow finish
+    EaseOut,
+    /// Slow at both ends
+    SCurve,
+}
+
+/// When a sleep timer pauses playback
+#[napi(string_enum)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub enum SleepTimerMode {
+    /// After `duration_ms`
+    Duration,
+    /// When the current track ends
+    EndOfTrack,
+    /// When a track from another album starts. MPRIS doesn't show the queue,
+    /// so the end of an album is only known once the next one is playing;
+    /// playback pauses right away, without a fade.
+    EndOfAlbum,
+    /// When the `tracks`-th track from now ends
+    AfterTracks,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepTimerOptions {
+    pub mode: SleepTimerMode,
+    /// Required for `Duration`
+    pub duration_ms: Option<u32>,
+    /// Required for `AfterTracks`
+    pub tracks: Option<u32>,
+    /// Fade the volume out over this long before pausing (default: no fade).
+    /// Ignored for `EndOfAlbum`.
+    pub fade_out_ms: Option<u32>,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepTimerState {
+    pub mode: SleepTimerMode,
+    /// When playback will pause (ms since the epoch), once known - for a countdown
+    pub ends_at_ms: Option<i64>,
+    /// Track changes left, for the track-based modes
+    pub tracks_remaining: Option<u32>,
+    pub fade_out_ms: u32,
+    /// Fading out right now
+    pub fading: bool,
+}
+
+/// Metadata the TUI has and spotifyd doesn't, published by our MPRIS player
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct MprisExtraMetadata {
+    /// Only apply while this track (`TrackInfo.uri`) is current
+    pub track_uri: Option<String>,
+    /// Cached album art on disk, published as mpris:artUrl
+    pub art_path: Option<String>,
+    /// Current lyrics line, published as xesam:asText
+    pub lyrics_line: Option<String>,
+    /// open.spotify.com link, published as xesam:url
+    pub url: Option<String>,
+}
+
+/// Settings for "now playing" desktop notifications
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct NotifierOptions {
+    /// Minimum time between notifications (default 2000); a burst of track
+    /// changes in between shows only the last one
+    pub min_interval_ms: Option<u32>,
+    /// How long notifications stay up; -1 (default) leaves it to the server
+    pub timeout_ms: Option<i32>,
+    /// Album art named `<track id>.jpg` or `.png` (default ~/.spotify-tui/art)
+    pub art_cache_dir: Option<String>,
+    /// Start with notifications suppressed
+    pub do_not_disturb: Option<bool>,
+}
+
+/// Settings for the listening history recorder
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct HistoryOptions {
+    /// History file (default ~/.spotify-tui/history.jsonl)
+    pub path: Option<String>,
+    /// Listening time for a track to count as played (default 30000, or half
+    /// of shorter tracks)
+    pub min_played_ms: Option<u32>,
+}
+
+/// One play of a track, as stored in the history file
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub uri: String,
+    /// When the track started (ms since the epoch)
+    pub started_at_ms: i64,
+    /// When it stopped being the current track (ms since the epoch)
+    pub ended_at_ms: i64,
+    pub duration_ms: i64,
+    /// Time actually spent playing, excluding pauses
+    pub played_ms: i64,
+    /// Left well before the end
+    pub skipped: bool,
+    pub seek_count: u32,
+}
+
+/// How far back a history query looks
+#[napi(string_enum)]
+#[derive(Debug, PartialEq)]
+pub enum HistoryRange {
+    /// Last 24 hours
+    Day,
+    /// Last 7 days
+    Week,
+    /// Last 30 days
+    Month,
+    /// Last 365 days
+    Year,
+    All,
+}
+
+/// A track or artist ranked by plays
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoryTopEntry {
+    /// Track title, or artist name
+    pub name: String,
+    /// The track's artist; None when ranking artists
+    pub artist: Option<String>,
+    pub plays: u32,
+    pub played_ms: i64,
+}
+
 #[napi(object)]
 #[derive(Clone, Debug)]
 pub struct ConnectionStatus {
@@ -41,40 +193,396 @@ pub struct ConnectionStatus {
 }
 
 #[napi(object)]
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SpotifydStatus {
     pub running: bool,
     pub pid: Option<u32>,
+    /// Shorthand for `auth_state == Authenticated`
     pub authenticated: bool,
+    pub auth_state: SpotifydAuthState,
+    /// Log line explaining a failed login
+    pub auth_error: Option<String>,
+    /// Full command line of the tracked spotifyd, argv[0] included
+    pub argv: Vec<String>,
+}
+
+/// Whether spotifyd has a Spotify session
+#[napi(string_enum)]
+#[derive(Default, Debug, PartialEq, Eq, Serialize)]
+pub enum SpotifydAuthState {
+    /// spotifyd isn't running
+    #[default]
+    Unknown,
+    /// Has credentials but no session yet
+    Pending,
+    /// Nothing cached and no username/password configured - needs a login
+    NoCredentials,
+    /// spotifyd reported a failed login
+    Failed,
+    /// Session established (spotifyd registered on D-Bus)
+    Authenticated,
+}
+
+/// Options for `spotifyd authenticate`
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct AuthenticateOptions {
+    /// Give up after this long (default: 2 minutes)
+    pub timeout_ms: Option<u32>,
+    /// Port for spotifyd's local OAuth callback server
+    pub oauth_port: Option<u32>,
+}
+
+/// Step reached in the OAuth login flow
+#[napi(string_enum = "kebab-case")]
+#[derive(Debug, PartialEq, Eq)]
+pub enum AuthProgressKind {
+    /// `spotifyd authenticate` is running
+    Started,
+    /// spotifyd printed the accounts.spotify.com login URL
+    UrlFound,
+    /// Waiting for the user to finish logging in in the browser
+    Waiting,
+    /// spotifyd wrote the OAuth credentials file
+    CredentialsWritten,
 }
 
-/// Result of starting or adopting spotifyd
 #[napi(object)]
 #[derive(Clone, Debug)]
+pub struct AuthProgress {
+    pub kind: AuthProgressKind,
+    /// Login URL, once known
+    pub url: Option<String>,
+    /// Credentials file, for `credentials-written`
+    pub path: Option<String>,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct AuthenticateResult {
+    pub success: bool,
+    pub message: String,
+    pub auth_url: Option<String>,
+    pub credentials_path: Option<String>,
+    pub cancelled: bool,
+}
+
+/// Result of starting or adopting spotifyd
+#[napi(object)]
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SpotifydStartResult {
     pub success: bool,
     pub message: String,
     pub pid: Option<u32>,
     /// True if we adopted an existing process, false if we spawned a new one
     pub adopted: bool,
+    /// Furthest startup stage confirmed before returning
+    pub stage: Option<SpotifydStartStage>,
+    /// Milliseconds from spawn until spotifyd registered on D-Bus (fresh starts only)
+    pub ready_ms: Option<u32>,
+    /// PIDs of stale spotifyd processes killed before starting fresh
+    pub killed: Vec<u32>,
+    /// spotifyd processes left alone by the kill policy
+    pub skipped: Vec<SkippedProcess>,
+}
+
+/// A spotifyd process the kill policy refused to touch
+#[napi(object)]
+#[derive(Clone, Debug, Serialize)]
+pub struct SkippedProcess {
+    pub pid: u32,
+    pub reason: String,
+}
+
+/// Which existing spotifyd processes may be killed before starting our own.
+/// Processes owned by other users are never touched.
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct SpotifydKillPolicy {
+    /// Only kill processes launched with our device name (default: true)
+    pub match_device_name: Option<bool>,
+    /// Only kill processes launched with our config file (default: false)
+    pub match_config_path: Option<bool>,
+    /// Only kill processes recorded as spawned by spotify-tui (default: false)
+    pub only_spawned: Option<bool>,
+}
+
+/// How spotifyd is asked to stop. Each step is skipped once the process
+/// is gone; SIGKILL is the last resort.
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct SpotifydStopPolicy {
+    /// Pause playback first (default: true)
+    pub pause: Option<bool>,
+    /// Call MPRIS Quit if spotifyd reports CanQuit (default: true)
+    pub mpris_quit: Option<bool>,
+    /// How long to wait after Quit before sending SIGTERM (default: 3000)
+    pub quit_timeout_ms: Option<u32>,
+    /// How long to wait after SIGTERM before sending SIGKILL (default: 2000)
+    pub term_timeout_ms: Option<u32>,
+}
+
+/// What terminated spotifyd during a stop
+#[napi(string_enum)]
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub enum SpotifydStopStage {
+    /// Exited before we sent it anything beyond a pause
+    AlreadyExited,
+    /// Exited after MPRIS Quit
+    MprisQuit,
+    /// Exited after SIGTERM
+    Sigterm,
+    /// Needed SIGKILL
+    Sigkill,
+}
+
+/// Result of stopping spotifyd
+#[napi(object)]
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpotifydStopResult {
+    /// Whether spotifyd is gone (also true if nothing was running)
+    pub stopped: bool,
+    pub pid: Option<u32>,
+    /// Step that terminated the process
+    pub stage: Option<SpotifydStopStage>,
+    /// Playback was paused before stopping
+    pub paused: bool,
+    pub elapsed_ms: u32,
+    pub message: String,
+}
+
+/// Result of restarting spotifyd
+#[napi(object)]
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpotifydRestartResult {
+    /// spotifyd came back up with the requested configuration
+    pub success: bool,
+    pub message: String,
+    pub stop: SpotifydStopResult,
+    /// Start with the new configuration, or with the old one after a rollback
+    pub start: SpotifydStartResult,
+    /// The new configuration failed to come up and the previous one was restored
+    pub rolled_back: bool,
+    /// Whether the linked MprisController reconnected; unset if none is linked
+    pub controller_reconnected: Option<bool>,
+}
+
+/// How far a spotifyd start got, in order
+#[napi(string_enum)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum SpotifydStartStage {
+    /// Process exec'd successfully but hasn't registered on D-Bus yet
+    Spawned,
+    /// `rs.spotifyd.instance*` controls interface is registered
+    ControlsRegistered,
+    /// `org.mpris.MediaPlayer2.spotifyd*` is registered
+    MprisRegistered,
+}
+
+/// Where a spotifyd binary candidate came from
+#[napi(string_enum)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum SpotifydBinarySource {
+    /// `binaryPath` in SpotifydConfig
+    Config,
+    /// `SPOTIFY_TUI_SPOTIFYD_PATH`
+    Environment,
+    /// ~/.spotify-tui/bin/spotifyd
+    Downloaded,
+    /// First `spotifyd` on PATH
+    Path,
+}
+
+/// What a spotifyd build supports
+#[napi(object)]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpotifydFeatures {
+    /// `spotifyd authenticate` (OAuth login, 0.4+)
+    pub authenticate: bool,
+    /// Built with MPRIS support
+    pub mpris: bool,
+    /// rs.spotifyd.Controls D-Bus interface (0.4+ with MPRIS)
+    pub controls: bool,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct SpotifydCandidate {
+    pub path: String,
+    pub source: SpotifydBinarySource,
+    pub exists: bool,
+    pub executable: bool,
+    /// Parsed from `--version`, as major.minor.patch
+    pub version: Option<String>,
+    pub features: Option<SpotifydFeatures>,
+    /// Why this candidate can't be used
+    pub error: Option<String>,
+}
+
+/// Result of probing every spotifyd candidate
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct SpotifydDetection {
+    /// First candidate that runs and reports a version
+    pub selected: Option<SpotifydCandidate>,
+    /// All candidates in priority order
+    pub candidates: Vec<SpotifydCandidate>,
+}
+
+/// Where to install spotifyd from. With neither `url` nor `archivePath`,
+/// the pinned release for this platform is downloaded.
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct InstallOptions {
+    /// Release archive (.tar.gz) to download
+    pub url: Option<String>,
+    /// Local .tar.gz archive to install from
+    pub archive_path: Option<String>,
+    /// Expected SHA-256 in hex, instead of the pinned manifest's
+    pub sha256: Option<String>,
+    /// Install directory (default: ~/.spotify-tui/bin)
+    pub install_dir: Option<String>,
+}
+
+/// Step reached by an install, in order
+#[napi(string_enum = "kebab-case")]
+#[derive(Debug, PartialEq, Eq)]
+pub enum InstallStage {
+    /// Fetching the archive (or reading a local one) and hashing it
+    Downloading,
+    /// Comparing the SHA-256 with the expected digest
+    Verifying,
+    /// Unpacking the binary from the archive
+    Extracting,
+    /// Moving the binary into place
+    Installing,
+    /// Running the new binary's `--version`
+    Checking,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct InstallProgress {
+    pub stage: InstallStage,
+    /// Bytes read so far, while downloading
+    pub bytes: i64,
+    pub total_bytes: Option<i64>,
+    /// 0-100, when the total size is known
+    pub percent: Option<u32>,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct InstallResult {
+    pub success: bool,
+    pub message: String,
+    /// Installed binary
+    pub path: Option<String>,
+    /// Reported by the installed binary's `--version`
+    pub version: Option<String>,
+    /// Digest of the installed archive
+    pub sha256: Option<String>,
+    /// Binary kept for rollbackInstall()
+    pub previous_path: Option<String>,
+}
+
+/// A spotifyd process found by scanning /proc
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct SpotifydProcessInfo {
+    pub pid: u32,
+    /// Resolved executable path, or argv[0] if /proc/<pid>/exe is unreadable
+    pub binary_path: Option<String>,
+    /// Command-line arguments, excluding argv[0]
+    pub args: Vec<String>,
+    pub uptime_ms: Option<i64>,
+    /// Resident set size in bytes
+    pub rss_bytes: i64,
 }
 
 #[napi(object)]
 #[derive(Clone, Debug)]
 pub struct SpotifydConfig {
+    /// Path to the spotifyd binary (otherwise discovered automatically)
+    pub binary_path: Option<String>,
+    /// Existing spotifyd config file to use as-is. When unset, one is
+    /// generated at ~/.spotify-tui/spotifyd.conf from the fields below.
     pub config_path: Option<String>,
     pub username: Option<String>,
     pub password: Option<String>,
     pub device_name: Option<String>,
+    /// Audio bitrate in kbit/s: 96, 160 or 320
+    pub bitrate: Option<u32>,
+    /// Audio backend, e.g. "alsa", "pulseaudio", "rodio"
+    pub backend: Option<String>,
+    /// Device type shown in Spotify Connect, e.g. "computer", "speaker"
+    pub device_type: Option<String>,
+    /// Directory for spotifyd's cache and cached credentials
+    pub cache_path: Option<String>,
+    /// With `config_path` set, only `true` takes effect: spotifyd has no flag
+    /// to turn this off over the user's file
+    pub volume_normalisation: Option<bool>,
+    /// Initial volume, 0-100
+    pub initial_volume: Option<u32>,
+    /// Keep playing similar songs after the queue ends. With `config_path`
+    /// set, only `true` takes effect, as for `volume_normalisation`.
+    pub autoplay: Option<bool>,
+    /// Audio device to play through, e.g. an ALSA device name
+    pub device: Option<String>,
+    /// ALSA mixer control
+    pub mixer: Option<String>,
+    /// Volume controller, e.g. "softvol", "alsa", "none"
+    pub volume_controller: Option<String>,
+    /// Port for Spotify Connect zeroconf discovery
+    pub zeroconf_port: Option<u32>,
+    /// Whether spotifyd exposes MPRIS
+    pub use_mpris: Option<bool>,
+    /// Bus spotifyd registers on: "session" or "system"
+    pub dbus_type: Option<String>,
+    /// Raw arguments appended after everything else
+    pub extra_args: Option<Vec<String>>,
+    /// Environment variables to set for spotifyd, overriding inherited ones
+    pub env: Option<HashMap<String, String>>,
+    /// Inherited environment variables to unset for spotifyd
+    pub env_remove: Option<Vec<String>>,
+    /// Directory spotifyd is started in
+    pub working_dir: Option<String>,
+    pub kill_policy: Option<SpotifydKillPolicy>,
+    /// How stop() escalates from MPRIS Quit to signals
+    pub stop_policy: Option<SpotifydStopPolicy>,
 }
 
 impl Default for SpotifydConfig {
     fn default() -> Self {
         Self {
+            binary_path: None,
             config_path: None,
             username: None,
             password: None,
             device_name: Some("spotify-tui".to_string()),
+            bitrate: None,
+            backend: None,
+            device_type: None,
+            cache_path: None,
+            volume_normalisation: None,
+            initial_volume: None,
+            autoplay: None,
+            device: None,
+            mixer: None,
+            volume_controller: None,
+            zeroconf_port: None,
+            use_mpris: None,
+            dbus_type: None,
+            extra_args: None,
+            env: None,
+            env_remove: None,
+            working_dir: None,
+            kill_policy: None,
+            stop_policy: None,
         }
     }
 }
@@ -0,0 +1,383 @@
+//! Drives the supervisor against the fake spotifyd (tests/support/fake_spotifyd.rs)
+//! on a private dbus-daemon, which must be installed.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use mpris_native::error::MprisError;
+use mpris_native::mock_player::DbusDaemon;
+use mpris_native::supervisor::SupervisorInner;
+use mpris_native::types::{
+    AuthProgressKind, AuthenticateOptions, SpotifydAuthState, SpotifydConfig, SpotifydStartStage,
+    SpotifydStopPolicy, SpotifydStopStage,
+};
+
+/// Tests share HOME and scan /proc for spotifyd, so they run one at a time
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// A private bus, a temporary HOME and the fake installed as `spotifyd`
+struct Harness {
+    _serial: MutexGuard<'static, ()>,
+    bus: DbusDaemon,
+    home: tempfile::TempDir,
+    binary: PathBuf,
+}
+
+impl Harness {
+    fn start() -> Self {
+        let serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let bus = DbusDaemon::start();
+        let home = tempfile::tempdir().unwrap();
+        std::env::set_var("HOME", home.path());
+
+        // Copied so the process is named spotifyd, like the real thing
+        let binary = home.path().join("spotifyd");
+        std::fs::copy(env!("CARGO_BIN_EXE_fake-spotifyd"), &binary).unwrap();
+
+        Self {
+            _serial: serial,
+            bus,
+            home,
+            binary,
+        }
+    }
+
+    /// Config running the fake with `env` as its script
+    fn config(&self, env: &[(&str, &str)]) -> SpotifydConfig {
+        SpotifydConfig {
+            binary_path: Some(self.binary.to_string_lossy().into_owned()),
+            device_name: Some("fake-device".to_string()),
+            cache_path: Some(self.home.path().join("cache").to_string_lossy().into_owned()),
+            env: Some(
+                env.iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect::<HashMap<_, _>>(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    fn supervisor(&self, config: SpotifydConfig) -> SupervisorInner {
+        SupervisorInner::new(config).with_bus_address(&self.bus.address)
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        // Don't leave fakes behind for the next test to adopt
+        for pid in fake_pids(&self.binary) {
+            unsafe { libc::kill(pid as i32, libc::SIGKILL) };
+        }
+    }
+}
+
+/// Running processes executing `binary`
+fn fake_pids(binary: &Path) -> Vec<u32> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            let exe = std::fs::read_link(entry.path().join("exe")).ok()?;
+            (exe == binary).then_some(pid)
+        })
+        .collect()
+}
+
+fn is_running(pid: u32) -> bool {
+    std::fs::read_to_string(format!("/proc/{}/stat", pid))
+        .map(|stat| !stat.contains(") Z "))
+        .unwrap_or(false)
+}
+
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Runtime::new().unwrap().block_on(future)
+}
+
+fn quick_stop() -> Option<SpotifydStopPolicy> {
+    Some(SpotifydStopPolicy {
+        pause: Some(true),
+        mpris_quit: Some(true),
+        quit_timeout_ms: Some(500),
+        term_timeout_ms: Some(500),
+    })
+}
+
+#[test]
+fn starts_fresh_and_stops_through_mpris_quit() {
+    let harness = Harness::start();
+    let config = harness.config(&[("FAKE_SPOTIFYD_MPRIS_DELAY_MS", "100")]);
+    let supervisor = harness.supervisor(config);
+
+    block_on(async {
+        let started = supervisor.start_or_adopt().await.unwrap();
+        assert!(started.success, "{}", started.message);
+        assert!(!started.adopted);
+        assert_eq!(started.stage, Some(SpotifydStartStage::MprisRegistered));
+        assert!(started.ready_ms.is_some());
+        let pid = started.pid.unwrap();
+
+        let status = supervisor.get_status();
+        assert!(status.running);
+        assert_eq!(status.auth_state, SpotifydAuthState::Authenticated);
+        assert!(status.argv.iter().any(|a| a == "--no-daemon"));
+
+        let stopped = supervisor.stop(false, quick_stop()).await.unwrap();
+        assert!(stopped.stopped);
+        assert!(stopped.paused);
+        assert_eq!(stopped.stage, Some(SpotifydStopStage::MprisQuit));
+        assert!(!is_running(pid));
+        assert!(!supervisor.get_status().running);
+    });
+}
+
+#[test]
+fn second_supervisor_adopts_the_running_instance() {
+    let harness = Harness::start();
+    let first = harness.supervisor(harness.config(&[]));
+    let second = harness.supervisor(harness.config(&[]));
+
+    block_on(async {
+        let started = first.start_or_adopt().await.unwrap();
+        assert!(started.success, "{}", started.message);
+
+        let adopted = second.start_or_adopt().await.unwrap();
+        assert!(adopted.success, "{}", adopted.message);
+        assert_eq!(adopted.pid, started.pid);
+        assert!(adopted.killed.is_empty());
+        assert_eq!(fake_pids(&harness.binary).len(), 1);
+
+        first.stop(true, quick_stop()).await.unwrap();
+    });
+}
+
+#[test]
+fn starting_again_keeps_ownership_of_the_spawned_instance() {
+    let harness = Harness::start();
+    let supervisor = harness.supervisor(harness.config(&[]));
+
+    block_on(async {
+        let started = supervisor.start_or_adopt().await.unwrap();
+        assert!(started.success, "{}", started.message);
+
+        let again = supervisor.start_or_adopt().await.unwrap();
+        assert!(again.success, "{}", again.message);
+        assert_eq!(again.pid, started.pid);
+        assert!(!again.adopted);
+        assert_eq!(fake_pids(&harness.binary).len(), 1);
+
+        supervisor.stop(false, quick_stop()).await.unwrap();
+    });
+}
+
+#[test]
+fn forced_stop_leaves_spotifyd_the_kill_policy_protects() {
+    let harness = Harness::start();
+    let owner = harness.supervisor(harness.config(&[]));
+    let other = harness.supervisor(SpotifydConfig {
+        device_name: Some("other-device".to_string()),
+        ..harness.config(&[])
+    });
+
+    block_on(async {
+        let started = owner.start_or_adopt().await.unwrap();
+        assert!(started.success, "{}", started.message);
+
+        let stopped = other.stop(true, quick_stop()).await.unwrap();
+        assert!(!stopped.stopped);
+        assert_eq!(stopped.pid, started.pid);
+        assert!(stopped.message.contains("device name"), "{}", stopped.message);
+        assert!(is_running(started.pid.unwrap()));
+
+        // A matching device name is fair game
+        let same = harness.supervisor(harness.config(&[]));
+        let stopped = same.stop(true, quick_stop()).await.unwrap();
+        assert!(stopped.stopped, "{}", stopped.message);
+        assert!(!is_running(started.pid.unwrap()));
+    });
+}
+
+#[test]
+fn controls_without_mpris_is_reported_as_partially_ready() {
+    let harness = Harness::start();
+    let config = harness.config(&[("FAKE_SPOTIFYD_MPRIS_DELAY_MS", "never")]);
+    let supervisor = harness.supervisor(config);
+
+    block_on(async {
+        let started = supervisor.start_or_adopt().await.unwrap();
+        assert!(started.success, "{}", started.message);
+        assert_eq!(started.stage, Some(SpotifydStartStage::ControlsRegistered));
+        assert!(started.ready_ms.is_some());
+
+        // No MPRIS to ask, so stopping falls through to SIGTERM
+        let stopped = supervisor.stop(false, quick_stop()).await.unwrap();
+        assert_eq!(stopped.stage, Some(SpotifydStopStage::Sigterm));
+    });
+}
+
+#[test]
+fn crash_during_startup_fails_the_start() {
+    let harness = Harness::start();
+    let supervisor = harness.supervisor(harness.config(&[
+        ("FAKE_SPOTIFYD_CONTROLS_DELAY_MS", "5000"),
+        ("FAKE_SPOTIFYD_CRASH_AFTER_MS", "200"),
+    ]));
+
+    block_on(async {
+        let started = supervisor.start_or_adopt().await.unwrap();
+        assert!(!started.success);
+        assert!(started.message.contains("exited"), "{}", started.message);
+        assert!(!supervisor.get_status().running);
+    });
+
+    // No record of the crashed process is left behind
+    let state_file = harness.home.path().join(".spotify-tui/spotifyd-state.json");
+    let state: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(state_file).unwrap()).unwrap();
+    assert_eq!(state["spawned"], serde_json::json!([]));
+}
+
+#[test]
+fn failed_login_is_reported_from_the_log() {
+    let harness = Harness::start();
+    let supervisor = harness.supervisor(harness.config(&[("FAKE_SPOTIFYD_AUTH", "fail")]));
+
+    block_on(async {
+        let started = supervisor.start_or_adopt().await.unwrap();
+        assert!(!started.success);
+        assert!(started.message.contains("Login failed"), "{}", started.message);
+    });
+}
+
+#[test]
+fn stop_escalates_past_ignored_quit_and_sigterm() {
+    let harness = Harness::start();
+
+    block_on(async {
+        let config = harness.config(&[("FAKE_SPOTIFYD_IGNORE_QUIT", "1")]);
+        let supervisor = harness.supervisor(config);
+        let started = supervisor.start_or_adopt().await.unwrap();
+        assert!(started.success, "{}", started.message);
+        let stopped = supervisor.stop(false, quick_stop()).await.unwrap();
+        assert_eq!(stopped.stage, Some(SpotifydStopStage::Sigterm));
+        assert!(!is_running(started.pid.unwrap()));
+
+        let supervisor = harness.supervisor(harness.config(&[
+            ("FAKE_SPOTIFYD_IGNORE_QUIT", "1"),
+            ("FAKE_SPOTIFYD_IGNORE_SIGTERM", "1"),
+        ]));
+        let started = supervisor.start_or_adopt().await.unwrap();
+        assert!(started.success, "{}", started.message);
+        let stopped = supervisor.stop(false, quick_stop()).await.unwrap();
+        assert!(stopped.stopped);
+        assert_eq!(stopped.stage, Some(SpotifydStopStage::Sigkill));
+        assert!(!is_running(started.pid.unwrap()));
+    });
+}
+
+#[test]
+fn restart_rolls_back_when_new_config_fails() {
+    let harness = Harness::start();
+    let supervisor = harness.supervisor(harness.config(&[]));
+
+    block_on(async {
+        let started = supervisor.start_or_adopt().await.unwrap();
+        assert!(started.success, "{}", started.message);
+
+        let broken = harness.config(&[("FAKE_SPOTIFYD_AUTH", "fail")]);
+        let restarted = supervisor.restart(Some(broken)).await.unwrap();
+        assert!(!restarted.success);
+        assert!(restarted.rolled_back);
+        assert!(restarted.start.success, "{}", restarted.start.message);
+        assert_ne!(restarted.start.pid, started.pid);
+        assert!(is_running(restarted.start.pid.unwrap()));
+        assert!(supervisor.config().env.as_ref().unwrap().is_empty());
+
+        supervisor.stop(false, quick_stop()).await.unwrap();
+    });
+}
+
+#[test]
+fn restart_with_invalid_config_leaves_spotifyd_running() {
+    let harness = Harness::start();
+    let supervisor = harness.supervisor(harness.config(&[]));
+
+    block_on(async {
+        let started = supervisor.start_or_adopt().await.unwrap();
+        assert!(started.success, "{}", started.message);
+
+        let invalid = SpotifydConfig {
+            bitrate: Some(123),
+            ..harness.config(&[])
+        };
+        let restarted = supervisor.restart(Some(invalid)).await;
+        assert!(matches!(restarted, Err(MprisError::InvalidConfig(_))));
+        assert!(is_running(started.pid.unwrap()));
+        assert_eq!(supervisor.get_tracked_pid().await, started.pid);
+        assert_eq!(supervisor.config().bitrate, None);
+
+        supervisor.stop(false, quick_stop()).await.unwrap();
+    });
+}
+
+#[test]
+fn authenticate_picks_up_written_credentials() {
+    let harness = Harness::start();
+    let supervisor = harness.supervisor(harness.config(&[]));
+
+    block_on(async {
+        let options = AuthenticateOptions {
+            timeout_ms: Some(5000),
+            oauth_port: None,
+        };
+        let result = supervisor.authenticate(options, |_| {}).await.unwrap();
+        assert!(result.success, "{}", result.message);
+        assert!(result.auth_url.unwrap().starts_with("https://accounts.spotify.com"));
+        assert!(Path::new(&result.credentials_path.unwrap()).exists());
+    });
+}
+
+#[test]
+fn authenticate_after_cancel_waits_for_the_old_flow() {
+    let harness = Harness::start();
+    let supervisor = harness.supervisor(harness.config(&[]));
+    let options = || AuthenticateOptions {
+        timeout_ms: Some(5000),
+        oauth_port: None,
+    };
+    let first_done = AtomicBool::new(false);
+
+    block_on(async {
+        let first = async {
+            let result = supervisor.authenticate(options(), |_| {}).await.unwrap();
+            first_done.store(true, Ordering::SeqCst);
+            result
+        };
+        let second = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert!(matches!(
+                supervisor.authenticate(options(), |_| {}).await,
+                Err(MprisError::AuthInProgress)
+            ));
+            assert!(supervisor.cancel_authenticate());
+            assert!(!supervisor.cancel_authenticate());
+            supervisor
+                .authenticate(options(), |progress| {
+                    if progress.kind == AuthProgressKind::Started {
+                        assert!(first_done.load(Ordering::SeqCst));
+                    }
+                })
+                .await
+                .unwrap()
+        };
+        let (first, second) = tokio::join!(first, second);
+        assert!(first.cancelled);
+        assert!(second.success, "{}", second.message);
+    });
+    assert!(!supervisor.cancel_authenticate());
+}
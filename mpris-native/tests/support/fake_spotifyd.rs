@@ -0,0 +1,228 @@
+//! Stand-in for spotifyd used by the supervisor integration tests.
+//!
+//! Understands the parts of spotifyd's CLI the supervisor uses (`--version`,
+//! `--help`, `authenticate`, `--no-daemon` with `--device-name`,
+//! `--cache-path` and `--config-path`) and registers the same D-Bus names.
+//! Its behaviour is scripted through the environment:
+//!
+//! - `FAKE_SPOTIFYD_CONTROLS_DELAY_MS` / `FAKE_SPOTIFYD_MPRIS_DELAY_MS`: when
+//!   the controls and MPRIS names are claimed (MPRIS is skipped if `never`)
+//! - `FAKE_SPOTIFYD_CRASH_AFTER_MS`: exit with status 101 after this long
+//! - `FAKE_SPOTIFYD_IGNORE_SIGTERM=1`: keep running on SIGTERM
+//! - `FAKE_SPOTIFYD_IGNORE_QUIT=1`: accept MPRIS Quit but keep running
+//! - `FAKE_SPOTIFYD_AUTH=fail`: log a failed login and exit (daemon) or fail
+//!   (`authenticate`)
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use zbus::{interface, Connection};
+
+const VERSION: &str = "0.4.2";
+
+const HELP: &str = "\
+A Spotify daemon
+
+Usage: spotifyd [OPTIONS] [COMMAND]
+
+Commands:
+  authenticate  Authenticate with Spotify using OAuth
+
+Options:
+      --no-daemon                 Don't detach from the console
+      --config-path <PATH>        Path to the config file
+      --device-name <NAME>        Name shown in Spotify Connect
+      --cache-path <PATH>         Cache directory
+      --use-mpris <BOOL>          Expose MPRIS on D-Bus
+      --dbus-type <TYPE>          Bus to register on
+  -h, --help                      Print help
+  -V, --version                   Print version";
+
+/// Failure line in the format spotifyd logs it
+const LOGIN_FAILED: &str = "Login failed with reason: Bad credentials";
+
+fn env_flag(name: &str) -> bool {
+    std::env::var(name).is_ok_and(|v| v == "1")
+}
+
+fn env_ms(name: &str) -> Option<Duration> {
+    std::env::var(name).ok()?.parse().ok().map(Duration::from_millis)
+}
+
+/// Value following `flag` on the command line
+fn flag_value(args: &[String], flag: &str) -> Option<String> {
+    let index = args.iter().position(|a| a == flag)?;
+    args.get(index + 1).cloned()
+}
+
+/// Cache directory from `--cache-path`, or the config file's `cache_path`
+fn cache_path(args: &[String]) -> Option<PathBuf> {
+    if let Some(path) = flag_value(args, "--cache-path") {
+        return Some(PathBuf::from(path));
+    }
+    let config = std::fs::read_to_string(flag_value(args, "--config-path")?).ok()?;
+    let table: toml::Table = config.parse().ok()?;
+    let path = table.get("global")?.get("cache_path")?.as_str()?;
+    Some(PathBuf::from(path))
+}
+
+fn authenticate(args: &[String]) -> i32 {
+    println!("Browse to: https://accounts.spotify.com/authorize?client_id=fake&state=test");
+    // The user takes a moment in the browser
+    std::thread::sleep(Duration::from_millis(200));
+    if std::env::var("FAKE_SPOTIFYD_AUTH").is_ok_and(|v| v == "fail") {
+        eprintln!("{}", LOGIN_FAILED);
+        return 1;
+    }
+    let Some(cache) = cache_path(args) else {
+        eprintln!("no cache path");
+        return 1;
+    };
+    let oauth = cache.join("oauth");
+    let credentials = r#"{"username":"fake","auth_type":1,"auth_data":"dG9rZW4="}"#;
+    match std::fs::create_dir_all(&oauth)
+        .and_then(|_| std::fs::write(oauth.join("credentials.json"), credentials))
+    {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("could not write credentials: {}", e);
+            1
+        }
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// D-Bus interfaces
+// ─────────────────────────────────────────────────────────────
+
+struct Controls;
+
+#[interface(name = "rs.spotifyd.Controls")]
+impl Controls {
+    async fn transfer_playback(&self) {}
+}
+
+struct Root;
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    async fn quit(&self) {
+        if env_flag("FAKE_SPOTIFYD_IGNORE_QUIT") {
+            return;
+        }
+        // Let the reply go out first
+        tokio::spawn(async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            std::process::exit(0);
+        });
+    }
+
+    #[zbus(property)]
+    async fn can_quit(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    async fn identity(&self) -> String {
+        "Fake spotifyd".to_string()
+    }
+}
+
+struct Player;
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    async fn pause(&self) {
+        println!("paused");
+    }
+
+    #[zbus(property)]
+    async fn playback_status(&self) -> String {
+        "Paused".to_string()
+    }
+
+    #[zbus(property)]
+    async fn metadata(&self) -> HashMap<String, zbus::zvariant::OwnedValue> {
+        HashMap::new()
+    }
+}
+
+async fn daemon(args: &[String]) -> i32 {
+    let pid = std::process::id();
+    println!("Connecting as {:?}", flag_value(args, "--device-name"));
+
+    if let Some(after) = env_ms("FAKE_SPOTIFYD_CRASH_AFTER_MS") {
+        tokio::spawn(async move {
+            tokio::time::sleep(after).await;
+            eprintln!("thread 'main' panicked: simulated crash");
+            std::process::exit(101);
+        });
+    }
+
+    if std::env::var("FAKE_SPOTIFYD_AUTH").is_ok_and(|v| v == "fail") {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        eprintln!("{}", LOGIN_FAILED);
+        return 1;
+    }
+
+    let conn = match Connection::session().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            eprintln!("no session bus: {}", e);
+            return 1;
+        }
+    };
+
+    tokio::time::sleep(env_ms("FAKE_SPOTIFYD_CONTROLS_DELAY_MS").unwrap_or_default()).await;
+    let server = conn.object_server();
+    if server.at("/", Controls).await.is_err()
+        || conn.request_name(format!("rs.spotifyd.instance{}", pid)).await.is_err()
+    {
+        return 1;
+    }
+
+    let mpris_delay = std::env::var("FAKE_SPOTIFYD_MPRIS_DELAY_MS").unwrap_or_default();
+    if mpris_delay != "never" {
+        let delay = mpris_delay.parse().map(Duration::from_millis).unwrap_or_default();
+        tokio::time::sleep(delay).await;
+        let path = "/org/mpris/MediaPlayer2";
+        if server.at(path, Root).await.is_err()
+            || server.at(path, Player).await.is_err()
+            || conn
+                .request_name(format!("org.mpris.MediaPlayer2.spotifyd.instance{}", pid))
+                .await
+                .is_err()
+        {
+            return 1;
+        }
+    }
+
+    std::future::pending::<()>().await;
+    0
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if env_flag("FAKE_SPOTIFYD_IGNORE_SIGTERM") {
+        unsafe { libc::signal(libc::SIGTERM, libc::SIG_IGN) };
+    }
+
+    let code = if args.iter().any(|a| a == "--version" || a == "-V") {
+        println!("spotifyd {}", VERSION);
+        0
+    } else if args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}", HELP);
+        0
+    } else if args.iter().any(|a| a == "authenticate") {
+        authenticate(&args)
+    } else if args.iter().any(|a| a == "--no-daemon") {
+        daemon(&args).await
+    } else {
+        eprintln!("only --no-daemon is supported");
+        1
+    };
+    std::process::exit(code);
+}
@@ -0,0 +1,30 @@
+#!/usr/bin/env bash
+
+# Pin the SHA-256 of every release archive in mpris-native/spotifyd-manifest.json.
+# Run after bumping the manifest version; needs curl, sha256sum and node.
+
+set -euo pipefail
+
+MANIFEST="$(dirname "$0")/../mpris-native/spotifyd-manifest.json"
+WORK="$(mktemp -d)"
+trap 'rm -rf "$WORK"' EXIT
+
+for url in $(node -e 'for (const a of require(process.argv[1]).artifacts) console.log(a.url)' "$(realpath "$MANIFEST")"); do
+	echo "Hashing $url"
+	curl -fsSL "$url" -o "$WORK/archive"
+	digest="$(sha256sum "$WORK/archive" | cut -d' ' -f1)"
+	echo "$url $digest" >>"$WORK/digests"
+done
+
+node -e '
+const fs = require("fs");
+const [manifestPath, digestsPath] = process.argv.slice(1);
+const manifest = JSON.parse(fs.readFileSync(manifestPath, "utf8"));
+const digests = Object.fromEntries(
+	fs.readFileSync(digestsPath, "utf8").trim().split("\n").map((l) => l.split(" ")),
+);
+for (const artifact of manifest.artifacts) artifact.sha256 = digests[artifact.url];
+fs.writeFileSync(manifestPath, JSON.stringify(manifest, null, 2) + "\n");
+' "$(realpath "$MANIFEST")" "$WORK/digests"
+
+echo "Pinned $(wc -l <"$WORK/digests") digests in $MANIFEST"
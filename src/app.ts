@@ -32,7 +32,7 @@ import type {
 	KeyEvent,
 } from "./types";
 import type { IMprisService } from "./types/mpris";
-import { calculateLayout, getLogger } from "./utils";
+import { calculateLayout, getLogger, onTerminalFocus } from "./utils";
 import { getMprisService } from "./services";
 
 // Import controllers
@@ -564,6 +564,13 @@ export class App {
 		keyInput.on("keypress", (key: KeyEvent) => {
 			this.inputHandler.handleKeyPress(key);
 		});
+
+		// gsd sends media keys to whoever grabbed last, so take them back on focus
+		onTerminalFocus(() => {
+			this.mpris.regrabMediaKeys?.().catch((error) => {
+				logger.debug("Could not regrab media keys:", error);
+			});
+		});
 	}
 
 	/**
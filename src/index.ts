@@ -6,9 +6,49 @@ import {
 } from "./container";
 import { getLogger } from "./utils";
 import { getSpotifydInstaller } from "./services/SpotifydInstaller";
+import { getSpotifydService } from "./services/SpotifydService";
 
 const logger = getLogger("Main");
 
+/**
+ * Native install failures that the TypeScript installer can still handle:
+ * the native module isn't built, or this release has no pinned checksum
+ */
+const NATIVE_INSTALL_UNAVAILABLE = /Failed to initialize native|no pinned checksum/;
+
+/**
+ * Print install progress as a simple progress bar
+ */
+function showProgress(message: string, percent?: number): void {
+	if (percent !== undefined) {
+		const filled = Math.floor(percent / 5);
+		const empty = 20 - filled;
+		const bar = "[" + "=".repeat(filled) + " ".repeat(empty) + "]";
+		process.stdout.write(`\r${bar} ${percent}% ${message}`.padEnd(60));
+	} else {
+		console.log(`  ${message}`);
+	}
+}
+
+/**
+ * Install spotifyd with the native installer, which verifies the archive
+ * against the pinned manifest, falling back to the TypeScript installer
+ */
+async function installSpotifyd(): Promise<{
+	success: boolean;
+	message: string;
+	version?: string;
+}> {
+	const result = await getSpotifydService().install(undefined, (progress) =>
+		showProgress(progress.stage, progress.percent),
+	);
+	if (result.success || !NATIVE_INSTALL_UNAVAILABLE.test(result.message)) {
+		return result;
+	}
+	logger.warn(`Native install unavailable (${result.message}), using fallback`);
+	return getSpotifydInstaller().install(showProgress);
+}
+
 /**
  * First-run setup - ensures spotifyd is installed before starting the TUI
  * This runs on every startup but only does work if spotifyd is missing
@@ -34,17 +74,7 @@ async function ensureSpotifydInstalled(): Promise<boolean> {
 		console.log("spotifyd not found. Downloading...");
 		console.log("");
 
-		const result = await installer.install((message, percent) => {
-			if (percent !== undefined) {
-				// Simple progress bar
-				const filled = Math.floor(percent / 5);
-				const empty = 20 - filled;
-				const bar = "[" + "=".repeat(filled) + " ".repeat(empty) + "]";
-				process.stdout.write(`\r${bar} ${percent}% ${message}`.padEnd(60));
-			} else {
-				console.log(`  ${message}`);
-			}
-		});
+		const result = await installSpotifyd();
 
 		console.log(""); // New line after progress
 
@@ -75,16 +105,7 @@ async function ensureSpotifydInstalled(): Promise<boolean> {
 		console.log("Attempting repair...");
 		console.log("");
 
-		const result = await installer.repair(verification, (message, percent) => {
-			if (percent !== undefined) {
-				const filled = Math.floor(percent / 5);
-				const empty = 20 - filled;
-				const bar = "[" + "=".repeat(filled) + " ".repeat(empty) + "]";
-				process.stdout.write(`\r${bar} ${percent}% ${message}`.padEnd(60));
-			} else {
-				console.log(`  ${message}`);
-			}
-		});
+		const result = await installer.repair(verification, showProgress);
 
 		console.log("");
 
@@ -11,6 +11,37 @@ export interface PlaybackState {
 	shuffle: boolean;
 	repeat: RepeatMode;
 	track: TrackInfo | null;
+	/** Running sleep timer, if any */
+	sleepTimer: SleepTimerState | null;
+}
+
+/** How a volume fade moves between its start and target */
+export type FadeCurve = "Linear" | "EaseIn" | "EaseOut" | "SCurve";
+
+/**
+ * When a sleep timer pauses playback. EndOfAlbum pauses once a track from
+ * another album starts, since MPRIS doesn't show the queue; it never fades.
+ */
+export type SleepTimerMode = "Duration" | "EndOfTrack" | "EndOfAlbum" | "AfterTracks";
+
+export interface SleepTimerOptions {
+	mode: SleepTimerMode;
+	/** Required for Duration */
+	durationMs?: number;
+	/** Required for AfterTracks */
+	tracks?: number;
+	/** Fade the volume out over this long before pausing (ignored for EndOfAlbum) */
+	fadeOutMs?: number;
+}
+
+export interface SleepTimerState {
+	mode: SleepTimerMode;
+	/** When playback will pause (ms since the epoch), once known */
+	endsAtMs: number | null;
+	/** Track changes left, for the track-based modes */
+	tracksRemaining: number | null;
+	fadeOutMs: number;
+	fading: boolean;
 }
 
 export enum RepeatMode {
@@ -27,17 +58,114 @@ export interface TrackInfo {
 	uri: string;
 }
 
+/** Metadata layered over spotifyd's on the TUI's own MPRIS player */
+export interface MprisExtraMetadata {
+	/** Only apply while this track (TrackInfo.uri) is current */
+	trackUri?: string;
+	/** Cached album art on disk */
+	artPath?: string;
+	/** Current lyrics line */
+	lyricsLine?: string;
+	/** open.spotify.com link */
+	url?: string;
+}
+
+export interface NotifierOptions {
+	/** Minimum time between notifications (default 2000); bursts show only the last track */
+	minIntervalMs?: number;
+	/** How long notifications stay up; -1 (default) leaves it to the server */
+	timeoutMs?: number;
+	/** Album art named `<track id>.jpg` or `.png` (default ~/.spotify-tui/art) */
+	artCacheDir?: string;
+	/** Start with notifications suppressed */
+	doNotDisturb?: boolean;
+}
+
+export interface HistoryOptions {
+	/** History file (default ~/.spotify-tui/history.jsonl) */
+	path?: string;
+	/** Listening time for a track to count as played (default 30000, or half of shorter tracks) */
+	minPlayedMs?: number;
+}
+
+/** One play of a track */
+export interface HistoryEntry {
+	title: string;
+	artist: string;
+	album: string;
+	uri: string;
+	/** When the track started (ms since the epoch) */
+	startedAtMs: number;
+	endedAtMs: number;
+	durationMs: number;
+	/** Time actually spent playing, excluding pauses */
+	playedMs: number;
+	/** Left well before the end */
+	skipped: boolean;
+	seekCount: number;
+}
+
+/** Last 24 hours, 7 days, 30 days, 365 days, or everything */
+export type HistoryRange = "Day" | "Week" | "Month" | "Year" | "All";
+
+/** A track or artist ranked by plays */
+export interface HistoryTopEntry {
+	/** Track title, or artist name */
+	name: string;
+	/** The track's artist; null when ranking artists */
+	artist: string | null;
+	plays: number;
+	playedMs: number;
+}
+
+export type SpotifydAuthState =
+	| "Unknown"
+	| "Pending"
+	| "NoCredentials"
+	| "Failed"
+	| "Authenticated";
+
 export interface SpotifydStatus {
 	running: boolean;
 	pid: number | null;
 	authenticated: boolean;
+	authState: SpotifydAuthState;
+	/** Log line explaining a failed login */
+	authError: string | null;
+	/** Full spotifyd command line, argv[0] included */
+	argv: string[];
 }
 
 export interface SpotifydConfig {
+	/** Path to the spotifyd binary */
+	binaryPath?: string;
+	/** Existing spotifyd config file; generated from the fields below if unset */
 	configPath?: string;
 	username?: string;
 	password?: string;
 	deviceName?: string;
+	bitrate?: 96 | 160 | 320;
+	backend?: string;
+	deviceType?: string;
+	cachePath?: string;
+	/** With configPath set, only true takes effect: spotifyd can't switch it off over that file */
+	volumeNormalisation?: boolean;
+	initialVolume?: number;
+	/** With configPath set, only true takes effect, as for volumeNormalisation */
+	autoplay?: boolean;
+	device?: string;
+	mixer?: string;
+	volumeController?: string;
+	zeroconfPort?: number;
+	useMpris?: boolean;
+	dbusType?: "session" | "system";
+	/** Raw arguments appended after all others */
+	extraArgs?: string[];
+	/** Environment variables set for spotifyd, overriding inherited ones */
+	env?: Record<string, string>;
+	/** Inherited environment variables unset for spotifyd */
+	envRemove?: string[];
+	workingDir?: string;
 }
 
 /**
@@ -47,6 +175,12 @@ export interface SpotifydConfig {
 export class MprisBridgeService {
 	private mpris: any = null;
 	private spotifyd: any = null;
+	private ipc: any = null;
+	private mprisServer: any = null;
+	private notifier: any = null;
+	private mediaKeys: any = null;
+	private history: any = null;
+	private native: any = null;
 	private stateCallbacks: Set<(state: PlaybackState) => void> = new Set();
 	private statusCallbacks: Set<(status: SpotifydStatus) => void> = new Set();
 	private isInitialized = false;
@@ -62,6 +196,7 @@ export class MprisBridgeService {
 			// Dynamically import the native module
 			// @ts-ignore - Native module will be available after build
 			const native = await import("../../mpris-native/index.js");
+			this.native = native;
 
 			// Initialize spotifyd supervisor
 			this.spotifyd = new native.SpotifydSupervisor();
@@ -178,6 +313,40 @@ export class MprisBridgeService {
 		await this.mpris.setVolume(volume);
 	}
 
+	/** Change volume relative to the player's live volume; returns the new volume */
+	async adjustVolume(delta: number): Promise<number> {
+		await this.ensureInitialized();
+		if (!this.mpris) {
+			throw new Error("MPRIS controller not initialized");
+		}
+		return this.mpris.adjustVolume(delta);
+	}
+
+	/** Fade to `target`; resolves to false if another volume change cut it short */
+	async fadeTo(target: number, durationMs: number, curve?: FadeCurve): Promise<boolean> {
+		await this.ensureInitialized();
+		if (!this.mpris) {
+			throw new Error("MPRIS controller not initialized");
+		}
+		return this.mpris.fadeTo(target, durationMs, curve);
+	}
+
+	async mute(): Promise<boolean> {
+		await this.ensureInitialized();
+		if (!this.mpris) {
+			throw new Error("MPRIS controller not initialized");
+		}
+		return this.mpris.mute();
+	}
+
+	async unmute(): Promise<boolean> {
+		await this.ensureInitialized();
+		if (!this.mpris) {
+			throw new Error("MPRIS controller not initialized");
+		}
+		return this.mpris.unmute();
+	}
+
 	async setShuffle(shuffle: boolean): Promise<void> {
 		await this.ensureInitialized();
 		if (!this.mpris) {
@@ -194,6 +363,23 @@ export class MprisBridgeService {
 		await this.mpris.setRepeat(repeat);
 	}
 
+	// ─────────────────────────────────────────────────────────────
+	// Sleep Timer
+	// ─────────────────────────────────────────────────────────────
+
+	/** Pause later; the countdown shows up in PlaybackState.sleepTimer */
+	async startSleepTimer(options: SleepTimerOptions): Promise<SleepTimerState> {
+		await this.ensureInitialized();
+		if (!this.mpris) {
+			throw new Error("MPRIS controller not initialized");
+		}
+		return this.mpris.startSleepTimer(options);
+	}
+
+	async cancelSleepTimer(): Promise<boolean> {
+		return (await this.mpris?.cancelSleepTimer()) ?? false;
+	}
+
 	// ─────────────────────────────────────────────────────────────
 	// State Management
 	// ─────────────────────────────────────────────────────────────
@@ -219,6 +405,124 @@ export class MprisBridgeService {
 		};
 	}
 
+	// ─────────────────────────────────────────────────────────────
+	// IPC Server
+	// ─────────────────────────────────────────────────────────────
+
+	/**
+	 * Serve JSON-RPC on a Unix socket for status bar widgets and scripts.
+	 * Returns the socket path ($XDG_RUNTIME_DIR/spotify-tui.sock by default).
+	 */
+	async startIpcServer(path?: string): Promise<string> {
+		await this.ensureInitialized();
+		if (!this.ipc) {
+			this.ipc = new this.native.IpcServer(this.mpris, this.spotifyd);
+		}
+		return this.ipc.start(path);
+	}
+
+	stopIpcServer(): boolean {
+		return this.ipc?.stop() ?? false;
+	}
+
+	// ─────────────────────────────────────────────────────────────
+	// MPRIS Server
+	// ─────────────────────────────────────────────────────────────
+
+	/**
+	 * Publish the TUI as an MPRIS player for desktop widgets and playerctl.
+	 * Returns the bus name (org.mpris.MediaPlayer2.spotify_tui normally).
+	 */
+	async startMprisServer(): Promise<string> {
+		await this.ensureInitialized();
+		if (!this.mprisServer) {
+			this.mprisServer = new this.native.MprisServer(this.mpris);
+		}
+		return this.mprisServer.start();
+	}
+
+	async stopMprisServer(): Promise<boolean> {
+		return (await this.mprisServer?.stop()) ?? false;
+	}
+
+	/** Album art path, lyrics line etc. for the current track */
+	async setMprisExtraMetadata(extra: MprisExtraMetadata): Promise<void> {
+		await this.mprisServer?.setExtraMetadata(extra);
+	}
+
+	// ─────────────────────────────────────────────────────────────
+	// Notifications
+	// ─────────────────────────────────────────────────────────────
+
+	/** Show a desktop notification whenever the track changes */
+	async startNotifications(options?: NotifierOptions): Promise<void> {
+		await this.ensureInitialized();
+		if (!this.notifier) {
+			this.notifier = new this.native.Notifier(this.mpris, options);
+		}
+		await this.notifier.start();
+	}
+
+	stopNotifications(): boolean {
+		return this.notifier?.stop() ?? false;
+	}
+
+	setDoNotDisturb(enabled: boolean): void {
+		this.notifier?.setDoNotDisturb(enabled);
+	}
+
+	// ─────────────────────────────────────────────────────────────
+	// Media Keys
+	// ─────────────────────────────────────────────────────────────
+
+	/** Grab media keys from gnome-settings-daemon. Throws if it isn't running. */
+	async startMediaKeys(): Promise<void> {
+		await this.ensureInitialized();
+		if (!this.mediaKeys) {
+			this.mediaKeys = new this.native.MediaKeys(this.mpris);
+		}
+		await this.mediaKeys.start();
+	}
+
+	/** Call when the TUI regains focus so media keys come back to it */
+	async regrabMediaKeys(): Promise<boolean> {
+		return (await this.mediaKeys?.regrab()) ?? false;
+	}
+
+	async stopMediaKeys(): Promise<boolean> {
+		return (await this.mediaKeys?.stop()) ?? false;
+	}
+
+	// ─────────────────────────────────────────────────────────────
+	// Listening History
+	// ─────────────────────────────────────────────────────────────
+
+	/** Record plays to the local history file */
+	async startHistory(options?: HistoryOptions): Promise<void> {
+		await this.ensureInitialized();
+		if (!this.history) {
+			this.history = new this.native.HistoryRecorder(this.mpris, options);
+		}
+		await this.history.start();
+	}
+
+	stopHistory(): boolean {
+		return this.history?.stop() ?? false;
+	}
+
+	/** Plays within `range`, newest first */
+	async getHistory(range: HistoryRange, limit: number): Promise<HistoryEntry[]> {
+		return (await this.history?.getHistory(range, limit)) ?? [];
+	}
+
+	async getTopTracks(range: HistoryRange, limit: number): Promise<HistoryTopEntry[]> {
+		return (await this.history?.getTopTracks(range, limit)) ?? [];
+	}
+
+	async getTopArtists(range: HistoryRange, limit: number): Promise<HistoryTopEntry[]> {
+		return (await this.history?.getTopArtists(range, limit)) ?? [];
+	}
+
 	// ─────────────────────────────────────────────────────────────
 	// Cleanup
 	// ─────────────────────────────────────────────────────────────
@@ -226,6 +530,11 @@ export class MprisBridgeService {
 	async cleanup(): Promise<void> {
 		this.stateCallbacks.clear();
 		this.statusCallbacks.clear();
+		this.stopIpcServer();
+		await this.stopMprisServer();
+		this.stopNotifications();
+		this.stopHistory();
+		await this.stopMediaKeys();
 
 		if (this.spotifyd) {
 			try {
@@ -46,8 +46,10 @@ interface NativeSpotifydStatus {
  * backed by the Rust native module for better performance
  */
 export class NativeMprisAdapter {
+	private native: any = null;
 	private mpris: any = null;
 	private spotifyd: any = null;
+	private mediaKeys: any = null;
 	private connected: boolean = false;
 	private isInitialized: boolean = false;
 	private lastState: NativePlaybackState | null = null;
@@ -62,6 +64,7 @@ export class NativeMprisAdapter {
 			// Dynamically import the native module
 			// @ts-ignore - Native module will be available after build
 			const native = await import("../../mpris-native/index.js");
+			this.native = native;
 
 			// Initialize MPRIS controller
 			this.mpris = new native.MprisController();
@@ -128,6 +131,8 @@ export class NativeMprisAdapter {
 			await this.mpris.connect();
 			this.connected = true;
 			logger.info("Connected to MPRIS via native module");
+			// Not awaited: media keys are optional and gsd may be slow to answer
+			this.startMediaKeys();
 			return true;
 		} catch (error) {
 			logger.error("Failed to connect to MPRIS:", error);
@@ -141,7 +146,9 @@ export class NativeMprisAdapter {
 	 */
 	async disconnect(): Promise<void> {
 		this.connected = false;
-		// Native module handles cleanup automatically
+		this.mediaKeys?.stop().catch(() => {});
+		this.mediaKeys = null;
+		// Native module handles the rest of the cleanup automatically
 	}
 
 	/**
@@ -352,8 +359,7 @@ export class NativeMprisAdapter {
 	async volumeUp(amount: number = 0.05): Promise<void> {
 		if (!this.mpris) return;
 		try {
-			const current = await this.getVolume();
-			await this.setVolume(current + amount);
+			await this.mpris.adjustVolume(amount);
 		} catch {
 			// Volume control can fail - silently ignore
 		}
@@ -362,8 +368,7 @@ export class NativeMprisAdapter {
 	async volumeDown(amount: number = 0.05): Promise<void> {
 		if (!this.mpris) return;
 		try {
-			const current = await this.getVolume();
-			await this.setVolume(current - amount);
+			await this.mpris.adjustVolume(-amount);
 		} catch {
 			// Volume control can fail - silently ignore
 		}
@@ -406,6 +411,32 @@ export class NativeMprisAdapter {
 		}
 	}
 
+	// ─────────────────────────────────────────────────────────────
+	// Media Keys
+	// ─────────────────────────────────────────────────────────────
+
+	/**
+	 * Grab media keys from gnome-settings-daemon, if it is running
+	 */
+	private async startMediaKeys(): Promise<void> {
+		if (this.mediaKeys || !this.native) return;
+		const mediaKeys = new this.native.MediaKeys(this.mpris);
+		try {
+			await mediaKeys.start();
+			this.mediaKeys = mediaKeys;
+			logger.info("Grabbed media keys");
+		} catch (error) {
+			logger.debug("Media keys unavailable:", error);
+		}
+	}
+
+	/**
+	 * Grab media keys again so they come back to us, e.g. on terminal focus
+	 */
+	async regrabMediaKeys(): Promise<boolean> {
+		return (await this.mediaKeys?.regrab()) ?? false;
+	}
+
 	// ─────────────────────────────────────────────────────────────
 	// Spotifyd Management
 	// ─────────────────────────────────────────────────────────────
@@ -13,10 +13,11 @@
 import { existsSync, readFileSync } from "node:fs";
 import { homedir } from "node:os";
 import { join } from "node:path";
-import { spawn, spawnSync } from "node:child_process";
+import { spawnSync } from "node:child_process";
 import open from "open";
 import { getLogger } from "../utils";
 import { getSpotifydInstaller } from "./SpotifydInstaller";
+import type { SpotifydAuthState, SpotifydConfig } from "./MprisBridgeService";
 
 const logger = getLogger("SpotifydService");
 
@@ -56,6 +57,14 @@ export interface SpotifydStartResult {
 	message: string;
 	pid?: number;
 	adopted: boolean;
+	/** Furthest startup stage confirmed (Spawned, ControlsRegistered, MprisRegistered) */
+	stage?: string;
+	/** Milliseconds from spawn until spotifyd registered on D-Bus */
+	readyMs?: number;
+	/** PIDs of stale spotifyd processes killed before starting */
+	killed?: number[];
+	/** spotifyd processes the kill policy left alone, with the reason */
+	skipped?: { pid: number; reason: string }[];
 }
 
 /**
@@ -76,10 +85,51 @@ interface NativeSpotifydSupervisor {
 		message: string;
 		pid?: number;
 		adopted: boolean;
+		stage?: string;
+		readyMs?: number;
+		killed?: number[];
+		skipped?: { pid: number; reason: string }[];
 	}>;
 	start(): Promise<void>;
-	stop(force?: boolean): Promise<void>;
-	getStatus(): { running: boolean; pid?: number; authenticated: boolean };
+	stop(force?: boolean, policy?: SpotifydStopPolicy): Promise<SpotifydStopResult>;
+	restart(newConfig?: SpotifydConfig): Promise<SpotifydRestartResult>;
+	/** Takes a native MprisController to reconnect after restarts */
+	linkController(controller: unknown): void;
+	getStatus(): {
+		running: boolean;
+		pid?: number;
+		authenticated: boolean;
+		authState: SpotifydAuthState;
+		authError?: string;
+	};
+	checkAuth(): Promise<{
+		running: boolean;
+		pid?: number;
+		authenticated: boolean;
+		authState: SpotifydAuthState;
+		authError?: string;
+	}>;
+	authenticate(
+		options?: { timeoutMs?: number; oauthPort?: number },
+		onProgress?: (progress: {
+			kind: "started" | "url-found" | "waiting" | "credentials-written";
+			url?: string;
+			path?: string;
+		}) => void,
+	): Promise<{
+		success: boolean;
+		message: string;
+		authUrl?: string;
+		credentialsPath?: string;
+		cancelled: boolean;
+	}>;
+	cancelAuthenticate(): boolean;
+	detectSpotifyd(): Promise<SpotifydDetection>;
+	install(
+		options?: InstallOptions,
+		onProgress?: (progress: InstallProgress) => void,
+	): Promise<InstallResult>;
+	rollbackInstall(installDir?: string): Promise<InstallResult>;
 	isRunning(): Promise<boolean>;
 	isHealthy(): Promise<boolean>;
 	getPid(): Promise<number | null>;
@@ -88,10 +138,94 @@ interface NativeSpotifydSupervisor {
 			running: boolean;
 			pid?: number;
 			authenticated: boolean;
+			authState: SpotifydAuthState;
+			authError?: string;
 		}) => void,
 	): void;
 }
 
+export interface SpotifydCandidate {
+	path: string;
+	source: "Config" | "Environment" | "Downloaded" | "Path";
+	exists: boolean;
+	executable: boolean;
+	version?: string;
+	features?: { authenticate: boolean; mpris: boolean; controls: boolean };
+	/** Why this candidate can't be used */
+	error?: string;
+}
+
+export interface SpotifydDetection {
+	/** First candidate that runs and reports a version */
+	selected?: SpotifydCandidate;
+	candidates: SpotifydCandidate[];
+}
+
+/** How stop() escalates: pause, MPRIS Quit, SIGTERM, SIGKILL */
+export interface SpotifydStopPolicy {
+	/** Pause playback first (default: true) */
+	pause?: boolean;
+	/** Call MPRIS Quit if spotifyd reports CanQuit (default: true) */
+	mprisQuit?: boolean;
+	quitTimeoutMs?: number;
+	termTimeoutMs?: number;
+}
+
+export interface SpotifydStopResult {
+	stopped: boolean;
+	pid?: number;
+	/** Step that terminated the process */
+	stage?: "AlreadyExited" | "MprisQuit" | "Sigterm" | "Sigkill";
+	paused: boolean;
+	elapsedMs: number;
+	message: string;
+}
+
+export interface SpotifydRestartResult {
+	/** spotifyd came back up with the requested configuration */
+	success: boolean;
+	message: string;
+	stop: SpotifydStopResult;
+	start: {
+		success: boolean;
+		message: string;
+		pid?: number;
+		adopted: boolean;
+	};
+	/** The new configuration failed and the previous one was restored */
+	rolledBack: boolean;
+	/** Unset if no controller is linked */
+	controllerReconnected?: boolean;
+}
+
+export interface InstallOptions {
+	/** Release archive (.tar.gz) to download */
+	url?: string;
+	/** Local .tar.gz archive to install from */
+	archivePath?: string;
+	/** Expected SHA-256 in hex, instead of the pinned manifest's */
+	sha256?: string;
+	/** Defaults to ~/.spotify-tui/bin */
+	installDir?: string;
+}
+
+export interface InstallProgress {
+	stage: "downloading" | "verifying" | "extracting" | "installing" | "checking";
+	bytes: number;
+	totalBytes?: number;
+	percent?: number;
+}
+
+export interface InstallResult {
+	success: boolean;
+	message: string;
+	path?: string;
+	version?: string;
+	sha256?: string;
+	/** Binary kept for rollbackInstall() */
+	previousPath?: string;
+}
+
 /**
  * SpotifydService - manages spotifyd daemon via Rust native module
  */
@@ -148,7 +282,7 @@ export class SpotifydService {
 	}
 
 	// ─────────────────────────────────────────────────────────────
-	// Installation & Authentication (stays in TypeScript)
+	// Installation & Authentication
 	// ─────────────────────────────────────────────────────────────
 
 	/**
@@ -221,107 +355,108 @@ export class SpotifydService {
 			};
 		}
 
-		// Check version supports authenticate command (0.4.0+)
-		const version = this.getVersion();
-		if (version) {
-			const match = version.match(/(\d+)\.(\d+)\.(\d+)/);
-			if (match) {
-				const major = parseInt(match[1], 10);
-				const minor = parseInt(match[2], 10);
-				if (major === 0 && minor < 4) {
-					return {
-						success: false,
-						message: `spotifyd ${version} doesn't support OAuth. Need version 0.4.0+`,
-					};
-				}
-			}
+		// Version support (0.4.0+ for OAuth) is checked natively
+		if (!(await this.initialize()) || !this.supervisor) {
+			return {
+				success: false,
+				message: "Failed to initialize native spotifyd supervisor",
+			};
 		}
 
 		onProgress?.("Starting authentication...");
 
-		return new Promise((resolve) => {
-			try {
-				const proc = spawn(this.binaryPath, ["authenticate"], {
-					stdio: ["ignore", "pipe", "pipe"],
-				});
-
-				let authUrl: string | undefined;
-				let stdout = "";
-				let stderr = "";
-
-				proc.stdout?.on("data", (data: Buffer) => {
-					const text = data.toString();
-					stdout += text;
-
-					// Look for the auth URL
-					const urlMatch = text.match(/https:\/\/accounts\.spotify\.com[^\s]+/);
-					if (urlMatch) {
-						authUrl = urlMatch[0];
-						onProgress?.("Opening browser for authentication...", authUrl);
-
-						// Auto-open browser
-						open(authUrl).catch(() => {
-							onProgress?.("Please open this URL manually:", authUrl);
-						});
-					}
-
-					// Check for success message
-					if (
-						text.includes("Successfully authenticated") ||
-						text.includes("Credentials saved")
-					) {
-						onProgress?.("Authentication successful!");
+		try {
+			const result = await this.supervisor.authenticate(
+				{ timeoutMs: 5 * 60 * 1000 },
+				(progress) => {
+					switch (progress.kind) {
+						case "url-found":
+							onProgress?.("Opening browser for authentication...", progress.url);
+							open(progress.url!).catch(() => {
+								onProgress?.("Please open this URL manually:", progress.url);
+							});
+							break;
+						case "waiting":
+							onProgress?.("Waiting for login in browser...", progress.url);
+							break;
+						case "credentials-written":
+							onProgress?.("Authentication successful!");
+							break;
 					}
-				});
+				},
+			);
+			return {
+				success: result.success,
+				message: result.success
+					? "Authentication successful! Credentials saved."
+					: result.message,
+				authUrl: result.authUrl,
+			};
+		} catch (error) {
+			return {
+				success: false,
+				message: error instanceof Error ? error.message : "Unknown error",
+			};
+		}
+	}
 
-				proc.stderr?.on("data", (data: Buffer) => {
-					stderr += data.toString();
-				});
+	/**
+	 * Probe every spotifyd binary candidate for version and feature support
+	 */
+	async detect(): Promise<SpotifydDetection | null> {
+		if (!(await this.initialize()) || !this.supervisor) return null;
+		return this.supervisor.detectSpotifyd();
+	}
 
-				proc.on("close", (code) => {
-					if (code === 0 || this.isAuthenticated()) {
-						resolve({
-							success: true,
-							message: "Authentication successful! Credentials saved.",
-							authUrl,
-						});
-					} else {
-						resolve({
-							success: false,
-							message: stderr || stdout || "Authentication failed",
-							authUrl,
-						});
-					}
-				});
+	/**
+	 * Install spotifyd natively, verifying the archive's SHA-256.
+	 * The replaced binary is kept for rollbackInstall().
+	 */
+	async install(
+		options?: InstallOptions,
+		onProgress?: (progress: InstallProgress) => void,
+	): Promise<InstallResult> {
+		if (!(await this.initialize()) || !this.supervisor) {
+			return {
+				success: false,
+				message: "Failed to initialize native spotifyd supervisor",
+			};
+		}
+		try {
+			return await this.supervisor.install(options, onProgress);
+		} catch (error) {
+			return {
+				success: false,
+				message: error instanceof Error ? error.message : "Unknown error",
+			};
+		}
+	}
 
-				proc.on("error", (err) => {
-					resolve({
-						success: false,
-						message: `Failed to run authenticate: ${err.message}`,
-					});
-				});
+	/**
+	 * Restore the spotifyd binary replaced by the last install()
+	 */
+	async rollbackInstall(installDir?: string): Promise<InstallResult> {
+		if (!(await this.initialize()) || !this.supervisor) {
+			return {
+				success: false,
+				message: "Failed to initialize native spotifyd supervisor",
+			};
+		}
+		try {
+			return await this.supervisor.rollbackInstall(installDir);
+		} catch (error) {
+			return {
+				success: false,
+				message: error instanceof Error ? error.message : "Unknown error",
+			};
+		}
+	}
 
-				// Timeout after 5 minutes
-				setTimeout(
-					() => {
-						if (proc.exitCode === null) {
-							proc.kill();
-							resolve({
-								success: false,
-								message: "Authentication timed out after 5 minutes",
-								authUrl,
-							});
-						}
-					},
-					5 * 60 * 1000,
-				);
-			} catch (error) {
-				resolve({
-					success: false,
-					message: error instanceof Error ? error.message : "Unknown error",
-				});
-			}
-		});
+	/**
+	 * Cancel a running authenticate()
+	 */
+	cancelAuthenticate(): boolean {
+		return this.supervisor?.cancelAuthenticate() ?? false;
 	}
 
 	// ─────────────────────────────────────────────────────────────
@@ -374,22 +509,53 @@ export class SpotifydService {
 
 	/**
 	 * Stop spotifyd
-	 * @param force - If true, kill any spotifyd. If false, only kill if we spawned/adopted it.
+	 * @param force - If true, also stop an adopted spotifyd, or an untracked one the kill policy
+	 *   allows. If false, only stop one we spawned.
 	 */
-	async stop(force = false): Promise<void> {
+	async stop(
+		force = false,
+		policy?: SpotifydStopPolicy,
+	): Promise<SpotifydStopResult | null> {
 		if (!this.supervisor) {
 			logger.warn("SpotifydService not initialized, nothing to stop");
-			return;
+			return null;
 		}
 
 		try {
-			await this.supervisor.stop(force);
-			logger.info("spotifyd stopped");
+			const result = await this.supervisor.stop(force, policy);
+			logger.info(`spotifyd stop: ${result.message}`);
+			return result;
 		} catch (error) {
 			logger.error("Failed to stop spotifyd:", error);
+			return null;
 		}
 	}
 
+	/**
+	 * Restart spotifyd, optionally with new configuration (e.g. device name
+	 * or bitrate). Falls back to the previous configuration if the new one
+	 * fails to come up.
+	 */
+	async restart(newConfig?: SpotifydConfig): Promise<SpotifydRestartResult | null> {
+		if (!(await this.initialize()) || !this.supervisor) return null;
+
+		try {
+			const result = await this.supervisor.restart(newConfig);
+			logger.info(`spotifyd restart: ${result.message}`);
+			return result;
+		} catch (error) {
+			logger.error("Failed to restart spotifyd:", error);
+			return null;
+		}
+	}
+
+	/**
+	 * Reconnect a native MprisController whenever restart() replaces spotifyd
+	 */
+	linkController(controller: unknown): void {
+		this.supervisor?.linkController(controller);
+	}
+
 	/**
 	 * Check if spotifyd process is running
 	 */
@@ -102,4 +102,7 @@ export interface IMprisService {
 	cycleLoopStatus(currentStatus?: LoopStatus): Promise<LoopStatus>;
 	seekForward(ms?: number): Promise<void>;
 	seekBackward(ms?: number): Promise<void>;
+
+	// Media keys (native module only): grab them again when the TUI regains focus
+	regrabMediaKeys?(): Promise<boolean>;
 }
@@ -1,4 +1,9 @@
-export { calculateLayout, cleanupTerminal, getTerminalSize } from "./terminal";
+export {
+	calculateLayout,
+	cleanupTerminal,
+	getTerminalSize,
+	onTerminalFocus,
+} from "./terminal";
 export {
 	Logger,
 	getLogger,
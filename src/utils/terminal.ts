@@ -27,6 +27,11 @@ const ESCAPE_SEQUENCES = {
 	// Screen buffer
 	ALT_SCREEN_OFF: "\x1b[?1049l",
 
+	// Focus reporting: the terminal sends FOCUS_IN when its window gains focus
+	FOCUS_REPORTING_ON: "\x1b[?1004h",
+	FOCUS_REPORTING_OFF: "\x1b[?1004l",
+	FOCUS_IN: "\x1b[I",
+
 	// Clear and reset
 	CLEAR_SCREEN: "\x1b[2J\x1b[H",
 	RESET_ATTRS: "\x1b[0m",
@@ -64,11 +69,31 @@ function resetTerminalState(): void {
 	}
 }
 
+/**
+ * Call `handler` whenever the terminal window regains focus.
+ * Returns a function that stops listening.
+ */
+export function onTerminalFocus(handler: () => void): () => void {
+	const onData = (data: Buffer | string) => {
+		if (data.toString().includes(ESCAPE_SEQUENCES.FOCUS_IN)) {
+			handler();
+		}
+	};
+	process.stdin.on("data", onData);
+	process.stdout.write(ESCAPE_SEQUENCES.FOCUS_REPORTING_ON);
+
+	return () => {
+		process.stdin.removeListener("data", onData);
+		process.stdout.write(ESCAPE_SEQUENCES.FOCUS_REPORTING_OFF);
+	};
+}
+
 /**
  * Comprehensive terminal cleanup
  * Call this before exiting the application
  */
 export function cleanupTerminal(): void {
+	process.stdout.write(ESCAPE_SEQUENCES.FOCUS_REPORTING_OFF);
 	disableMouseTracking();
 	resetTerminalVisuals();
 	resetTerminalState();